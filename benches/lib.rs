@@ -47,6 +47,24 @@ fn bench_1_t() {
     assert!(false);
 }
 
+#[bench]
+fn bench_sweep(b: &mut Bencher) {
+    b.iter(|| black_box(bench_3()));
+}
+
+/// Rest 100k orders over 100 levels then sweep them with one order
+pub fn bench_3() {
+    let mut lob = Market::default();
+    for i in 0..100_000_u32 {
+        black_box(assert!(lob
+            .submit_order(i, 1, (i / 1_000) as f32, OrderSide::Sell)
+            .is_ok()));
+    }
+    black_box(assert!(lob
+        .submit_order(0, 100_000, 100.0, OrderSide::Buy)
+        .is_ok()));
+}
+
 pub fn bench_2() {
     use rand::Rng;
 
@@ -0,0 +1,195 @@
+//! Execution algorithms
+//!
+//! Algorithms are `sim::Agent`s which schedule child limit orders by the market clock, run them
+//! in a `sim::Simulation` alongside other agents:
+//! - `Twap` splits an order into equal slices sent at even intervals
+//! - `Participation` trades a fixed fraction of the market's traded volume
+use std::time::Duration;
+
+use crate::{sim::Agent, Command, Fill, Market, OrderRequest, OrderSide};
+
+/// The parent order an algorithm works
+#[derive(PartialEq, Clone, Debug)]
+pub struct ParentOrder {
+    pub trader_id: u32,
+    pub side: OrderSide,
+    pub amount: u32,
+    /// Limit price of every child order
+    pub price: f32,
+}
+
+impl ParentOrder {
+    fn child(&self, amount: u32) -> Command {
+        Command::Submit(OrderRequest::new(
+            self.trader_id,
+            amount,
+            self.price,
+            self.side.clone(),
+        ))
+    }
+}
+
+/// Time weighted execution in `slices` equal child orders over `duration` from `start`
+#[derive(PartialEq, Clone, Debug)]
+pub struct Twap {
+    pub parent: ParentOrder,
+    /// Market time of the first slice in nanoseconds
+    pub start: u64,
+    pub duration: Duration,
+    pub slices: u32,
+    /// Slices sent so far
+    sent: u32,
+}
+
+impl Twap {
+    pub fn new(parent: ParentOrder, start: u64, duration: Duration, slices: u32) -> Self {
+        Twap {
+            parent,
+            start,
+            duration,
+            slices: slices.max(1),
+            sent: 0,
+        }
+    }
+}
+
+impl Agent for Twap {
+    fn on_tick(&mut self, _market: &Market, now: u64) -> Vec<Command> {
+        if now < self.start {
+            return vec![];
+        }
+        let interval = self.duration.as_nanos() as u64 / self.slices as u64;
+        let elapsed = now - self.start;
+        let due = (elapsed.checked_div(interval).unwrap_or(u64::MAX))
+            .saturating_add(1)
+            .min(self.slices as u64) as u32;
+        let mut children = vec![];
+        while self.sent < due {
+            // the last slice carries the remainder
+            let mut amount = self.parent.amount / self.slices;
+            if self.sent + 1 == self.slices {
+                amount += self.parent.amount % self.slices;
+            }
+            self.sent += 1;
+            if amount > 0 {
+                children.push(self.parent.child(amount));
+            }
+        }
+        children
+    }
+}
+
+/// Trades `rate` of the volume traded by others since the algorithm started
+#[derive(PartialEq, Clone, Debug)]
+pub struct Participation {
+    pub parent: ParentOrder,
+    /// Target fraction of the market's volume
+    pub rate: f64,
+    /// Market volume when the algorithm first ran
+    start_volume: Option<u64>,
+    sent: u32,
+    filled: u64,
+}
+
+impl Participation {
+    pub fn new(parent: ParentOrder, rate: f64) -> Self {
+        Participation {
+            parent,
+            rate,
+            start_volume: None,
+            sent: 0,
+            filled: 0,
+        }
+    }
+}
+
+impl Agent for Participation {
+    fn on_tick(&mut self, market: &Market, _now: u64) -> Vec<Command> {
+        let volume = market.trade_stats().volume;
+        let start = *self.start_volume.get_or_insert(volume);
+        let others = volume.saturating_sub(start).saturating_sub(self.filled);
+        let target = ((others as f64 * self.rate) as u64).min(self.parent.amount as u64) as u32;
+        if target <= self.sent {
+            return vec![];
+        }
+        let amount = target - self.sent;
+        self.sent = target;
+        vec![self.parent.child(amount)]
+    }
+    fn on_fill(&mut self, fill: &Fill) {
+        self.filled += fill.amount as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ParentOrder, Participation, Twap};
+    use crate::{
+        sim::{Agent, Simulation},
+        Command, Market, OrderRequest, OrderSide,
+    };
+
+    /// Trader `0` trades `amount` at 1.0 every step
+    struct Flow(u32, u32, OrderSide);
+
+    impl Agent for Flow {
+        fn on_tick(&mut self, _market: &Market, _now: u64) -> Vec<Command> {
+            vec![Command::Submit(OrderRequest::new(
+                self.0,
+                self.1,
+                1.0,
+                self.2.clone(),
+            ))]
+        }
+    }
+
+    fn parent(trader_id: u32, amount: u32) -> ParentOrder {
+        ParentOrder {
+            trader_id,
+            side: OrderSide::Buy,
+            amount,
+            price: 1.0,
+        }
+    }
+
+    #[test]
+    fn twap_slices_evenly() {
+        let second = Duration::from_secs(1);
+        let mut twap = Twap::new(parent(1, 100), 2_000_000_000, second * 3, 3);
+        let market = Market::default();
+        let amounts = |commands: Vec<Command>| -> Vec<u32> {
+            commands
+                .into_iter()
+                .map(|command| match command {
+                    Command::Submit(request) => request.amount,
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+        assert!(twap.on_tick(&market, 1_000_000_000).is_empty());
+        assert_eq!(amounts(twap.on_tick(&market, 2_000_000_000)), vec![33]);
+        assert!(twap.on_tick(&market, 2_500_000_000).is_empty());
+        // a late tick catches up on missed slices
+        assert_eq!(amounts(twap.on_tick(&market, 9_000_000_000)), vec![33, 34]);
+        assert!(twap.on_tick(&market, 10_000_000_000).is_empty());
+    }
+
+    #[test]
+    fn participates_in_volume() {
+        let mut sim = Simulation::new(Market::default(), Duration::from_secs(1), 1);
+        sim.add_agent(8, Flow(8, 100, OrderSide::Sell));
+        sim.add_agent(9, Flow(9, 100, OrderSide::Buy));
+        sim.add_agent(3, Participation::new(parent(3, 1_000), 0.25));
+        sim.run(3);
+
+        // step 1 sets the starting volume, step 2 bids 25 of the 100 traded since, which the
+        // step 3 seller fills before others trade 75 more
+        assert_eq!(sim.market().position(3).position, 25);
+        let bid = sim.market().depth(1).bids[0].clone();
+        // the step 3 buyer rests its unfilled 25 ahead of another 18 from the algo
+        assert_eq!(bid.amount, 25 + (175.0 * 0.25) as u64 - 25);
+        assert_eq!(bid.orders, 2);
+    }
+}
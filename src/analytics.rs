@@ -0,0 +1,247 @@
+//! Market quality and execution analytics
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{
+    book::BookBackend,
+    ledger::{Trade, TradeStats},
+    Market, Order, OrderSide,
+};
+
+/// Volume buckets of the VPIN metric
+#[derive(PartialEq, Clone, Debug)]
+pub struct VpinConfig {
+    /// Traded volume per bucket
+    pub bucket_volume: u64,
+    /// Number of most recent full buckets averaged
+    pub buckets: usize,
+}
+
+impl Default for VpinConfig {
+    fn default() -> Self {
+        VpinConfig {
+            bucket_volume: 1_000,
+            buckets: 50,
+        }
+    }
+}
+
+/// The most recent full volume buckets and the one filling, kept as trades are recorded
+#[derive(Debug, Default)]
+pub(crate) struct VpinBuckets {
+    /// Buy and sell volume of the most recent full buckets, oldest first
+    full: VecDeque<(u64, u64)>,
+    buy: u64,
+    sell: u64,
+}
+
+impl VpinBuckets {
+    /// Add the volume of `trade` to the buckets, classified by its aggressor and split across
+    /// bucket boundaries
+    pub fn add(&mut self, config: &VpinConfig, trade: &Trade) {
+        if config.bucket_volume == 0 || config.buckets == 0 {
+            return;
+        }
+        let mut remaining = trade.amount as u64;
+        while remaining > 0 {
+            let amount = remaining.min(config.bucket_volume - self.buy - self.sell);
+            match trade.aggressor {
+                OrderSide::Buy => self.buy += amount,
+                OrderSide::Sell => self.sell += amount,
+            }
+            remaining -= amount;
+            if self.buy + self.sell == config.bucket_volume {
+                if self.full.len() == config.buckets {
+                    self.full.pop_front();
+                }
+                self.full.push_back((self.buy, self.sell));
+                (self.buy, self.sell) = (0, 0);
+            }
+        }
+    }
+    /// Mean buy/sell imbalance of the most recent full buckets
+    pub fn vpin(&self, config: &VpinConfig) -> Option<f64> {
+        if self.full.is_empty() {
+            return None;
+        }
+        let imbalance: u64 = self
+            .full
+            .iter()
+            .map(|(buy, sell)| buy.abs_diff(*sell))
+            .sum();
+        Some(imbalance as f64 / (self.full.len() as u64 * config.bucket_volume) as f64)
+    }
+}
+
+/// Summary statistics of the market's trading
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct MarketStats {
+    pub trades: TradeStats,
+    /// Volume-synchronized probability of informed trading, `None` until a bucket fills
+    pub vpin: Option<f64>,
+}
+
+impl Market {
+    pub fn stats(&self) -> MarketStats {
+        MarketStats {
+            trades: self.trade_stats().clone(),
+            vpin: self.vpin.vpin(&self.config().vpin),
+        }
+    }
+    /// Refill the volume buckets from the trades which have not been busted
+    pub(crate) fn rebucket(&mut self) {
+        let mut vpin = VpinBuckets::default();
+        for trade in self.trades().iter().filter(|trade| !trade.busted) {
+            vpin.add(&self.config.vpin, trade);
+        }
+        self.vpin = vpin;
+    }
+    /// Traded volume per second over the trades of the last `window`, zero for a zero window
+    pub fn trade_rate(&self, window: Duration) -> f64 {
+        if window.is_zero() {
+            return 0.0;
+        }
+        let since = self.now().saturating_sub(window.as_nanos() as u64);
+        let volume: u64 = self
+            .trades()
+            .iter()
+            .rev()
+            .take_while(|trade| trade.timestamp >= since)
+            .filter(|trade| !trade.busted)
+            .map(|trade| trade.amount as u64)
+            .sum();
+        volume as f64 / window.as_secs_f64()
+    }
+    /// Estimate how long a hypothetical order would take to fill completely
+    ///
+    /// The order joins the back of its price level, it fills once the amount ahead of it and its
+    /// own amount have traded at the rate of the last `window`. Orders crossing the book are
+    /// expected to fill immediately. `None` if nothing traded within the window or the estimate
+    /// is too long for a `Duration`.
+    pub fn expected_time_to_fill(
+        &self,
+        side: OrderSide,
+        amount: u32,
+        price: f32,
+        window: Duration,
+    ) -> Option<Duration> {
+        let (crosses, ahead) = match side {
+            OrderSide::Buy => (
+                self.sells
+                    .front()
+                    .is_some_and(|best| best.inner().price <= price),
+                self.buys
+                    .iter()
+                    .map(Order::inner)
+                    .take_while(|order| order.price >= price)
+                    .map(|order| order.amount as u64)
+                    .sum::<u64>(),
+            ),
+            OrderSide::Sell => (
+                self.buys
+                    .front()
+                    .is_some_and(|best| best.inner().price >= price),
+                self.sells
+                    .iter()
+                    .map(Order::inner)
+                    .take_while(|order| order.price <= price)
+                    .map(|order| order.amount as u64)
+                    .sum::<u64>(),
+            ),
+        };
+        if crosses {
+            return Some(Duration::ZERO);
+        }
+        let rate = self.trade_rate(window);
+        if !(rate.is_finite() && rate > 0.0) {
+            return None;
+        }
+        Duration::try_from_secs_f64((ahead + amount as u64) as f64 / rate).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::VpinConfig;
+    use crate::{clock::SimClock, Market, MarketConfig, OrderSide, LOB};
+
+    #[test]
+    fn time_to_fill() {
+        let clock = SimClock::new(0);
+        let mut lob = Market::default();
+        lob.set_clock(clock.clone());
+        let window = Duration::from_secs(10);
+        assert_eq!(
+            lob.expected_time_to_fill(OrderSide::Buy, 10, 1.0, window),
+            None
+        );
+
+        // 100 traded 5 seconds ago, 100 just now
+        lob.submit_order(1, 200, 2.0, OrderSide::Sell).unwrap();
+        clock.advance(Duration::from_secs(20));
+        lob.submit_order(2, 100, 2.0, OrderSide::Buy).unwrap();
+        clock.advance(Duration::from_secs(5));
+        lob.submit_order(2, 100, 2.0, OrderSide::Buy).unwrap();
+        assert_eq!(lob.trade_rate(window), 20.0);
+
+        lob.submit_order(3, 30, 1.0, OrderSide::Buy).unwrap();
+        lob.submit_order(3, 50, 1.5, OrderSide::Buy).unwrap();
+        assert_eq!(
+            lob.expected_time_to_fill(OrderSide::Buy, 20, 1.0, window),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            lob.expected_time_to_fill(OrderSide::Sell, 20, 1.5, window),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn time_to_fill_without_rate() {
+        let clock = SimClock::new(0);
+        let mut lob = Market::default();
+        lob.set_clock(clock.clone());
+        lob.submit_order(1, 200, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 100, 2.0, OrderSide::Buy).unwrap();
+        lob.submit_order(3, 10, 1.0, OrderSide::Buy).unwrap();
+
+        // a zero window holds the trade of this instant
+        assert_eq!(lob.trade_rate(Duration::ZERO), 0.0);
+        assert_eq!(
+            lob.expected_time_to_fill(OrderSide::Buy, 10, 1.0, Duration::ZERO),
+            None
+        );
+        // nothing traded in the last second
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(
+            lob.expected_time_to_fill(OrderSide::Buy, 10, 1.0, Duration::from_secs(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn vpin_buckets() {
+        let mut lob = Market::new(MarketConfig {
+            vpin: VpinConfig {
+                bucket_volume: 100,
+                buckets: 2,
+            },
+            ..Default::default()
+        });
+        lob.submit_order(1, 1_000, 1.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 150, 1.0, OrderSide::Buy).unwrap();
+        assert_eq!(lob.stats().vpin, Some(1.0));
+
+        // buckets: 100 buy, 50 buy + 50 sell, 100 sell
+        lob.submit_order(3, 1_000, 0.5, OrderSide::Buy).unwrap();
+        lob.submit_order(1, 150, 0.5, OrderSide::Sell).unwrap();
+        let stats = lob.stats();
+        assert_eq!(stats.vpin, Some(0.5));
+        assert_eq!(stats.trades.volume, 300);
+
+        // busting the sell leaves one full buy bucket
+        lob.bust_trade(1, false).unwrap();
+        assert_eq!(lob.stats().vpin, Some(1.0));
+    }
+}
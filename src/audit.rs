@@ -0,0 +1,332 @@
+//! Quantity conservation audit and command audit trail
+//!
+//! Every unit submitted to the market must have been filled, cancelled or still be resting,
+//! `Market::audit_conservation` checks this holds for a recorded `History`.
+//!
+//! An `AuditTrail` records every command executed through it, accepted or rejected, with a
+//! timestamp, sequence number and the resulting events. It is kept separately from the storage
+//! journal, which only needs enough to rebuild state.
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Command, Event, Fill, LimitOrder, Market, OrderId, OrderRequest, OrderResult};
+
+/// A record of placed orders, all fills they produced and quantity taken off the book
+#[derive(Default, Debug)]
+pub struct History {
+    /// Submitted orders by id
+    orders: BTreeMap<OrderId, OrderRequest>,
+    fills: Vec<Fill>,
+    /// Amount cancelled, amended away or expired by order id
+    cancelled: BTreeMap<OrderId, u64>,
+}
+
+impl History {
+    /// Record a placed order and its result
+    pub fn record(&mut self, request: &OrderRequest, result: &OrderResult) {
+        self.orders.insert(result.order_id, request.clone());
+        self.record_result(result);
+    }
+    /// Record the result of amending `order_id` to `amount` at `price`
+    ///
+    /// A replacement order is recorded as submitted with the original's side and flags.
+    pub fn record_amend(
+        &mut self,
+        order_id: OrderId,
+        amount: u32,
+        price: f32,
+        result: &OrderResult,
+    ) {
+        if result.order_id != order_id {
+            if let Some(original) = self.orders.get(&order_id).cloned() {
+                let request = OrderRequest {
+                    amount,
+                    price,
+                    ..original
+                };
+                self.orders.insert(result.order_id, request);
+            }
+        }
+        self.record_result(result);
+    }
+    /// Record a cancelled order
+    pub fn record_cancel(&mut self, order: &LimitOrder) {
+        *self.cancelled.entry(order.nonce).or_default() += order.amount as u64;
+    }
+    /// Record a day order expired at the end of day
+    pub fn record_expire(&mut self, order: &LimitOrder) {
+        self.record_cancel(order);
+    }
+    fn record_result(&mut self, result: &OrderResult) {
+        self.fills.extend(result.fills.iter().cloned());
+        for order in result.cancelled.iter() {
+            self.record_cancel(order);
+        }
+    }
+    /// All orders recorded so far in id order
+    pub fn orders(&self) -> impl Iterator<Item = (OrderId, &OrderRequest)> {
+        self.orders
+            .iter()
+            .map(|(order_id, request)| (*order_id, request))
+    }
+    /// All fills recorded so far
+    pub fn fills(&self) -> &[Fill] {
+        self.fills.as_slice()
+    }
+}
+
+/// An order whose quantity is not accounted for
+#[derive(PartialEq, Clone, Debug)]
+pub struct Discrepancy {
+    pub order_id: OrderId,
+    /// Originally submitted amount, zero if the order was never recorded
+    pub submitted: u32,
+    /// Cumulative filled amount
+    pub filled: u64,
+    /// Amount cancelled, amended away or expired
+    pub cancelled: u64,
+    /// Amount still resting on the book
+    pub resting: u32,
+}
+
+impl Market {
+    /// Cross-check `history` against the book, returning any orders where
+    /// `submitted != filled + cancelled + resting`
+    pub fn audit_conservation(&self, history: &History) -> Vec<Discrepancy> {
+        let mut filled = BTreeMap::<OrderId, u64>::new();
+        for fill in history.fills.iter() {
+            *filled.entry(fill.order_id).or_default() += fill.amount as u64;
+        }
+
+        let mut order_ids: Vec<OrderId> = history.orders.keys().copied().collect();
+        order_ids.extend(filled.keys().filter(|id| !history.orders.contains_key(id)));
+        order_ids.extend(
+            history
+                .cancelled
+                .keys()
+                .filter(|id| !history.orders.contains_key(id) && !filled.contains_key(id)),
+        );
+        order_ids.sort_unstable();
+
+        order_ids
+            .into_iter()
+            .filter_map(|order_id| {
+                let submitted = history
+                    .orders
+                    .get(&order_id)
+                    .map(|request| request.amount)
+                    .unwrap_or_default();
+                let filled = filled.get(&order_id).copied().unwrap_or_default();
+                let cancelled = history
+                    .cancelled
+                    .get(&order_id)
+                    .copied()
+                    .unwrap_or_default();
+                let resting = self
+                    .get_order(order_id)
+                    .map(|order| order.amount)
+                    .unwrap_or_default();
+                if submitted as u64 != filled + cancelled + resting as u64 {
+                    Some(Discrepancy {
+                        order_id,
+                        submitted,
+                        filled,
+                        cancelled,
+                        resting,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// A command and its outcome
+#[derive(PartialEq, Clone, Debug)]
+pub struct AuditRecord {
+    pub seq: u64,
+    /// Nanoseconds since the unix epoch when the command was received
+    pub timestamp: u64,
+    pub command: Command,
+    pub events: Vec<Event>,
+}
+
+impl AuditRecord {
+    /// Whether the market accepted the command
+    pub fn accepted(&self) -> bool {
+        !matches!(self.events.first(), Some(Event::Rejected(_)))
+    }
+}
+
+/// Append-only record of every command executed through it
+#[derive(Default, Debug)]
+pub struct AuditTrail {
+    records: Vec<AuditRecord>,
+}
+
+impl AuditTrail {
+    /// Execute `command` on `market` recording it and its outcome
+    pub fn execute(&mut self, market: &mut Market, command: &Command) -> Vec<Event> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default();
+        let events = market.execute(command);
+        self.records.push(AuditRecord {
+            seq: self.records.len() as u64,
+            timestamp,
+            command: command.clone(),
+            events: events.clone(),
+        });
+        events
+    }
+    /// All records in sequence order
+    pub fn records(&self) -> &[AuditRecord] {
+        self.records.as_slice()
+    }
+    /// Write one JSON object per record, commands and events in their text form
+    /// e.g. `{"seq":0,"timestamp":..,"command":"cancel 4","accepted":false,"events":[..]}`
+    #[cfg(feature = "json")]
+    pub fn write_jsonl(&self, mut out: impl std::io::Write) -> std::io::Result<()> {
+        for record in self.records.iter() {
+            let line = serde_json::json!({
+                "seq": record.seq,
+                "timestamp": record.timestamp,
+                "command": record.command.to_string(),
+                "accepted": record.accepted(),
+                "events": record.events.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            });
+            writeln!(out, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditTrail, Discrepancy, History};
+    use crate::{
+        policy::StpAction, Command, Event, Fill, Market, MarketConfig, OrderFlags, OrderRequest,
+        OrderSide,
+    };
+
+    #[test]
+    fn conserved_quantities() {
+        let mut lob = Market::default();
+        let mut history = History::default();
+        let requests = [
+            OrderRequest::new(1, 100, 1.0, OrderSide::Sell),
+            OrderRequest::new(2, 50, 2.0, OrderSide::Sell),
+            OrderRequest::new(3, 120, 2.0, OrderSide::Buy),
+            OrderRequest::new(4, 80, 0.5, OrderSide::Buy),
+        ];
+        for request in requests {
+            let result = lob.place_order(request.clone()).unwrap();
+            history.record(&request, &result);
+        }
+        assert_eq!(history.fills().len(), 4);
+        assert!(lob.audit_conservation(&history).is_empty());
+    }
+
+    #[test]
+    fn cancels_amends_and_expiry_are_conserved() {
+        let mut lob = Market::new(MarketConfig {
+            stp_action: StpAction::CancelResting,
+            ..Default::default()
+        });
+        let mut history = History::default();
+        let mut place = |lob: &mut Market, request: OrderRequest| {
+            let result = lob.place_order(request.clone()).unwrap();
+            history.record(&request, &result);
+            result.order_id
+        };
+        let cancelled = place(&mut lob, OrderRequest::new(1, 100, 1.0, OrderSide::Sell));
+        let reduced = place(&mut lob, OrderRequest::new(2, 100, 1.1, OrderSide::Sell));
+        let replaced = place(&mut lob, OrderRequest::new(3, 100, 1.2, OrderSide::Sell));
+        let day = place(
+            &mut lob,
+            OrderRequest::new(4, 100, 1.3, OrderSide::Sell).with_flags(OrderFlags::DAY),
+        );
+        // self trade prevention cancels trader 5's resting bid when its ask rests across it
+        let internal = place(&mut lob, OrderRequest::new(5, 100, 0.5, OrderSide::Buy));
+        place(
+            &mut lob,
+            OrderRequest::new(5, 10, 0.4, OrderSide::Sell).with_flags(OrderFlags::ANTI_INTERNALIZE),
+        );
+        assert!(lob.get_order(internal).is_none());
+
+        let order = lob.cancel_order(cancelled).unwrap();
+        history.record_cancel(&order);
+        for (order_id, amount, price) in [(reduced, 40, 1.1), (replaced, 60, 1.25)] {
+            let result = lob.amend_order(order_id, amount, price).unwrap();
+            history.record_amend(order_id, amount, price, &result);
+        }
+        for event in lob.end_of_day() {
+            if let Event::Expired(order) = event {
+                history.record_expire(&order);
+            }
+        }
+        assert!(lob.get_order(day).is_none());
+        assert!(lob.audit_conservation(&history).is_empty());
+    }
+
+    #[test]
+    fn reports_discrepancies() {
+        let mut lob = Market::default();
+        let mut history = History::default();
+        let request = OrderRequest::new(1, 100, 1.0, OrderSide::Sell);
+        let result = lob.place_order(request.clone()).unwrap();
+        history.record(&request, &result);
+        // a fill the market never produced
+        history
+            .fills
+            .push(Fill::new(10, 1.0, OrderSide::Sell, 1, 2, result.order_id));
+
+        assert_eq!(
+            lob.audit_conservation(&history),
+            vec![Discrepancy {
+                order_id: result.order_id,
+                submitted: 100,
+                filled: 10,
+                cancelled: 0,
+                resting: 100,
+            }]
+        );
+    }
+
+    #[test]
+    fn audit_trail_records_commands() {
+        let mut lob = Market::default();
+        let mut trail = AuditTrail::default();
+        trail.execute(
+            &mut lob,
+            &Command::Submit(OrderRequest::new(1, 100, 1.0, OrderSide::Sell)),
+        );
+        trail.execute(&mut lob, &Command::Cancel(4));
+
+        let records = trail.records();
+        assert_eq!(
+            records.iter().map(|r| r.seq).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert!(records[0].accepted());
+        assert!(!records[1].accepted());
+        assert!(records[0].timestamp <= records[1].timestamp);
+
+        #[cfg(feature = "json")]
+        {
+            let mut out = Vec::new();
+            trail.write_jsonl(&mut out).unwrap();
+            let lines: Vec<serde_json::Value> = String::from_utf8(out)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            assert_eq!(lines[1]["command"], "cancel 4");
+            assert_eq!(lines[1]["events"][0], "reject order 4 not found");
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! Configurable benchmark workloads
+//!
+//! A `Scenario` describes the order flow to generate, e.g.
+//! ```text
+//! orders = 100000
+//! prices = uniform 1 100
+//! cancel_ratio = 0.2
+//! depth = 1000
+//! seed = 7
+//! ```
+//! `WorkloadRunner` replays it against a market and reports throughput and latency percentiles.
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{Liquidity, Market, OrderId, OrderRequest, OrderSide};
+
+/// Distribution of generated order prices
+#[derive(PartialEq, Clone, Debug)]
+pub enum PriceDistribution {
+    Uniform { min: f32, max: f32 },
+    Normal { mean: f32, std_dev: f32 },
+}
+
+impl PriceDistribution {
+    /// Whether the parameters are finite and describe a distribution, e.g. `min <= max`
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Self::Uniform { min, max } => min.is_finite() && max.is_finite() && min <= max,
+            Self::Normal { mean, std_dev } => {
+                mean.is_finite() && std_dev.is_finite() && std_dev >= 0.0
+            }
+        }
+    }
+    /// Panics unless `is_valid`
+    fn sample(&self, rng: &mut StdRng) -> f32 {
+        let price = match *self {
+            Self::Uniform { min, max } => rng.gen_range(min..=max),
+            Self::Normal { mean, std_dev } => {
+                // Box-Muller transform
+                let u_0: f32 = rng.gen_range(f32::EPSILON..1.0);
+                let u_1: f32 = rng.gen();
+                let z = (-2.0 * u_0.ln()).sqrt() * (std::f32::consts::TAU * u_1).cos();
+                mean + z * std_dev
+            }
+        };
+        // round to a 0.01 tick so price levels are shared
+        (price * 100.0).round() / 100.0
+    }
+}
+
+/// Description of a benchmark workload
+#[derive(PartialEq, Clone, Debug)]
+pub struct Scenario {
+    /// Number of operations (submits and cancels) to run
+    pub orders: usize,
+    pub prices: PriceDistribution,
+    /// Fraction of operations which cancel a resting order
+    pub cancel_ratio: f32,
+    /// Number of resting orders to maintain, excess orders are cancelled
+    pub depth: usize,
+    pub seed: u64,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            orders: 10_000,
+            prices: PriceDistribution::Uniform {
+                min: 1.0,
+                max: 100.0,
+            },
+            cancel_ratio: 0.0,
+            depth: usize::MAX,
+            seed: 0,
+        }
+    }
+}
+
+/// Errors parsing a `Scenario`
+#[derive(PartialEq, Clone, Debug)]
+pub enum ScenarioError {
+    UnknownKey(String),
+    InvalidValue(String),
+    /// A directly built scenario's price distribution fails `is_valid`
+    InvalidPrices(PriceDistribution),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownKey(key) => write!(f, "unknown scenario key: {key}"),
+            Self::InvalidValue(line) => write!(f, "invalid scenario value: {line}"),
+            Self::InvalidPrices(prices) => write!(f, "invalid price distribution: {prices:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl FromStr for Scenario {
+    type Err = ScenarioError;
+    /// Parse `key = value` lines, unspecified keys keep their default value
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scenario = Scenario::default();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || ScenarioError::InvalidValue(line.to_string());
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim();
+            match key.trim() {
+                "orders" => scenario.orders = value.parse().map_err(|_| invalid())?,
+                "cancel_ratio" => scenario.cancel_ratio = value.parse().map_err(|_| invalid())?,
+                "depth" => scenario.depth = value.parse().map_err(|_| invalid())?,
+                "seed" => scenario.seed = value.parse().map_err(|_| invalid())?,
+                "prices" => {
+                    let parts: Vec<&str> = value.split_whitespace().collect();
+                    let param = |idx: usize| -> Result<f32, ScenarioError> {
+                        parts
+                            .get(idx)
+                            .and_then(|p| p.parse().ok())
+                            .ok_or_else(invalid)
+                    };
+                    let prices = match parts.first() {
+                        Some(&"uniform") => PriceDistribution::Uniform {
+                            min: param(1)?,
+                            max: param(2)?,
+                        },
+                        Some(&"normal") => PriceDistribution::Normal {
+                            mean: param(1)?,
+                            std_dev: param(2)?,
+                        },
+                        _ => return Err(invalid()),
+                    };
+                    if !prices.is_valid() {
+                        return Err(invalid());
+                    }
+                    scenario.prices = prices;
+                }
+                key => return Err(ScenarioError::UnknownKey(key.to_string())),
+            }
+        }
+        Ok(scenario)
+    }
+}
+
+/// Results of a workload run
+#[derive(Clone, Debug)]
+pub struct Report {
+    /// Number of operations run
+    pub operations: usize,
+    pub elapsed: Duration,
+    /// Operations per second
+    pub throughput: f64,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ops in {:?} ({:.0} ops/s) p50: {:?} p90: {:?} p99: {:?} max: {:?}",
+            self.operations, self.elapsed, self.throughput, self.p50, self.p90, self.p99, self.max
+        )
+    }
+}
+
+/// Runs a `Scenario` against a market
+pub struct WorkloadRunner {
+    scenario: Scenario,
+}
+
+impl WorkloadRunner {
+    /// A runner for `scenario`, failing if its price distribution is invalid
+    pub fn new(scenario: Scenario) -> Result<Self, ScenarioError> {
+        if !scenario.prices.is_valid() {
+            return Err(ScenarioError::InvalidPrices(scenario.prices));
+        }
+        Ok(WorkloadRunner { scenario })
+    }
+    /// Run the scenario against a new market
+    pub fn run(&self) -> Report {
+        let scenario = &self.scenario;
+        let mut rng = StdRng::seed_from_u64(scenario.seed);
+        let mut market = Market::default();
+        let mut resting = Vec::<OrderId>::default();
+        let mut latencies = Vec::<Duration>::with_capacity(scenario.orders);
+
+        let start = Instant::now();
+        for i in 0..scenario.orders {
+            let cancel = !resting.is_empty()
+                && (resting.len() > scenario.depth || rng.gen::<f32>() < scenario.cancel_ratio);
+            if cancel {
+                let order_id = resting.swap_remove(rng.gen_range(0..resting.len()));
+                let s_0 = Instant::now();
+                let _ = market.cancel_order(order_id);
+                latencies.push(s_0.elapsed());
+            } else {
+                let side = if rng.gen() {
+                    OrderSide::Buy
+                } else {
+                    OrderSide::Sell
+                };
+                let request = OrderRequest::new(
+                    i as u32,
+                    rng.gen_range(1..=100),
+                    scenario.prices.sample(&mut rng),
+                    side,
+                );
+                let s_0 = Instant::now();
+                let result = market.place_order(request);
+                latencies.push(s_0.elapsed());
+                if let Ok(result) = result {
+                    // forget resting orders filled whole so cancels and `depth` only see live ids
+                    let filled: Vec<OrderId> = result
+                        .fills
+                        .iter()
+                        .filter(|fill| fill.flags.liquidity == Liquidity::Added)
+                        .map(|fill| fill.order_id)
+                        .filter(|order_id| market.get_order(*order_id).is_none())
+                        .collect();
+                    if !filled.is_empty() {
+                        resting.retain(|order_id| !filled.contains(order_id));
+                    }
+                    if market.get_order(result.order_id).is_some() {
+                        resting.push(result.order_id);
+                    }
+                }
+            }
+        }
+        let elapsed = start.elapsed();
+
+        latencies.sort_unstable();
+        let percentile = |p: usize| {
+            latencies
+                .get((latencies.len() * p / 100).min(latencies.len().saturating_sub(1)))
+                .copied()
+                .unwrap_or_default()
+        };
+        Report {
+            operations: latencies.len(),
+            elapsed,
+            throughput: latencies.len() as f64 / elapsed.as_secs_f64(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: latencies.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PriceDistribution, Scenario, ScenarioError, WorkloadRunner};
+
+    #[test]
+    fn parse_scenario() {
+        let scenario: Scenario = "
+            # a small workload
+            orders = 500
+            prices = normal 50 2.5
+            cancel_ratio = 0.25
+            depth = 100
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(
+            scenario,
+            Scenario {
+                orders: 500,
+                prices: PriceDistribution::Normal {
+                    mean: 50.0,
+                    std_dev: 2.5
+                },
+                cancel_ratio: 0.25,
+                depth: 100,
+                seed: 0,
+            }
+        );
+        assert_eq!(
+            "size = 1".parse::<Scenario>(),
+            Err(ScenarioError::UnknownKey("size".to_string()))
+        );
+        assert!("prices = poisson 1".parse::<Scenario>().is_err());
+        assert_eq!(
+            "prices = uniform 100 1".parse::<Scenario>(),
+            Err(ScenarioError::InvalidValue(
+                "prices = uniform 100 1".to_string()
+            ))
+        );
+        assert!("prices = normal 50 -1".parse::<Scenario>().is_err());
+        assert!("prices = uniform 1 inf".parse::<Scenario>().is_err());
+    }
+
+    #[test]
+    fn run_scenario() {
+        // a narrow price range so orders cross and fill resting orders
+        let scenario = "orders = 1000\nprices = uniform 1 2\ncancel_ratio = 0.5\ndepth = 50";
+        let report = WorkloadRunner::new(scenario.parse().unwrap())
+            .unwrap()
+            .run();
+        assert_eq!(report.operations, 1_000);
+        assert!(report.p50 <= report.p99 && report.p99 <= report.max);
+
+        let scenario = Scenario {
+            prices: PriceDistribution::Uniform {
+                min: 100.0,
+                max: 1.0,
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            WorkloadRunner::new(scenario),
+            Err(ScenarioError::InvalidPrices(_))
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+//! HTTP frontend for an `Exchange`
+//!
+//! Usage: `lob-rest [ADDR] [SYMBOL...]`, defaults to `127.0.0.1:8080` with a single `LOB` market
+//!
+//! - `POST /orders?symbol=` place an order, body is a `WireOrder`
+//! - `DELETE /orders/{id}?symbol=` cancel a resting order
+//! - `GET /depth?symbol=&levels=` aggregated price levels
+//! - `GET /trades?symbol=` fills, oldest first
+//! - `GET /markets` instrument and status of every market
+//! - `GET /markets/{symbol}` instrument and status of a market
+//!
+//! `symbol` defaults to the first listed market
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use simple_lob::{
+    wire::{WireFill, WireOrder},
+    Error, Exchange, OrderId, OrderRequest,
+};
+
+type Shared = Arc<Mutex<Exchange>>;
+
+#[derive(Deserialize)]
+struct Params {
+    symbol: Option<String>,
+    levels: Option<usize>,
+}
+
+impl Params {
+    fn symbol(&self, exchange: &Exchange) -> String {
+        self.symbol
+            .clone()
+            .or_else(|| exchange.symbols().next().map(str::to_string))
+            .unwrap_or_default()
+    }
+}
+
+fn error(status: StatusCode, err: impl ToString) -> Response {
+    (status, Json(json!({ "error": err.to_string() }))).into_response()
+}
+
+fn market_error(err: Error) -> Response {
+    match err {
+        Error::UnknownSymbol(_) | Error::OrderNotFound(_) => error(StatusCode::NOT_FOUND, err),
+        _ => error(StatusCode::BAD_REQUEST, err),
+    }
+}
+
+async fn submit(
+    State(exchange): State<Shared>,
+    Query(params): Query<Params>,
+    Json(order): Json<WireOrder>,
+) -> Response {
+    let request = match OrderRequest::try_from(order) {
+        Ok(request) => request,
+        Err(err) => return error(StatusCode::BAD_REQUEST, err),
+    };
+    let mut exchange = exchange.lock().expect("lock poisoned");
+    let symbol = params.symbol(&exchange);
+    match exchange.submit(&symbol, request) {
+        Ok(result) => {
+            let fills: Vec<WireFill> = result.fills.iter().map(WireFill::from).collect();
+            Json(json!({ "order_id": result.order_id, "fills": fills })).into_response()
+        }
+        Err(err) => market_error(err),
+    }
+}
+
+async fn cancel(
+    State(exchange): State<Shared>,
+    Path(order_id): Path<OrderId>,
+    Query(params): Query<Params>,
+) -> Response {
+    let mut exchange = exchange.lock().expect("lock poisoned");
+    let symbol = params.symbol(&exchange);
+    match exchange.cancel(&symbol, order_id) {
+        Ok(order) => Json(json!({ "order_id": order_id, "amount": order.amount })).into_response(),
+        Err(err) => market_error(err),
+    }
+}
+
+async fn depth(State(exchange): State<Shared>, Query(params): Query<Params>) -> Response {
+    let exchange = exchange.lock().expect("lock poisoned");
+    let symbol = params.symbol(&exchange);
+    match exchange.market(&symbol) {
+        Some(market) => Json(market.depth(params.levels.unwrap_or(10))).into_response(),
+        None => market_error(Error::UnknownSymbol(symbol)),
+    }
+}
+
+async fn trades(State(exchange): State<Shared>, Query(params): Query<Params>) -> Response {
+    let exchange = exchange.lock().expect("lock poisoned");
+    let symbol = params.symbol(&exchange);
+    match exchange.fills(&symbol) {
+        Some(fills) => Json(fills.iter().map(WireFill::from).collect::<Vec<_>>()).into_response(),
+        None => market_error(Error::UnknownSymbol(symbol)),
+    }
+}
+
+async fn markets(State(exchange): State<Shared>) -> Response {
+    let exchange = exchange.lock().expect("lock poisoned");
+    Json(exchange.list_markets()).into_response()
+}
+
+async fn market_info(State(exchange): State<Shared>, Path(symbol): Path<String>) -> Response {
+    let exchange = exchange.lock().expect("lock poisoned");
+    match exchange.market_info(&symbol) {
+        Some(info) => Json(info).into_response(),
+        None => market_error(Error::UnknownSymbol(symbol)),
+    }
+}
+
+fn router(exchange: Exchange) -> Router {
+    Router::new()
+        .route("/orders", post(submit))
+        .route("/orders/{id}", delete(cancel))
+        .route("/depth", get(depth))
+        .route("/trades", get(trades))
+        .route("/markets", get(markets))
+        .route("/markets/{symbol}", get(market_info))
+        .with_state(Arc::new(Mutex::new(exchange)))
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let mut exchange = Exchange::default();
+    for symbol in args {
+        exchange.add_market(&symbol);
+    }
+    if exchange.symbols().next().is_none() {
+        exchange.add_market("LOB");
+    }
+
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .expect("bind address");
+    println!("listening on {addr}");
+    axum::serve(listener, router(exchange))
+        .await
+        .expect("server error");
+}
@@ -0,0 +1,37 @@
+//! Run matching scenario files against a `Market`
+//!
+//! Usage: `lob-scenario FILE...`, prints a report per scenario and exits with a failure status
+//! if any scenario could not be run or differed from its expectations.
+use std::{env, fs, process::ExitCode};
+
+use simple_lob::scenario::Scenario;
+
+fn main() -> ExitCode {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("usage: lob-scenario FILE...");
+        return ExitCode::FAILURE;
+    }
+    let mut failed = false;
+    for path in paths {
+        let report = fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| text.parse::<Scenario>().map_err(|err| err.to_string()))
+            .and_then(|scenario| scenario.run().map_err(|err| err.to_string()));
+        match report {
+            Ok(report) => {
+                failed |= !report.passed();
+                print!("{report}");
+            }
+            Err(err) => {
+                failed = true;
+                eprintln!("{path}: {err}");
+            }
+        }
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,202 @@
+//! Terminal price ladder for a `Market`
+//!
+//! Usage: `lob-tui [TICK]`, defaults to a tick of `0.25` around a book seeded at `100`
+//!
+//! - `Up`/`Down` move the cursor a tick, `PageUp`/`PageDown` ten ticks
+//! - `+`/`-` change the order amount
+//! - `b`/`s` buy or sell the amount at the cursor price
+//! - `c` cancel your latest order at the cursor price
+//! - `q`/`Esc` quit
+//!
+//! The event log is fed by the market's event bus.
+use std::{
+    collections::VecDeque,
+    io,
+    sync::mpsc::{self, Receiver},
+};
+
+use ratatui::{
+    crossterm::event::{self, Event as TermEvent, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{Block, Cell, List, Row, Table},
+    DefaultTerminal, Frame,
+};
+use simple_lob::{Command, Event, Market, OrderId, OrderRequest, OrderSide};
+
+/// Trader id of the orders entered from the keyboard
+const TRADER_ID: u32 = 1;
+/// Trader id of the seeded liquidity
+const SEED_TRADER_ID: u32 = 0;
+const LOG_LINES: usize = 200;
+
+struct App {
+    market: Market,
+    events: Receiver<Event>,
+    log: VecDeque<String>,
+    tick: f32,
+    /// Cursor price in ticks
+    cursor: i64,
+    amount: u32,
+    /// Orders entered from the keyboard, oldest first
+    orders: Vec<OrderId>,
+}
+
+impl App {
+    fn new(tick: f32) -> Self {
+        let mut market = Market::default();
+        let (sender, events) = mpsc::channel();
+        market.subscribe::<Event>(move |event| {
+            let _ = sender.send(event.clone());
+        });
+        let mid = (100.0 / tick).round() as i64;
+        for level in 1..=5 {
+            let amount = 10 * level as u32;
+            for (side, price) in [
+                (OrderSide::Buy, mid - level),
+                (OrderSide::Sell, mid + level),
+            ] {
+                let request = OrderRequest::new(SEED_TRADER_ID, amount, price as f32 * tick, side);
+                market.execute(&Command::Submit(request));
+            }
+        }
+        let mut app = App {
+            market,
+            events,
+            log: VecDeque::default(),
+            tick,
+            cursor: mid,
+            amount: 10,
+            orders: vec![],
+        };
+        app.drain_events();
+        app
+    }
+    fn price(&self, ticks: i64) -> f32 {
+        ticks as f32 * self.tick
+    }
+    fn ticks(&self, price: f32) -> i64 {
+        (price / self.tick).round() as i64
+    }
+    fn drain_events(&mut self) {
+        for event in self.events.try_iter() {
+            self.log.push_front(event.to_string());
+        }
+        self.log.truncate(LOG_LINES);
+    }
+    fn submit(&mut self, side: OrderSide) {
+        let request = OrderRequest::new(TRADER_ID, self.amount, self.price(self.cursor), side);
+        for event in self.market.execute(&Command::Submit(request)) {
+            if let Event::Accepted(order_id) = event {
+                self.orders.push(order_id);
+            }
+        }
+    }
+    fn cancel(&mut self) {
+        let cursor = self.cursor;
+        self.orders
+            .retain(|order_id| self.market.get_order(*order_id).is_some());
+        let latest = self.orders.iter().rev().copied().find(|order_id| {
+            self.market
+                .get_order(*order_id)
+                .is_some_and(|order| self.ticks(order.price) == cursor)
+        });
+        if let Some(order_id) = latest {
+            self.market.execute(&Command::Cancel(order_id));
+        }
+    }
+    /// Handle a key returning false to quit
+    fn key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up => self.cursor += 1,
+            KeyCode::Down => self.cursor -= 1,
+            KeyCode::PageUp => self.cursor += 10,
+            KeyCode::PageDown => self.cursor -= 10,
+            KeyCode::Char('+') | KeyCode::Char('=') => self.amount += 1,
+            KeyCode::Char('-') => self.amount = self.amount.saturating_sub(1).max(1),
+            KeyCode::Char('b') => self.submit(OrderSide::Buy),
+            KeyCode::Char('s') => self.submit(OrderSide::Sell),
+            KeyCode::Char('c') => self.cancel(),
+            _ => (),
+        }
+        self.drain_events();
+        true
+    }
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [ladder, log] =
+            Layout::horizontal([Constraint::Length(38), Constraint::Min(0)]).areas(main);
+
+        // rows of the ladder centered on the cursor, highest price first
+        let depth = self.market.depth(usize::MAX);
+        let amount_at = |levels: &[simple_lob::depth::Level], ticks| {
+            levels
+                .iter()
+                .find(|level| self.ticks(level.price) == ticks)
+                .map(|level| level.amount.to_string())
+                .unwrap_or_default()
+        };
+        let half = ladder.height.saturating_sub(3) as i64 / 2;
+        let rows = (self.cursor - half..=self.cursor + half)
+            .rev()
+            .map(|ticks| {
+                let row = Row::new([
+                    Cell::from(Line::from(amount_at(&depth.bids, ticks)).right_aligned())
+                        .fg(Color::Green),
+                    Cell::from(Line::from(format!("{:.4}", self.price(ticks))).centered()),
+                    Cell::from(amount_at(&depth.asks, ticks)).fg(Color::Red),
+                ]);
+                if ticks == self.cursor {
+                    row.style(Style::new().reversed())
+                } else {
+                    row
+                }
+            });
+        let widths = [Constraint::Length(12); 3];
+        let table = Table::new(rows, widths)
+            .header(Row::new(["bids", "price", "asks"]).bold())
+            .block(Block::bordered().title("ladder"));
+        frame.render_widget(table, ladder);
+
+        let log_lines = self.log.iter().map(String::as_str);
+        frame.render_widget(
+            List::new(log_lines).block(Block::bordered().title("events")),
+            log,
+        );
+
+        let position = self.market.position(TRADER_ID);
+        frame.render_widget(
+            Line::from(format!(
+                " amount {} | position {} | balance {:.2} | b buy s sell c cancel +/- amount q quit",
+                self.amount, position.position, position.balance
+            )),
+            status,
+        );
+    }
+}
+
+fn run(terminal: &mut DefaultTerminal, mut app: App) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let TermEvent::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !app.key(key.code) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn main() -> io::Result<()> {
+    let tick = std::env::args()
+        .nth(1)
+        .map(|tick| tick.parse::<f32>().expect("tick is a number"))
+        .filter(|tick| *tick > 0.0)
+        .unwrap_or(0.25);
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, App::new(tick));
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,101 @@
+//! Touch following tick array book
+use super::{BookBackend, TickLevels};
+use crate::{Fill, LimitOrder, Order, OrderId};
+
+/// Near-touch levels in a dense tick array and far levels in a `BTreeMap`
+///
+/// A `TickLevels` whose span of `LEVELS` ticks follows the touch. The span starts a quarter of
+/// its width better than the touch, leaving room for improving orders, and once the touch moves
+/// a quarter of the width from where the span was placed, levels are migrated between the array
+/// and the tree around the new touch. Memory stays bounded by `LEVELS` however wide the book.
+#[derive(Debug)]
+pub struct AdaptiveLevels<T: Order, const TICKS: u32 = 100, const LEVELS: usize = 4096> {
+    levels: TickLevels<T, TICKS, LEVELS>,
+    /// Touch tick the span was placed around
+    anchor: Option<i64>,
+}
+
+impl<T: Order, const TICKS: u32, const LEVELS: usize> Default for AdaptiveLevels<T, TICKS, LEVELS> {
+    fn default() -> Self {
+        AdaptiveLevels {
+            levels: TickLevels::default(),
+            anchor: None,
+        }
+    }
+}
+
+impl<T: Order + From<LimitOrder>, const TICKS: u32, const LEVELS: usize>
+    AdaptiveLevels<T, TICKS, LEVELS>
+{
+    /// Re-place the span if the touch has moved too far from its anchor
+    fn follow_touch(&mut self) {
+        let Some(touch) = self
+            .levels
+            .front()
+            .map(|order| TickLevels::<T, TICKS, LEVELS>::tick(order.inner().price))
+        else {
+            return;
+        };
+        let quarter = (LEVELS / 4) as i64;
+        if self
+            .anchor
+            .is_some_and(|anchor| touch.abs_diff(anchor) <= quarter as u64)
+        {
+            return;
+        }
+        let low = if TickLevels::<T, TICKS, LEVELS>::ascending() {
+            touch.saturating_sub(quarter)
+        } else {
+            touch.saturating_add(quarter - LEVELS as i64 + 1)
+        };
+        self.levels.migrate(low);
+        self.anchor = Some(touch);
+    }
+}
+
+impl<T: Order + From<LimitOrder>, const TICKS: u32, const LEVELS: usize> BookBackend<T>
+    for AdaptiveLevels<T, TICKS, LEVELS>
+{
+    fn front(&self) -> Option<&T> {
+        self.levels.front()
+    }
+    fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+    fn len(&self) -> usize {
+        self.levels.len()
+    }
+    fn capacity(&self) -> usize {
+        self.levels.capacity()
+    }
+    fn compact(&mut self) {
+        self.levels.compact()
+    }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.levels.iter()
+    }
+    fn get(&self, order_id: OrderId) -> Option<&T> {
+        self.levels.get(order_id)
+    }
+    fn remove(&mut self, order_id: OrderId) -> Option<T> {
+        let order = self.levels.remove(order_id);
+        self.follow_touch();
+        order
+    }
+    fn insert_order(&mut self, order: &T) -> Result<(), ()> {
+        self.levels.insert_order(order)?;
+        self.follow_touch();
+        Ok(())
+    }
+    fn submit_order<'a>(
+        &mut self,
+        order: &'a mut T::Opposite,
+    ) -> (Vec<Fill>, Option<&'a T::Opposite>) {
+        let (fills, unfilled) = self.levels.submit_order(order);
+        self.follow_touch();
+        (fills, unfilled)
+    }
+}
@@ -0,0 +1,106 @@
+//! Sorted `VecDeque` book
+use std::collections::VecDeque;
+
+use super::BookBackend;
+use crate::{Fill, LimitOrder, Order, OrderId};
+
+/// All resting orders in one `VecDeque` sorted by price then nonce
+///
+/// Cheap to match against the top of the book, inserting deep in the book shifts orders
+#[derive(Debug)]
+pub struct SortedDeque<T: Order>(VecDeque<T>);
+
+impl<T: Order> Default for SortedDeque<T> {
+    fn default() -> Self {
+        SortedDeque(VecDeque::default())
+    }
+}
+
+impl<T: Order + From<LimitOrder>> BookBackend<T> for SortedDeque<T> {
+    fn front(&self) -> Option<&T> {
+        self.0.front()
+    }
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+    fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+    fn compact(&mut self) {
+        self.0.make_contiguous();
+        self.0.shrink_to_fit();
+    }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.0.iter()
+    }
+    fn get(&self, order_id: OrderId) -> Option<&T> {
+        self.0.iter().find(|order| order.inner().nonce == order_id)
+    }
+    fn get_at(&self, order_id: OrderId, price: f32) -> Option<&T> {
+        let key = LimitOrder {
+            price,
+            nonce: order_id,
+            ..Default::default()
+        };
+        let idx = self.0.binary_search(&key.into()).ok()?;
+        self.0.get(idx)
+    }
+    fn remove(&mut self, order_id: OrderId) -> Option<T> {
+        let idx = self
+            .0
+            .iter()
+            .position(|order| order.inner().nonce == order_id)?;
+        self.0.remove(idx)
+    }
+    fn insert_order(&mut self, order: &T) -> Result<(), ()> {
+        if let Err(idx) = self.0.binary_search(order) {
+            self.0.insert(idx, order.clone());
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+    fn submit_order<'a>(
+        &mut self,
+        order: &'a mut T::Opposite,
+    ) -> (Vec<Fill>, Option<&'a T::Opposite>) {
+        // try add the order to the book absorbing any resting liquidity
+        let mut fills = Vec::<Fill>::default();
+        let mut sink = |fill| fills.push(fill);
+        let mut remove_count = 0;
+        if !order.is_zero() {
+            for resting_order in self.0.iter_mut() {
+                if !resting_order.crosses(order) {
+                    break;
+                }
+                // orders smaller than the remainder are consumed whole, the first which is not
+                // fills the remainder and ends the sweep
+                if resting_order.inner().amount < order.inner().amount {
+                    resting_order.fill_into(order, &mut sink);
+                    remove_count += 1;
+                } else {
+                    resting_order.fill_into(order, &mut sink);
+                    remove_count += resting_order.is_zero() as usize;
+                    break;
+                }
+            }
+        }
+
+        // Remove filled orders from the book
+        if remove_count > 0 {
+            let _ = self.0.drain(0..remove_count);
+        }
+
+        if order.is_zero() {
+            (fills, None)
+        } else {
+            (fills, Some(order))
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Per trader index over a book backend
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+};
+
+use super::BookBackend;
+use crate::{Fill, Liquidity, Order, OrderId};
+
+/// A `BookBackend` with an index of each trader's resting orders
+///
+/// Finding a trader's orders costs O(k) in their number of orders rather than a scan of the book
+#[derive(Debug)]
+pub struct TraderIndexed<T: Order, B> {
+    book: B,
+    /// Price of each resting order by trader then order id
+    traders: HashMap<u32, BTreeMap<OrderId, f32>>,
+    _order: PhantomData<T>,
+}
+
+impl<T: Order, B: Default> Default for TraderIndexed<T, B> {
+    fn default() -> Self {
+        TraderIndexed {
+            book: B::default(),
+            traders: HashMap::default(),
+            _order: PhantomData,
+        }
+    }
+}
+
+impl<T: Order, B: BookBackend<T>> TraderIndexed<T, B> {
+    /// Ids and prices of the resting orders of `trader_id` in time priority
+    pub fn order_ids_of(&self, trader_id: u32) -> impl Iterator<Item = (OrderId, f32)> + '_ {
+        self.traders
+            .get(&trader_id)
+            .into_iter()
+            .flat_map(|orders| orders.iter().map(|(order_id, price)| (*order_id, *price)))
+    }
+    /// Resting orders of `trader_id` in time priority
+    pub fn orders_of(&self, trader_id: u32) -> impl Iterator<Item = &T> {
+        self.order_ids_of(trader_id)
+            .filter_map(|(order_id, price)| self.book.get_at(order_id, price))
+    }
+    /// Number of resting orders of `trader_id`
+    pub fn count_of(&self, trader_id: u32) -> usize {
+        self.traders.get(&trader_id).map_or(0, BTreeMap::len)
+    }
+    fn unindex(&mut self, trader_id: u32, order_id: OrderId) {
+        if let Some(orders) = self.traders.get_mut(&trader_id) {
+            orders.remove(&order_id);
+            if orders.is_empty() {
+                self.traders.remove(&trader_id);
+            }
+        }
+    }
+}
+
+impl<T: Order, B: BookBackend<T>> BookBackend<T> for TraderIndexed<T, B> {
+    fn front(&self) -> Option<&T> {
+        self.book.front()
+    }
+    fn is_empty(&self) -> bool {
+        self.book.is_empty()
+    }
+    fn len(&self) -> usize {
+        self.book.len()
+    }
+    fn capacity(&self) -> usize {
+        self.book.capacity()
+    }
+    fn compact(&mut self) {
+        self.book.compact();
+        self.traders.shrink_to_fit();
+    }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.book.iter()
+    }
+    fn get(&self, order_id: OrderId) -> Option<&T> {
+        self.book.get(order_id)
+    }
+    fn get_at(&self, order_id: OrderId, price: f32) -> Option<&T> {
+        self.book.get_at(order_id, price)
+    }
+    fn remove(&mut self, order_id: OrderId) -> Option<T> {
+        let order = self.book.remove(order_id)?;
+        self.unindex(order.inner().trader_id, order_id);
+        Some(order)
+    }
+    fn insert_order(&mut self, order: &T) -> Result<(), ()> {
+        self.book.insert_order(order)?;
+        let inner = order.inner();
+        self.traders
+            .entry(inner.trader_id)
+            .or_default()
+            .insert(inner.nonce, inner.price);
+        Ok(())
+    }
+    fn submit_order<'a>(
+        &mut self,
+        order: &'a mut T::Opposite,
+    ) -> (Vec<Fill>, Option<&'a T::Opposite>) {
+        let (fills, unfilled) = self.book.submit_order(order);
+        for fill in fills
+            .iter()
+            .filter(|f| f.flags.liquidity == Liquidity::Added)
+        {
+            let price = self
+                .traders
+                .get(&fill.trader)
+                .and_then(|orders| orders.get(&fill.order_id));
+            if let Some(price) = price {
+                if self.book.get_at(fill.order_id, *price).is_none() {
+                    self.unindex(fill.trader, fill.order_id);
+                }
+            }
+        }
+        (fills, unfilled)
+    }
+}
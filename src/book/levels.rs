@@ -0,0 +1,137 @@
+//! `BTreeMap` price level book
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use super::BookBackend;
+use crate::{Fill, LimitOrder, Order, OrderId};
+
+/// FIFO queues of resting orders in a `BTreeMap` keyed by price
+///
+/// Inserting and cancelling cost O(log levels + level size) regardless of book depth
+#[derive(Debug)]
+pub struct PriceLevels<T: Order> {
+    /// Keyed by a zero nonce order at the level's price so levels sort in price priority
+    levels: BTreeMap<T, VecDeque<T>>,
+    /// Price of each resting order
+    prices: HashMap<OrderId, f32>,
+}
+
+impl<T: Order> Default for PriceLevels<T> {
+    fn default() -> Self {
+        PriceLevels {
+            levels: BTreeMap::default(),
+            prices: HashMap::default(),
+        }
+    }
+}
+
+impl<T: Order + From<LimitOrder>> PriceLevels<T> {
+    fn level_key(price: f32) -> T {
+        LimitOrder {
+            price,
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+impl<T: Order + From<LimitOrder>> BookBackend<T> for PriceLevels<T> {
+    fn front(&self) -> Option<&T> {
+        self.levels.values().next().and_then(VecDeque::front)
+    }
+    fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+    fn len(&self) -> usize {
+        self.prices.len()
+    }
+    fn capacity(&self) -> usize {
+        let queued: usize = self.levels.values().map(VecDeque::capacity).sum();
+        queued + self.prices.capacity()
+    }
+    fn compact(&mut self) {
+        for level in self.levels.values_mut() {
+            level.make_contiguous();
+            level.shrink_to_fit();
+        }
+        self.prices.shrink_to_fit();
+    }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.levels.values().flatten()
+    }
+    fn get(&self, order_id: OrderId) -> Option<&T> {
+        let price = self.prices.get(&order_id)?;
+        self.levels
+            .get(&Self::level_key(*price))?
+            .iter()
+            .find(|order| order.inner().nonce == order_id)
+    }
+    fn remove(&mut self, order_id: OrderId) -> Option<T> {
+        let key = Self::level_key(self.prices.remove(&order_id)?);
+        let level = self.levels.get_mut(&key)?;
+        let idx = level
+            .iter()
+            .position(|order| order.inner().nonce == order_id)?;
+        let order = level.remove(idx);
+        if level.is_empty() {
+            self.levels.remove(&key);
+        }
+        order
+    }
+    fn insert_order(&mut self, order: &T) -> Result<(), ()> {
+        let inner = order.inner();
+        if self.prices.contains_key(&inner.nonce) {
+            return Err(());
+        }
+        let level = self.levels.entry(Self::level_key(inner.price)).or_default();
+        // orders are usually appended, re-inserted orders keep their queue position
+        let idx = level.partition_point(|resting| resting < order);
+        level.insert(idx, order.clone());
+        self.prices.insert(inner.nonce, inner.price);
+        Ok(())
+    }
+    fn submit_order<'a>(
+        &mut self,
+        order: &'a mut T::Opposite,
+    ) -> (Vec<Fill>, Option<&'a T::Opposite>) {
+        let mut fills = Vec::<Fill>::default();
+        let mut sink = |fill| fills.push(fill);
+        while let Some(mut entry) = self.levels.first_entry() {
+            let level = entry.get_mut();
+            // orders of a level share its price, check it crosses once
+            if !level
+                .front()
+                .is_some_and(|resting_order| resting_order.crosses(order))
+            {
+                break;
+            }
+            // orders smaller than the remainder are consumed whole, the first which is not
+            // fills the remainder
+            while let Some(resting_order) = level.front_mut() {
+                let whole = resting_order.inner().amount < order.inner().amount;
+                resting_order.fill_into(order, &mut sink);
+                if whole || resting_order.is_zero() {
+                    let filled = level.pop_front().expect("front exists");
+                    self.prices.remove(&filled.inner().nonce);
+                }
+                if !whole {
+                    break;
+                }
+            }
+            if level.is_empty() {
+                entry.remove();
+            }
+            if order.is_zero() {
+                break;
+            }
+        }
+
+        if order.is_zero() {
+            (fills, None)
+        } else {
+            (fills, Some(order))
+        }
+    }
+}
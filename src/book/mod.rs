@@ -0,0 +1,193 @@
+//! Order book storage backends
+//!
+//! The market stores each side of the book in a `BookBackend`, selected at compile time:
+//!
+//! - default: `SortedDeque`, a single price-time sorted `VecDeque`
+//! - `book-btree`: `PriceLevels`, FIFO queues in a `BTreeMap` keyed by price
+//! - `book-ticks`: `TickLevels`, FIFO queues in an array indexed by tick, for dense tick ranges
+//! - `book-adaptive`: `AdaptiveLevels`, a tick array following the touch with far levels in a `BTreeMap`
+//!
+//! and wrapped in `TraderIndexed` to find each trader's resting orders without a scan.
+use crate::{Fill, OrderId};
+
+mod adaptive;
+mod deque;
+mod indexed;
+mod levels;
+mod ticks;
+
+pub use adaptive::AdaptiveLevels;
+pub use deque::SortedDeque;
+pub use indexed::TraderIndexed;
+pub use levels::PriceLevels;
+pub use ticks::TickLevels;
+
+/// One side of an order book holding resting orders in price-time priority
+pub trait BookBackend<T: crate::Order>: Default {
+    /// The best resting order
+    fn front(&self) -> Option<&T>;
+    fn is_empty(&self) -> bool;
+    /// Number of resting orders
+    fn len(&self) -> usize {
+        self.iter().count()
+    }
+    /// Order slots allocated by the underlying containers
+    fn capacity(&self) -> usize;
+    /// Defragment storage and release excess capacity
+    fn compact(&mut self);
+    /// Iterate resting orders in priority order
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a;
+    /// Find a resting order by id
+    fn get(&self, order_id: OrderId) -> Option<&T>;
+    /// Find a resting order by id given its price
+    fn get_at(&self, order_id: OrderId, _price: f32) -> Option<&T> {
+        self.get(order_id)
+    }
+    /// Remove a resting order by id
+    fn remove(&mut self, order_id: OrderId) -> Option<T>;
+    /// Insert an order into the book at the correct location
+    #[allow(clippy::result_unit_err)]
+    fn insert_order(&mut self, order: &T) -> Result<(), ()>;
+    /// Submit an order to the book
+    /// Returning fills and remaining unfilled order if any
+    fn submit_order<'a>(
+        &mut self,
+        order: &'a mut T::Opposite,
+    ) -> (Vec<Fill>, Option<&'a T::Opposite>);
+}
+
+/// Backend used by `Market`
+#[cfg(not(any(
+    feature = "book-btree",
+    feature = "book-ticks",
+    feature = "book-adaptive"
+)))]
+pub(crate) type OrderBook<T> = TraderIndexed<T, SortedDeque<T>>;
+/// Backend used by `Market`
+#[cfg(all(
+    feature = "book-btree",
+    not(any(feature = "book-ticks", feature = "book-adaptive"))
+))]
+pub(crate) type OrderBook<T> = TraderIndexed<T, PriceLevels<T>>;
+/// Backend used by `Market`
+#[cfg(all(feature = "book-ticks", not(feature = "book-adaptive")))]
+pub(crate) type OrderBook<T> = TraderIndexed<T, TickLevels<T>>;
+/// Backend used by `Market`
+#[cfg(feature = "book-adaptive")]
+pub(crate) type OrderBook<T> = TraderIndexed<T, AdaptiveLevels<T>>;
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveLevels, BookBackend, PriceLevels, SortedDeque, TickLevels};
+    use crate::{BuyLimitOrder, LimitOrder, Order, SellLimitOrder};
+
+    fn order(nonce: u64, amount: u32, price: f32) -> LimitOrder {
+        LimitOrder {
+            price,
+            nonce,
+            amount,
+            trader_id: nonce as u32,
+            ..Default::default()
+        }
+    }
+
+    /// Run the same order flow through `B` returning the fills and the resting book
+    fn exercise<B: BookBackend<BuyLimitOrder>>() -> (Vec<crate::Fill>, Vec<LimitOrder>) {
+        let mut book = B::default();
+        for (nonce, amount, price) in [(0, 10, 1.0), (1, 10, 2.0), (2, 5, 2.0), (3, 7, 1.5)] {
+            book.insert_order(&order(nonce, amount, price).into())
+                .unwrap();
+        }
+        assert!(book.insert_order(&order(0, 10, 1.0).into()).is_err());
+        assert_eq!(book.remove(3).map(|o| o.inner().amount), Some(7));
+        assert!(book.remove(3).is_none());
+
+        let mut sell: SellLimitOrder = order(4, 12, 1.5).into();
+        let (fills, unfilled) = book.submit_order(&mut sell);
+        assert!(unfilled.is_none());
+        assert_eq!(book.get(2).map(|o| o.inner().amount), Some(3));
+        assert_eq!(book.front().map(|o| o.inner().nonce), Some(2));
+        let resting = book.iter().map(|o| o.inner().clone()).collect();
+        (fills, resting)
+    }
+
+    #[test]
+    fn backends_agree() {
+        let expected = exercise::<SortedDeque<BuyLimitOrder>>();
+        assert_eq!(expected.0.len(), 4);
+        assert_eq!(
+            expected.1.iter().map(|o| o.nonce).collect::<Vec<_>>(),
+            vec![2, 0]
+        );
+        assert_eq!(exercise::<PriceLevels<BuyLimitOrder>>(), expected);
+        assert_eq!(exercise::<TickLevels<BuyLimitOrder>>(), expected);
+        // a span of 4 ticks pushes the 2.0 bids into the overflow
+        assert_eq!(exercise::<TickLevels<BuyLimitOrder, 2, 4>>(), expected);
+        assert_eq!(exercise::<AdaptiveLevels<BuyLimitOrder, 2, 4>>(), expected);
+    }
+
+    #[test]
+    fn tick_levels_off_grid_and_overflow() {
+        // 1 tick per price unit over at most 3 ticks
+        let mut book = TickLevels::<SellLimitOrder, 1, 3>::default();
+        for (nonce, price) in [(0, 10.2), (1, 9.8), (2, 11.0), (3, 20.0), (4, 10.0)] {
+            book.insert_order(&order(nonce, 1, price).into()).unwrap();
+        }
+        let priority: Vec<_> = book.iter().map(|o| o.inner().nonce).collect();
+        assert_eq!(priority, vec![1, 4, 0, 2, 3]);
+        assert_eq!(book.len(), 5);
+
+        book.remove(1);
+        assert_eq!(book.front().map(|o| o.inner().nonce), Some(4));
+        let mut buy: BuyLimitOrder = order(5, 10, 30.0).into();
+        let (fills, unfilled) = book.submit_order(&mut buy);
+        let prices: Vec<_> = fills.iter().step_by(2).map(|f| f.price).collect();
+        assert_eq!(prices, vec![10.0, 10.2, 11.0, 20.0]);
+        assert_eq!(unfilled.map(|o| o.inner().amount), Some(6));
+        assert!(book.is_empty());
+    }
+
+    /// Rest a huge price next to a negative one, whose ticks are far apart in `i64`
+    fn extreme_prices<B: BookBackend<SellLimitOrder>>() {
+        let mut book = B::default();
+        for (nonce, price) in [(0, -1.0), (1, 3.0e38), (2, -3.0e38), (3, 2.0)] {
+            book.insert_order(&order(nonce, 10, price).into()).unwrap();
+        }
+        let priority: Vec<_> = book.iter().map(|o| o.inner().nonce).collect();
+        assert_eq!(priority, vec![2, 0, 3, 1]);
+        let mut buy: BuyLimitOrder = order(4, 40, f32::MAX).into();
+        let (fills, unfilled) = book.submit_order(&mut buy);
+        assert_eq!(fills.len(), 8);
+        assert!(unfilled.is_none());
+        assert!(book.is_empty());
+    }
+
+    #[test]
+    fn tick_backends_extreme_prices() {
+        extreme_prices::<TickLevels<SellLimitOrder>>();
+        extreme_prices::<TickLevels<SellLimitOrder, 1, 3>>();
+        extreme_prices::<AdaptiveLevels<SellLimitOrder>>();
+        extreme_prices::<AdaptiveLevels<SellLimitOrder, 1, 8>>();
+    }
+
+    #[test]
+    fn adaptive_levels_follow_the_touch() {
+        // 1 tick per price unit over 8 ticks, re-placed once the touch moves 2 ticks
+        let mut book = AdaptiveLevels::<SellLimitOrder, 1, 8>::default();
+        for nonce in 0..40 {
+            book.insert_order(&order(nonce, 1, 100.0 + nonce as f32).into())
+                .unwrap();
+        }
+        let mut buy: BuyLimitOrder = order(40, 30, 200.0).into();
+        let (fills, _) = book.submit_order(&mut buy);
+        assert_eq!(fills.len(), 60);
+        assert_eq!(book.len(), 10);
+        assert_eq!(book.front().map(|o| o.inner().price), Some(130.0));
+        let priority: Vec<_> = book.iter().map(|o| o.inner().nonce).collect();
+        assert_eq!(priority, (30..40).collect::<Vec<_>>());
+        assert_eq!(book.remove(39).map(|o| o.inner().price), Some(139.0));
+        assert_eq!(book.get(31).map(|o| o.inner().price), Some(131.0));
+    }
+}
@@ -0,0 +1,334 @@
+//! Tick indexed array book
+use std::collections::{HashMap, VecDeque};
+
+use super::{BookBackend, PriceLevels};
+use crate::{Fill, LimitOrder, Order, OrderId};
+
+/// FIFO queues of resting orders in a flat array indexed by tick, with a cursor on the best level
+///
+/// Prices map to ticks of `1 / TICKS`. The array spans at most `LEVELS` ticks, growing to cover
+/// prices as orders arrive, so finding a level is O(1) and the cursor only walks empty ticks
+/// when the best level empties. Suited to dense books over a bounded tick range, orders priced
+/// outside the span rest in a `PriceLevels` overflow. Orders off the tick grid share the queue
+/// of the nearest tick in price-time order.
+#[derive(Debug)]
+pub struct TickLevels<T: Order, const TICKS: u32 = 100, const LEVELS: usize = 65_536> {
+    /// Queue of each tick from `base`
+    levels: VecDeque<VecDeque<T>>,
+    /// Tick of `levels[0]`
+    base: i64,
+    /// Tick of the best non-empty level
+    best: Option<i64>,
+    /// Tick of each order resting in `levels`
+    ticks: HashMap<OrderId, i64>,
+    /// Orders priced outside the span
+    overflow: PriceLevels<T>,
+}
+
+impl<T: Order, const TICKS: u32, const LEVELS: usize> Default for TickLevels<T, TICKS, LEVELS> {
+    fn default() -> Self {
+        TickLevels {
+            levels: VecDeque::default(),
+            base: 0,
+            best: None,
+            ticks: HashMap::default(),
+            overflow: PriceLevels::default(),
+        }
+    }
+}
+
+impl<T: Order + From<LimitOrder>, const TICKS: u32, const LEVELS: usize>
+    TickLevels<T, TICKS, LEVELS>
+{
+    pub(super) fn tick(price: f32) -> i64 {
+        (price as f64 * TICKS as f64).round() as i64
+    }
+    /// Whether better prices have lower ticks, i.e. this is the sell side
+    pub(super) fn ascending() -> bool {
+        let order = |price| {
+            T::from(LimitOrder {
+                price,
+                ..Default::default()
+            })
+        };
+        order(0.0) < order(1.0)
+    }
+    fn index(&self, tick: i64) -> usize {
+        (tick - self.base) as usize
+    }
+    /// Whether the span can grow to cover `tick`
+    ///
+    /// Ticks within `LEVELS` of the `i64` bounds, e.g. saturated from huge prices, never fit so
+    /// the span arithmetic can't overflow.
+    fn fits(&self, tick: i64) -> bool {
+        let span = LEVELS as i64;
+        if tick.checked_sub(span).is_none() || tick.checked_add(span).is_none() {
+            return false;
+        }
+        let last = self.base + self.levels.len() as i64 - 1;
+        self.levels.is_empty()
+            || tick
+                .max(last)
+                .checked_sub(tick.min(self.base))
+                .is_some_and(|width| width < span)
+    }
+    fn grow(&mut self, tick: i64) {
+        if self.levels.is_empty() {
+            self.base = tick;
+        }
+        while tick < self.base {
+            self.levels.push_front(VecDeque::default());
+            self.base -= 1;
+        }
+        while tick >= self.base + self.levels.len() as i64 {
+            self.levels.push_back(VecDeque::default());
+        }
+    }
+    /// Move the cursor from an empty best level to the next non-empty level
+    fn advance_best(&mut self) {
+        let step = if Self::ascending() { 1 } else { -1 };
+        let mut tick = self.best;
+        while let Some(current) = tick {
+            match self.levels.get(self.index(current)) {
+                Some(level) if level.is_empty() => {
+                    tick = current.checked_add(step).filter(|next| *next >= self.base)
+                }
+                Some(_) => break,
+                None => tick = None,
+            }
+        }
+        self.best = tick;
+    }
+    /// Move the array to span `LEVELS` ticks from `low`
+    ///
+    /// Orders of levels leaving the span move to the overflow and overflow orders inside the
+    /// new span move into the array, costing O(orders moved) plus a scan of the overflow and
+    /// of the array for the best level. `low` is clamped so the span stays clear of the `i64`
+    /// bounds.
+    pub(super) fn migrate(&mut self, low: i64) {
+        let span = LEVELS as i64;
+        let low = low.clamp(i64::MIN + span, i64::MAX - 2 * span);
+        let high = low + span - 1;
+        let last = self.base + self.levels.len() as i64 - 1;
+        if self.levels.is_empty() || last < low || self.base > high {
+            self.evict(self.levels.len());
+            self.levels.clear();
+            self.base = low;
+        }
+        while self.base < low {
+            self.evict(1);
+            self.levels.pop_front();
+            self.base += 1;
+        }
+        while self.base + self.levels.len() as i64 - 1 > high {
+            let outside = self.levels.len() - 1;
+            let level = std::mem::take(&mut self.levels[outside]);
+            self.demote(level);
+            self.levels.pop_back();
+        }
+
+        let inside: Vec<T> = self
+            .overflow
+            .iter()
+            .filter(|order| (low..=high).contains(&Self::tick(order.inner().price)))
+            .cloned()
+            .collect();
+        for order in inside {
+            let tick = Self::tick(order.inner().price);
+            self.overflow.remove(order.inner().nonce);
+            self.grow(tick);
+            let idx = self.index(tick);
+            let level = &mut self.levels[idx];
+            let position = level.partition_point(|resting| resting < &order);
+            level.insert(position, order.clone());
+            self.ticks.insert(order.inner().nonce, tick);
+        }
+
+        let non_empty = |level: &VecDeque<T>| !level.is_empty();
+        let best = if Self::ascending() {
+            self.levels.iter().position(non_empty)
+        } else {
+            self.levels.iter().rposition(non_empty)
+        };
+        self.best = best.map(|idx| self.base + idx as i64);
+    }
+    /// Move the orders of the first `count` levels to the overflow
+    fn evict(&mut self, count: usize) {
+        for idx in 0..count.min(self.levels.len()) {
+            let level = std::mem::take(&mut self.levels[idx]);
+            self.demote(level);
+        }
+    }
+    fn demote(&mut self, level: VecDeque<T>) {
+        for order in level {
+            self.ticks.remove(&order.inner().nonce);
+            self.overflow
+                .insert_order(&order)
+                .expect("order ids are unique");
+        }
+    }
+    fn array_front(&self) -> Option<&T> {
+        self.best
+            .and_then(|tick| self.levels[self.index(tick)].front())
+    }
+    /// Resting orders of the array in priority order
+    fn array_iter(&self) -> impl Iterator<Item = &T> {
+        let best = self.best.map(|tick| self.index(tick));
+        let (worse_up, worse_down) = match best {
+            Some(best) if Self::ascending() => (Some(self.levels.range(best..)), None),
+            Some(best) => (None, Some(self.levels.range(..=best).rev())),
+            None => (None, None),
+        };
+        worse_up
+            .into_iter()
+            .flatten()
+            .chain(worse_down.into_iter().flatten())
+            .flatten()
+    }
+}
+
+impl<T: Order + From<LimitOrder>, const TICKS: u32, const LEVELS: usize> BookBackend<T>
+    for TickLevels<T, TICKS, LEVELS>
+{
+    fn front(&self) -> Option<&T> {
+        match (self.array_front(), self.overflow.front()) {
+            (Some(array), Some(overflow)) => Some(if array <= overflow { array } else { overflow }),
+            (array, overflow) => array.or(overflow),
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.ticks.is_empty() && self.overflow.is_empty()
+    }
+    fn len(&self) -> usize {
+        self.ticks.len() + self.overflow.len()
+    }
+    fn capacity(&self) -> usize {
+        let queued: usize = self.levels.iter().map(VecDeque::capacity).sum();
+        queued + self.ticks.capacity() + self.overflow.capacity()
+    }
+    fn compact(&mut self) {
+        while self.levels.front().is_some_and(VecDeque::is_empty) {
+            self.levels.pop_front();
+            self.base += 1;
+        }
+        while self.levels.back().is_some_and(VecDeque::is_empty) {
+            self.levels.pop_back();
+        }
+        for level in self.levels.iter_mut() {
+            level.make_contiguous();
+            level.shrink_to_fit();
+        }
+        self.levels.shrink_to_fit();
+        self.ticks.shrink_to_fit();
+        self.overflow.compact();
+    }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        // merge the array and overflow, both in priority order
+        let mut array = self.array_iter().peekable();
+        let mut overflow = self.overflow.iter().peekable();
+        std::iter::from_fn(move || match (array.peek(), overflow.peek()) {
+            (Some(a), Some(o)) if o < a => overflow.next(),
+            (Some(_), _) => array.next(),
+            (None, _) => overflow.next(),
+        })
+    }
+    fn get(&self, order_id: OrderId) -> Option<&T> {
+        match self.ticks.get(&order_id) {
+            Some(tick) => self.levels[self.index(*tick)]
+                .iter()
+                .find(|order| order.inner().nonce == order_id),
+            None => self.overflow.get(order_id),
+        }
+    }
+    fn remove(&mut self, order_id: OrderId) -> Option<T> {
+        let Some(tick) = self.ticks.remove(&order_id) else {
+            return self.overflow.remove(order_id);
+        };
+        let idx = self.index(tick);
+        let level = &mut self.levels[idx];
+        let position = level
+            .iter()
+            .position(|order| order.inner().nonce == order_id)?;
+        let order = level.remove(position);
+        if level.is_empty() && self.best == Some(tick) {
+            self.advance_best();
+        }
+        order
+    }
+    fn insert_order(&mut self, order: &T) -> Result<(), ()> {
+        let inner = order.inner();
+        if self.ticks.contains_key(&inner.nonce) || self.overflow.get(inner.nonce).is_some() {
+            return Err(());
+        }
+        let tick = Self::tick(inner.price);
+        if !self.fits(tick) {
+            return self.overflow.insert_order(order);
+        }
+        self.grow(tick);
+        let improves = self.array_front().is_none_or(|best| order < best);
+        let idx = self.index(tick);
+        let level = &mut self.levels[idx];
+        // orders are usually appended, re-inserted orders keep their queue position
+        let position = level.partition_point(|resting| resting < order);
+        level.insert(position, order.clone());
+        self.ticks.insert(inner.nonce, tick);
+        if improves {
+            self.best = Some(tick);
+        }
+        Ok(())
+    }
+    fn submit_order<'a>(
+        &mut self,
+        order: &'a mut T::Opposite,
+    ) -> (Vec<Fill>, Option<&'a T::Opposite>) {
+        let mut fills = Vec::<Fill>::default();
+        let mut sink = |fill| fills.push(fill);
+        while !order.is_zero() {
+            let from_array = match (self.array_front(), self.overflow.front()) {
+                (Some(array), Some(overflow)) => array <= overflow,
+                (array, overflow) if array.is_some() || overflow.is_some() => array.is_some(),
+                _ => break,
+            };
+            if from_array {
+                let tick = self.best.expect("array has a best level");
+                let idx = self.index(tick);
+                let level = &mut self.levels[idx];
+                let resting_order = level.front_mut().expect("best level is not empty");
+                if !resting_order.crosses(order) {
+                    break;
+                }
+                resting_order.fill_into(order, &mut sink);
+                if resting_order.is_zero() {
+                    let filled = level.pop_front().expect("front exists");
+                    self.ticks.remove(&filled.inner().nonce);
+                    if level.is_empty() {
+                        self.advance_best();
+                    }
+                }
+            } else {
+                // outliers are rare, take them off the overflow one at a time
+                let front = self.overflow.front().expect("overflow is not empty");
+                if !front.crosses(order) {
+                    break;
+                }
+                let nonce = front.inner().nonce;
+                let mut resting_order = self.overflow.remove(nonce).expect("front exists");
+                resting_order.fill_into(order, &mut sink);
+                if !resting_order.is_zero() {
+                    self.overflow
+                        .insert_order(&resting_order)
+                        .expect("order was resting");
+                }
+            }
+        }
+
+        if order.is_zero() {
+            (fills, None)
+        } else {
+            (fills, Some(order))
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! Typed event subscriptions
+//!
+//! Components subscribe to the stream of one event type, e.g. fills, and are invoked for every
+//! matching event of commands run through `Market::execute`, letting optional subsystems observe
+//! the matcher without it knowing about them:
+//! ```
+//! use std::sync::mpsc;
+//! use simple_lob::{Command, Fill, Market, OrderRequest, OrderSide};
+//!
+//! let mut market = Market::default();
+//! let (tx, rx) = mpsc::channel();
+//! market.subscribe(move |fill: &Fill| tx.send(fill.amount).unwrap());
+//! market.execute(&Command::Submit(OrderRequest::new(1, 10, 1.0, OrderSide::Sell)));
+//! market.execute(&Command::Submit(OrderRequest::new(2, 10, 1.0, OrderSide::Buy)));
+//! assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![10, 10]);
+//! ```
+//! Subscribers with a `Filter` are only dispatched events passing it, selected by the bus so
+//! narrow consumers, e.g. one per trader, cost nothing for other traders' events. Level change
+//! subscribers receive changes to the best `n` price levels of each side, computed only while
+//! someone is subscribed.
+use std::collections::HashMap;
+
+use crate::{
+    depth::{DepthSnapshot, LevelChange},
+    ledger::{Correction, Trade},
+    Error, Event, Fill, LimitOrder, Market,
+};
+
+/// An event type which can be subscribed to
+pub trait Topic: 'static {
+    /// The topic's payload if `event` belongs to it
+    fn from_event(event: &Event) -> Option<&Self>;
+}
+
+/// Every event
+impl Topic for Event {
+    fn from_event(event: &Event) -> Option<&Self> {
+        Some(event)
+    }
+}
+
+impl Topic for Fill {
+    fn from_event(event: &Event) -> Option<&Self> {
+        match event {
+            Event::Fill(fill) => Some(fill),
+            _ => None,
+        }
+    }
+}
+
+/// Cancelled and expired orders
+impl Topic for LimitOrder {
+    fn from_event(event: &Event) -> Option<&Self> {
+        match event {
+            Event::Cancelled(order) | Event::Expired(order) => Some(order),
+            _ => None,
+        }
+    }
+}
+
+/// Rejected commands
+impl Topic for Error {
+    fn from_event(event: &Event) -> Option<&Self> {
+        match event {
+            Event::Rejected(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Busted trades
+impl Topic for Trade {
+    fn from_event(event: &Event) -> Option<&Self> {
+        match event {
+            Event::TradeBust(trade) => Some(trade),
+            _ => None,
+        }
+    }
+}
+
+impl Topic for Correction {
+    fn from_event(event: &Event) -> Option<&Self> {
+        match event {
+            Event::TradeCorrected(correction) => Some(correction),
+            _ => None,
+        }
+    }
+}
+
+/// Events a filtered subscriber is dispatched
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Filter {
+    /// Fills, busts and corrections
+    Trades,
+    /// Fills, cancels and expiries of the trader's orders and busts of their trades
+    Trader(u32),
+}
+
+type Handler = Box<dyn FnMut(&Event) + Send>;
+type LevelHandler = Box<dyn FnMut(&LevelChange) + Send>;
+
+/// Subscribers of a market in subscription order
+#[derive(Default)]
+pub(crate) struct EventBus {
+    subscribers: Vec<Handler>,
+    trades: Vec<Handler>,
+    traders: HashMap<u32, Vec<Handler>>,
+    /// Level change subscribers with the number of levels they follow
+    levels: Vec<(usize, LevelHandler)>,
+}
+
+fn handler<T: Topic>(mut handler: impl FnMut(&T) + Send + 'static) -> Handler {
+    Box::new(move |event| {
+        if let Some(payload) = T::from_event(event) {
+            handler(payload)
+        }
+    })
+}
+
+/// Traders an event belongs to
+fn traders(event: &Event) -> [Option<u32>; 2] {
+    match event {
+        Event::Fill(fill) => [Some(fill.trader), None],
+        Event::Cancelled(order) | Event::Expired(order) => [Some(order.trader_id), None],
+        Event::TradeBust(trade) if trade.buyer == trade.seller => [Some(trade.buyer), None],
+        Event::TradeBust(trade) => [Some(trade.buyer), Some(trade.seller)],
+        _ => [None, None],
+    }
+}
+
+impl EventBus {
+    pub fn subscribe<T: Topic>(&mut self, handler: impl FnMut(&T) + Send + 'static) {
+        self.subscribers.push(self::handler(handler));
+    }
+    pub fn subscribe_filtered<T: Topic>(
+        &mut self,
+        filter: Filter,
+        handler: impl FnMut(&T) + Send + 'static,
+    ) {
+        let handler = self::handler(handler);
+        match filter {
+            Filter::Trades => self.trades.push(handler),
+            Filter::Trader(trader_id) => self.traders.entry(trader_id).or_default().push(handler),
+        }
+    }
+    pub fn subscribe_levels(
+        &mut self,
+        levels: usize,
+        handler: impl FnMut(&LevelChange) + Send + 'static,
+    ) {
+        self.levels.push((levels, Box::new(handler)));
+    }
+    pub fn publish(&mut self, event: &Event) {
+        for subscriber in self.subscribers.iter_mut() {
+            subscriber(event);
+        }
+        if matches!(
+            event,
+            Event::Fill(_) | Event::TradeBust(_) | Event::TradeCorrected(_)
+        ) {
+            for subscriber in self.trades.iter_mut() {
+                subscriber(event);
+            }
+        }
+        if self.traders.is_empty() {
+            return;
+        }
+        for trader_id in traders(event).into_iter().flatten() {
+            for subscriber in self.traders.get_mut(&trader_id).into_iter().flatten() {
+                subscriber(event);
+            }
+        }
+    }
+    /// Most levels followed by a level change subscriber
+    pub fn level_depth(&self) -> Option<usize> {
+        self.levels.iter().map(|(levels, _)| *levels).max()
+    }
+    /// Dispatch the changes from `before` to `after` within each subscriber's levels
+    pub fn publish_levels(&mut self, before: &DepthSnapshot, after: &DepthSnapshot) {
+        let top = |depth: &DepthSnapshot, n: usize| DepthSnapshot {
+            bids: depth.bids.iter().take(n).cloned().collect(),
+            asks: depth.asks.iter().take(n).cloned().collect(),
+        };
+        for (levels, subscriber) in self.levels.iter_mut() {
+            for change in top(before, *levels).diff(&top(after, *levels)) {
+                subscriber(&change);
+            }
+        }
+    }
+}
+
+impl Market {
+    /// Invoke `handler` for every event of type `T` published by `execute`
+    pub fn subscribe<T: Topic>(&mut self, handler: impl FnMut(&T) + Send + 'static) {
+        self.bus.subscribe(handler);
+    }
+    /// Invoke `handler` for events of type `T` published by `execute` which pass `filter`
+    pub fn subscribe_filtered<T: Topic>(
+        &mut self,
+        filter: Filter,
+        handler: impl FnMut(&T) + Send + 'static,
+    ) {
+        self.bus.subscribe_filtered(filter, handler);
+    }
+    /// Invoke `handler` for changes to the best `levels` price levels of either side made by
+    /// `execute`, bids first then asks
+    pub fn subscribe_levels(
+        &mut self,
+        levels: usize,
+        handler: impl FnMut(&LevelChange) + Send + 'static,
+    ) {
+        self.bus.subscribe_levels(levels, handler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::Filter;
+    use crate::{
+        depth::LevelChange, ledger::Trade, Command, Event, Fill, LimitOrder, Market, OrderRequest,
+        OrderSide,
+    };
+
+    #[test]
+    fn typed_subscriptions() {
+        let mut lob = Market::default();
+        let (fills_tx, fills) = mpsc::channel();
+        let (busts_tx, busts) = mpsc::channel();
+        let (events_tx, events) = mpsc::channel();
+        lob.subscribe(move |fill: &Fill| fills_tx.send(fill.order_id).unwrap());
+        lob.subscribe(move |trade: &Trade| busts_tx.send(trade.trade_id).unwrap());
+        lob.subscribe(move |event: &Event| events_tx.send(event.clone()).unwrap());
+
+        let commands = [
+            Command::Submit(OrderRequest::new(1, 10, 1.0, OrderSide::Sell)),
+            Command::Submit(OrderRequest::new(2, 10, 1.0, OrderSide::Buy)),
+            Command::Bust {
+                trade_id: 0,
+                restore_liquidity: false,
+            },
+            Command::Cancel(0),
+        ];
+        let mut published = vec![];
+        for command in &commands {
+            published.extend(lob.execute(command));
+        }
+
+        assert_eq!(fills.try_iter().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(busts.try_iter().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(events.try_iter().collect::<Vec<_>>(), published);
+    }
+
+    #[test]
+    fn filtered_subscriptions() {
+        let mut lob = Market::default();
+        let (trades_tx, trades) = mpsc::channel();
+        let (trader_tx, trader) = mpsc::channel();
+        let (levels_tx, levels) = mpsc::channel();
+        lob.subscribe_filtered(Filter::Trades, move |event: &Event| {
+            trades_tx.send(event.clone()).unwrap()
+        });
+        lob.subscribe_filtered(Filter::Trader(2), move |order: &LimitOrder| {
+            trader_tx.send(order.nonce).unwrap()
+        });
+        lob.subscribe_levels(1, move |change: &LevelChange| {
+            levels_tx.send(change.clone()).unwrap()
+        });
+
+        let sell = |trader_id, price| {
+            Command::Submit(OrderRequest::new(trader_id, 10, price, OrderSide::Sell))
+        };
+        for command in [
+            sell(1, 2.0),
+            sell(2, 3.0),
+            sell(3, 4.0),
+            Command::Cancel(0),
+            Command::Cancel(1),
+            Command::Submit(OrderRequest::new(4, 5, 4.0, OrderSide::Buy)),
+        ] {
+            lob.execute(&command);
+        }
+
+        assert_eq!(trades.try_iter().count(), 2);
+        assert_eq!(trader.try_iter().collect::<Vec<_>>(), vec![1]);
+        // only the best ask is followed
+        let changes: Vec<_> = levels
+            .try_iter()
+            .map(|change| match change {
+                LevelChange::Added { level, .. } => ("added", level.price, level.amount),
+                LevelChange::Removed { level, .. } => ("removed", level.price, level.amount),
+                LevelChange::Changed { to, .. } => ("changed", to.price, to.amount),
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("added", 2.0, 10),
+                ("removed", 2.0, 10),
+                ("added", 3.0, 10),
+                ("removed", 3.0, 10),
+                ("added", 4.0, 10),
+                ("changed", 4.0, 5),
+            ]
+        );
+    }
+}
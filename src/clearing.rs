@@ -0,0 +1,142 @@
+//! End of day clearing report
+//!
+//! Summarizes each trader's trades from the market's trade history: net position, traded
+//! volume, fees and the trades themselves. Busted trades are excluded and corrected trades
+//! report their corrected values. Notionals and fees are rounded by the market's
+//! `MarketConfig::quote_rounding`.
+use std::collections::BTreeMap;
+
+use crate::{ledger::TradeId, Market, OrderSide};
+
+/// Fees charged as a fraction of trade notional
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct FeeSchedule {
+    /// Charged to the resting order's trader
+    pub maker: f64,
+    /// Charged to the incoming order's trader
+    pub taker: f64,
+}
+
+/// One side of a trade from a trader's point of view
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ClearedTrade {
+    pub trade_id: TradeId,
+    pub side: OrderSide,
+    pub amount: u32,
+    pub price: f32,
+    /// Whether the trader's order was resting
+    pub maker: bool,
+    pub fee: f64,
+}
+
+/// A trader's trading activity
+#[derive(PartialEq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TraderReport {
+    pub trader_id: u32,
+    /// Net amount bought
+    pub position: i64,
+    /// Net cash received before fees
+    pub balance: f64,
+    /// Total amount traded on either side
+    pub volume: u64,
+    pub fees: f64,
+    pub trades: Vec<ClearedTrade>,
+}
+
+/// Clearing report of every trader with at least one trade, in trader id order
+#[derive(PartialEq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ClearingReport {
+    pub traders: Vec<TraderReport>,
+}
+
+impl ClearingReport {
+    pub fn trader(&self, trader_id: u32) -> Option<&TraderReport> {
+        self.traders
+            .iter()
+            .find(|report| report.trader_id == trader_id)
+    }
+}
+
+impl Market {
+    /// Produce a clearing report of all trades charging `fees`
+    pub fn clearing_report(&self, fees: &FeeSchedule) -> ClearingReport {
+        let mut traders = BTreeMap::<u32, TraderReport>::new();
+        for trade in self.trades().iter().filter(|trade| !trade.busted) {
+            for (side, trader_id) in [
+                (OrderSide::Buy, trade.buyer),
+                (OrderSide::Sell, trade.seller),
+            ] {
+                let maker = side != trade.aggressor;
+                let notional = self.config().round_quote(trade.notional());
+                let rate = if maker { fees.maker } else { fees.taker };
+                let fee = self.config().round_quote(notional * rate);
+                let report = traders.entry(trader_id).or_insert_with(|| TraderReport {
+                    trader_id,
+                    ..Default::default()
+                });
+                let (amount, notional) = match side {
+                    OrderSide::Buy => (trade.amount as i64, -notional),
+                    OrderSide::Sell => (-(trade.amount as i64), notional),
+                };
+                report.position += amount;
+                report.balance += notional;
+                report.volume += trade.amount as u64;
+                report.fees += fee;
+                report.trades.push(ClearedTrade {
+                    trade_id: trade.trade_id,
+                    side,
+                    amount: trade.amount,
+                    price: trade.price,
+                    maker,
+                    fee,
+                });
+            }
+        }
+        ClearingReport {
+            traders: traders.into_values().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FeeSchedule;
+    use crate::{Market, OrderSide, LOB};
+
+    #[test]
+    fn reports_per_trader() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 30, 2.0, OrderSide::Buy).unwrap();
+        lob.submit_order(3, 20, 2.0, OrderSide::Buy).unwrap();
+        lob.submit_order(2, 10, 2.0, OrderSide::Buy).unwrap();
+        lob.bust_trade(1, false).unwrap();
+
+        let report = lob.clearing_report(&FeeSchedule {
+            maker: 0.001,
+            taker: 0.002,
+        });
+        assert_eq!(
+            report
+                .traders
+                .iter()
+                .map(|report| report.trader_id)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        let maker = report.trader(1).unwrap();
+        assert_eq!((maker.position, maker.volume), (-40, 40));
+        assert_eq!(maker.balance, 80.0);
+        assert!((maker.fees - 0.08).abs() < 1e-9);
+        assert!(maker.trades.iter().all(|trade| trade.maker));
+
+        let taker = report.trader(2).unwrap();
+        assert_eq!(taker.position, 40);
+        assert!((taker.fees - 0.16).abs() < 1e-9);
+        assert_eq!(taker.trades.len(), 2);
+        assert_eq!(lob.position(2).position, taker.position);
+    }
+}
@@ -0,0 +1,68 @@
+//! Time sources for the market
+//!
+//! Trades are stamped with the market clock's time. Live markets use the `SystemClock`,
+//! simulations and replays use a `SimClock` advanced by the driver.
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A source of time in nanoseconds
+pub trait Clock: Send {
+    fn now(&self) -> u64;
+}
+
+/// Wall clock time since the unix epoch
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default()
+    }
+}
+
+/// Manually advanced clock, clones share the same time
+#[derive(Clone, Default)]
+pub struct SimClock(Arc<AtomicU64>);
+
+impl SimClock {
+    pub fn new(now: u64) -> Self {
+        SimClock(Arc::new(AtomicU64::new(now)))
+    }
+    pub fn set(&self, now: u64) {
+        self.0.store(now, Ordering::Relaxed);
+    }
+    pub fn advance(&self, duration: Duration) {
+        self.0
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for SimClock {
+    fn now(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The market's clock, the system clock unless replaced
+pub(crate) struct MarketClock(pub Box<dyn Clock>);
+
+impl Default for MarketClock {
+    fn default() -> Self {
+        MarketClock(Box::new(SystemClock))
+    }
+}
+
+impl fmt::Debug for SimClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SimClock").field(&self.now()).finish()
+    }
+}
@@ -0,0 +1,354 @@
+//! Market commands and the events they produce
+//!
+//! Both have a line based text form used by replay files, e.g.
+//! ```text
+//! submit 1 buy 100 1.5
+//! submit 2 sell 10 1.5 post-only anti-internalize
+//! amend 0 50 1.25
+//! cancel 0
+//! bust 0 restore
+//! correct 1 40 1.5
+//! halt
+//! resume
+//! end-of-day
+//! ```
+use std::{fmt, str::FromStr};
+
+use crate::{
+    ledger::{Correction, Trade, TradeId},
+    Error, Fill, LimitOrder, Market, OrderFlags, OrderId, OrderRequest, OrderSide,
+};
+
+/// An instruction to the market
+#[derive(PartialEq, Clone, Debug)]
+pub enum Command {
+    Submit(OrderRequest),
+    Cancel(OrderId),
+    Amend {
+        order_id: OrderId,
+        amount: u32,
+        price: f32,
+    },
+    /// Bust a trade, optionally restoring the resting order's liquidity
+    Bust {
+        trade_id: TradeId,
+        restore_liquidity: bool,
+    },
+    /// Correct the amount and price of a trade
+    Correct {
+        trade_id: TradeId,
+        amount: u32,
+        price: f32,
+    },
+    /// Halt trading
+    Halt,
+    /// Resume trading, releasing parked orders
+    Resume,
+    /// Expire day orders and start a new session
+    EndOfDay,
+}
+
+/// Outcome of executing a `Command`
+#[derive(PartialEq, Clone, Debug)]
+pub enum Event {
+    /// An order was accepted and assigned an id
+    Accepted(OrderId),
+    Fill(Fill),
+    /// A resting order was cancelled
+    Cancelled(LimitOrder),
+    /// A resting order was amended, `new_order_id` differs if it lost priority
+    Amended {
+        order_id: OrderId,
+        new_order_id: OrderId,
+    },
+    Rejected(Error),
+    /// A trade was busted
+    TradeBust(Trade),
+    /// A trade was corrected
+    TradeCorrected(Correction),
+    /// An order submitted during a halt was parked
+    Parked(OrderRequest),
+    /// A parked order was released into matching, followed by its events
+    Released(OrderRequest),
+    /// A resting day order expired at the end of the session
+    Expired(LimitOrder),
+}
+
+impl Market {
+    /// Execute `command` returning the resulting events, which are also published to subscribers
+    pub fn execute(&mut self, command: &Command) -> Vec<Event> {
+        let levels = self.bus.level_depth();
+        let before = levels.map(|levels| self.depth(levels));
+        let events = self.apply_command(command);
+        for event in &events {
+            self.bus.publish(event);
+        }
+        if let (Some(levels), Some(before)) = (levels, before) {
+            let after = self.depth(levels);
+            self.bus.publish_levels(&before, &after);
+        }
+        events
+    }
+    /// Place `request` returning its events
+    pub(crate) fn submit_events(&mut self, request: OrderRequest) -> Vec<Event> {
+        match self.place_order(request) {
+            Ok(result) => std::iter::once(Event::Accepted(result.order_id))
+                .chain(result.fills.into_iter().map(Event::Fill))
+                .collect(),
+            Err(err) => vec![Event::Rejected(err)],
+        }
+    }
+    fn apply_command(&mut self, command: &Command) -> Vec<Event> {
+        match command {
+            Command::Submit(request) => match self.try_park(request.clone()) {
+                Ok(parked) => vec![parked],
+                Err(request) => self.submit_events(request),
+            },
+            Command::Cancel(order_id) => match self.cancel_order(*order_id) {
+                Some(order) => vec![Event::Cancelled(order)],
+                None => vec![Event::Rejected(Error::OrderNotFound(*order_id))],
+            },
+            Command::Amend {
+                order_id,
+                amount,
+                price,
+            } => match self.amend_order(*order_id, *amount, *price) {
+                Ok(result) => std::iter::once(Event::Amended {
+                    order_id: *order_id,
+                    new_order_id: result.order_id,
+                })
+                .chain(result.fills.into_iter().map(Event::Fill))
+                .collect(),
+                Err(err) => vec![Event::Rejected(err)],
+            },
+            Command::Bust {
+                trade_id,
+                restore_liquidity,
+            } => match self.bust_trade(*trade_id, *restore_liquidity) {
+                Ok(trade) => vec![Event::TradeBust(trade)],
+                Err(err) => vec![Event::Rejected(err)],
+            },
+            Command::Correct {
+                trade_id,
+                amount,
+                price,
+            } => match self.correct_trade(*trade_id, *amount, *price) {
+                Ok(correction) => vec![Event::TradeCorrected(correction)],
+                Err(err) => vec![Event::Rejected(err)],
+            },
+            Command::Halt => {
+                self.halt();
+                vec![]
+            }
+            Command::Resume => self.resume(),
+            Command::EndOfDay => self.end_of_day(),
+        }
+    }
+}
+
+/// Write the fields of `request` after `verb` in the `submit` text form
+fn write_request(f: &mut fmt::Formatter<'_>, verb: &str, request: &OrderRequest) -> fmt::Result {
+    write!(
+        f,
+        "{verb} {} {} {} {}",
+        request.trader_id,
+        request.side.as_str(),
+        request.amount,
+        request.price
+    )?;
+    if !request.flags.is_empty() {
+        write!(f, " {}", request.flags)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Submit(request) => write_request(f, "submit", request),
+            Self::Cancel(order_id) => write!(f, "cancel {order_id}"),
+            Self::Amend {
+                order_id,
+                amount,
+                price,
+            } => write!(f, "amend {order_id} {amount} {price}"),
+            Self::Bust {
+                trade_id,
+                restore_liquidity,
+            } => {
+                write!(f, "bust {trade_id}")?;
+                if *restore_liquidity {
+                    write!(f, " restore")?;
+                }
+                Ok(())
+            }
+            Self::Correct {
+                trade_id,
+                amount,
+                price,
+            } => write!(f, "correct {trade_id} {amount} {price}"),
+            Self::Halt => write!(f, "halt"),
+            Self::Resume => write!(f, "resume"),
+            Self::EndOfDay => write!(f, "end-of-day"),
+        }
+    }
+}
+
+/// A command line could not be parsed
+#[derive(PartialEq, Clone, Debug)]
+pub struct CommandParseError(pub String);
+
+impl fmt::Display for CommandParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid command: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for CommandParseError {}
+
+impl FromStr for Command {
+    type Err = CommandParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CommandParseError(s.to_string());
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        match parts.as_slice() {
+            ["submit", trader_id, side, amount, price, flags @ ..] => {
+                Ok(Command::Submit(OrderRequest {
+                    trader_id: trader_id.parse().map_err(|_| invalid())?,
+                    side: OrderSide::try_from(*side).map_err(|_| invalid())?,
+                    amount: amount.parse().map_err(|_| invalid())?,
+                    price: price.parse().map_err(|_| invalid())?,
+                    flags: OrderFlags::from_names(flags.iter().copied()).ok_or_else(invalid)?,
+                }))
+            }
+            ["cancel", order_id] => Ok(Command::Cancel(order_id.parse().map_err(|_| invalid())?)),
+            ["amend", order_id, amount, price] => Ok(Command::Amend {
+                order_id: order_id.parse().map_err(|_| invalid())?,
+                amount: amount.parse().map_err(|_| invalid())?,
+                price: price.parse().map_err(|_| invalid())?,
+            }),
+            ["bust", trade_id] => Ok(Command::Bust {
+                trade_id: trade_id.parse().map_err(|_| invalid())?,
+                restore_liquidity: false,
+            }),
+            ["bust", trade_id, "restore"] => Ok(Command::Bust {
+                trade_id: trade_id.parse().map_err(|_| invalid())?,
+                restore_liquidity: true,
+            }),
+            ["correct", trade_id, amount, price] => Ok(Command::Correct {
+                trade_id: trade_id.parse().map_err(|_| invalid())?,
+                amount: amount.parse().map_err(|_| invalid())?,
+                price: price.parse().map_err(|_| invalid())?,
+            }),
+            ["halt"] => Ok(Command::Halt),
+            ["resume"] => Ok(Command::Resume),
+            ["end-of-day"] => Ok(Command::EndOfDay),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Accepted(order_id) => write!(f, "accept {order_id}"),
+            Self::Fill(fill) => write!(
+                f,
+                "fill {} {} {} {} {} {}",
+                fill.order_id,
+                fill.trader,
+                fill.counter_party,
+                fill.side.as_str(),
+                fill.amount,
+                fill.price
+            ),
+            Self::Cancelled(order) => write!(f, "cancel {} {}", order.nonce, order.amount),
+            Self::Amended {
+                order_id,
+                new_order_id,
+            } => write!(f, "amend {order_id} {new_order_id}"),
+            Self::Rejected(err) => write!(f, "reject {err}"),
+            Self::TradeBust(trade) => write!(
+                f,
+                "bust {} {} {}",
+                trade.trade_id, trade.amount, trade.price
+            ),
+            Self::TradeCorrected(correction) => write!(
+                f,
+                "correct {} {} {} {} {}",
+                correction.trade_id,
+                correction.previous_amount,
+                correction.previous_price,
+                correction.amount,
+                correction.price
+            ),
+            Self::Parked(request) => write_request(f, "park", request),
+            Self::Released(request) => write_request(f, "release", request),
+            Self::Expired(order) => write!(f, "expire {} {}", order.nonce, order.amount),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Event};
+    use crate::{Error, Market, OrderFlags, OrderRequest, OrderSide};
+
+    #[test]
+    fn command_text_round_trip() {
+        for command in [
+            Command::Submit(OrderRequest::new(1, 100, 1.25, OrderSide::Buy)),
+            Command::Submit(OrderRequest::new(1, 5, 2.0, OrderSide::Sell).with_anti_internalize()),
+            Command::Submit(
+                OrderRequest::new(1, 5, 2.0, OrderSide::Sell)
+                    .with_flags(OrderFlags::POST_ONLY | OrderFlags::HIDDEN),
+            ),
+            Command::Cancel(7),
+            Command::Amend {
+                order_id: 7,
+                amount: 10,
+                price: 0.5,
+            },
+            Command::Bust {
+                trade_id: 3,
+                restore_liquidity: true,
+            },
+            Command::Correct {
+                trade_id: 3,
+                amount: 5,
+                price: 2.5,
+            },
+            Command::Halt,
+            Command::Resume,
+            Command::EndOfDay,
+        ] {
+            assert_eq!(command.to_string().parse(), Ok(command));
+        }
+        assert!("submit 1 buy".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn execute_commands() {
+        let mut lob = Market::default();
+        let sell = Command::Submit(OrderRequest::new(1, 100, 1.0, OrderSide::Sell));
+        assert_eq!(lob.execute(&sell), vec![Event::Accepted(0)]);
+
+        let buy = Command::Submit(OrderRequest::new(2, 40, 1.0, OrderSide::Buy));
+        let events: Vec<String> = lob.execute(&buy).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            events,
+            vec!["accept 1", "fill 0 1 2 sell 40 1", "fill 1 2 1 buy 40 1"]
+        );
+
+        assert_eq!(
+            lob.execute(&Command::Cancel(0))
+                .first()
+                .map(ToString::to_string),
+            Some("cancel 0 60".to_string())
+        );
+        assert_eq!(
+            lob.execute(&Command::Cancel(0)),
+            vec![Event::Rejected(Error::OrderNotFound(0))]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Book storage maintenance
+//!
+//! The containers holding each side of the book keep their capacity as orders leave, so a
+//! market which once held a deep book keeps its memory. `Market::compact` defragments them and
+//! releases the excess, with `MarketConfig::compaction` set the market compacts itself after
+//! orders and cancels once allocated slots outgrow resting orders.
+use crate::{book::BookBackend, Market};
+
+/// Automatic compaction threshold
+#[derive(PartialEq, Clone, Debug)]
+pub struct Compaction {
+    /// Compact once allocated order slots exceed this multiple of resting orders
+    pub ratio: f64,
+    /// Allocated order slots below which the book is never compacted
+    pub min_capacity: usize,
+}
+
+impl Default for Compaction {
+    fn default() -> Self {
+        Compaction {
+            ratio: 4.0,
+            min_capacity: 1024,
+        }
+    }
+}
+
+impl Market {
+    /// Order slots allocated by both sides of the book
+    pub fn book_capacity(&self) -> usize {
+        self.buys.capacity() + self.sells.capacity()
+    }
+    /// Defragment both sides of the book and release excess capacity, returning the number of
+    /// order slots released
+    pub fn compact(&mut self) -> usize {
+        let before = self.book_capacity();
+        self.buys.compact();
+        self.sells.compact();
+        before.saturating_sub(self.book_capacity())
+    }
+    /// Compact if the book exceeds the `MarketConfig::compaction` threshold
+    pub(crate) fn auto_compact(&mut self) {
+        let Some(compaction) = &self.config.compaction else {
+            return;
+        };
+        let capacity = self.book_capacity();
+        let resting = self.buys.len() + self.sells.len();
+        if capacity >= compaction.min_capacity
+            && capacity as f64 > resting as f64 * compaction.ratio
+        {
+            self.compact();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compaction;
+    use crate::{Market, MarketConfig, OrderSide, LOB};
+
+    /// Rest 1000 bids then cancel all but the last 10
+    fn drain(lob: &mut Market) {
+        for i in 0..1000 {
+            lob.submit_order(1, 10, i as f32, OrderSide::Buy).unwrap();
+        }
+        for order_id in 0..990 {
+            lob.cancel_order(order_id).unwrap();
+        }
+    }
+
+    #[test]
+    fn compacts_on_demand_and_by_threshold() {
+        let mut lob = Market::default();
+        drain(&mut lob);
+        assert!(lob.book_capacity() >= 1000);
+        assert!(lob.compact() >= 990);
+        assert_eq!(lob.depth(usize::MAX).bids.len(), 10);
+        assert_eq!(
+            lob.submit_order(2, 100, 990.0, OrderSide::Sell)
+                .unwrap()
+                .len(),
+            20
+        );
+
+        let mut lob = Market::new(MarketConfig {
+            compaction: Some(Compaction {
+                ratio: 2.0,
+                min_capacity: 64,
+            }),
+            ..Default::default()
+        });
+        drain(&mut lob);
+        assert!(lob.book_capacity() < 200);
+    }
+}
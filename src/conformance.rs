@@ -0,0 +1,199 @@
+//! Matching conformance suite for `LOB` implementations
+//!
+//! The bundled suite is a set of named cases, each an order stream against an empty book with
+//! the fills every order is expected to produce. Fills are written without order ids as
+//! `<trader> <counter party> <side> <amount> <price>`, resting fill first:
+//! ```text
+//! case partial fill rests the remainder
+//! > submit 1 sell 100 10.5
+//! > submit 2 buy 150 10.5
+//! < 1 2 sell 100 10.5
+//! < 2 1 buy 100 10.5
+//! ```
+//! Alternative books check they match with identical semantics by running the suite:
+//! ```
+//! use simple_lob::{conformance, Market};
+//!
+//! conformance::run(Market::default).unwrap();
+//! ```
+use std::{fmt, str::FromStr};
+
+use crate::{Command, Fill, OrderRequest, LOB};
+
+/// The bundled conformance suite
+pub const SUITE: &str = include_str!("../golden/conformance.golden");
+
+/// An order stream and the fills each order produces, in their text form
+#[derive(PartialEq, Clone, Debug)]
+pub struct Case {
+    pub name: String,
+    pub steps: Vec<(OrderRequest, Vec<String>)>,
+}
+
+/// Ways a book can fail the suite
+#[derive(PartialEq, Clone, Debug)]
+pub enum ConformanceError {
+    /// Suite line could not be parsed
+    Parse { line: usize, text: String },
+    /// The order at `step` of `case` was rejected
+    Rejected {
+        case: String,
+        step: usize,
+        error: String,
+    },
+    /// The order at `step` of `case` produced different fills
+    FillMismatch {
+        case: String,
+        step: usize,
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+}
+
+impl fmt::Display for ConformanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse { line, text } => write!(f, "line {line}: cannot parse {text:?}"),
+            Self::Rejected { case, step, error } => {
+                write!(f, "{case} step {step}: rejected {error}")
+            }
+            Self::FillMismatch {
+                case,
+                step,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{case} step {step}: expected {expected:?} got {actual:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConformanceError {}
+
+/// Text form of a fill compared by the suite
+pub fn fill_line(fill: &Fill) -> String {
+    format!(
+        "{} {} {} {} {}",
+        fill.trader,
+        fill.counter_party,
+        fill.side.as_str(),
+        fill.amount,
+        fill.price
+    )
+}
+
+impl Case {
+    /// Run the case against an empty `book`
+    pub fn check<L: LOB>(&self, book: &mut L) -> Result<(), ConformanceError>
+    where
+        L::Error: fmt::Debug,
+    {
+        for (step, (request, expected)) in self.steps.iter().enumerate() {
+            let fills = book
+                .submit(request.clone())
+                .map_err(|err| ConformanceError::Rejected {
+                    case: self.name.clone(),
+                    step,
+                    error: format!("{err:?}"),
+                })?;
+            let actual: Vec<String> = fills.iter().map(fill_line).collect();
+            if &actual != expected {
+                return Err(ConformanceError::FillMismatch {
+                    case: self.name.clone(),
+                    step,
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a suite into its cases
+pub fn parse(suite: &str) -> Result<Vec<Case>, ConformanceError> {
+    let mut cases = Vec::<Case>::default();
+    for (idx, line) in suite.lines().enumerate() {
+        let parse_err = || ConformanceError::Parse {
+            line: idx + 1,
+            text: line.to_string(),
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("case ") {
+            cases.push(Case {
+                name: name.trim().to_string(),
+                steps: vec![],
+            });
+            continue;
+        }
+        let steps = &mut cases.last_mut().ok_or_else(parse_err)?.steps;
+        let (prefix, rest) = line.split_at_checked(1).ok_or_else(parse_err)?;
+        match (prefix, Command::from_str(rest.trim())) {
+            (">", Ok(Command::Submit(request))) => steps.push((request, vec![])),
+            ("<", _) => steps
+                .last_mut()
+                .ok_or_else(parse_err)?
+                .1
+                .push(rest.trim().to_string()),
+            _ => return Err(parse_err()),
+        }
+    }
+    Ok(cases)
+}
+
+/// Run every case of the bundled suite against a book created by `new`
+pub fn run<L: LOB>(mut new: impl FnMut() -> L) -> Result<(), ConformanceError>
+where
+    L::Error: fmt::Debug,
+{
+    for case in parse(SUITE)? {
+        case.check(&mut new())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, run, ConformanceError, SUITE};
+    use crate::{Fill, Market, OrderSide, LOB};
+
+    #[test]
+    fn market_conforms() {
+        assert!(parse(SUITE).unwrap().len() >= 8);
+        assert_eq!(run(Market::default), Ok(()));
+    }
+
+    /// Fills at the incoming order's price instead of the resting order's
+    #[derive(Default)]
+    struct AggressorPriced(Market);
+
+    impl LOB for AggressorPriced {
+        type Error = crate::Error;
+        fn submit_order(
+            &mut self,
+            trader_id: u32,
+            amount: u32,
+            price: f32,
+            side: OrderSide,
+        ) -> Result<Vec<Fill>, Self::Error> {
+            let fills = self.0.submit_order(trader_id, amount, price, side)?;
+            Ok(fills
+                .into_iter()
+                .map(|fill| Fill { price, ..fill })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn detects_divergence() {
+        assert!(matches!(
+            run(AggressorPriced::default),
+            Err(ConformanceError::FillMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+//! Consolidated depth across venues
+//!
+//! `Aggregator` merges the depth of several books, e.g. the local `Market` and `ShadowBook`s of
+//! external venues, into one view attributing each price level's amount to its venues.
+use std::collections::BTreeMap;
+
+use crate::{
+    depth::{DepthQuery, Level},
+    OrderSide,
+};
+
+/// Resting amount at a price summed across venues
+#[derive(PartialEq, Clone, Debug)]
+pub struct VenueLevel {
+    pub price: f32,
+    /// Total amount across venues
+    pub amount: u64,
+    /// Amount by venue name
+    pub venues: BTreeMap<String, u64>,
+}
+
+/// Consolidated price levels of both sides, best price first
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct ConsolidatedDepth {
+    pub bids: Vec<VenueLevel>,
+    pub asks: Vec<VenueLevel>,
+}
+
+/// Named books to consolidate
+#[derive(Default)]
+pub struct Aggregator<'a> {
+    venues: Vec<(String, &'a dyn DepthQuery)>,
+}
+
+impl<'a> Aggregator<'a> {
+    /// Include `book` under the name `venue`
+    pub fn venue(mut self, venue: &str, book: &'a dyn DepthQuery) -> Self {
+        self.venues.push((venue.to_string(), book));
+        self
+    }
+    /// The best `max_levels` consolidated price levels of each side
+    pub fn depth(&self, max_levels: usize) -> ConsolidatedDepth {
+        let depths: Vec<_> = self
+            .venues
+            .iter()
+            .map(|(venue, book)| (venue, book.depth(max_levels)))
+            .collect();
+        let side = |side: OrderSide| {
+            let levels = depths.iter().flat_map(|(venue, depth)| {
+                let levels = match side {
+                    OrderSide::Buy => &depth.bids,
+                    OrderSide::Sell => &depth.asks,
+                };
+                levels.iter().map(move |level| (*venue, level))
+            });
+            merge(levels, &side, max_levels)
+        };
+        ConsolidatedDepth {
+            bids: side(OrderSide::Buy),
+            asks: side(OrderSide::Sell),
+        }
+    }
+}
+
+/// Merge venues' levels of one side into the best `max_levels` consolidated levels
+fn merge<'a>(
+    levels: impl Iterator<Item = (&'a String, &'a Level)>,
+    side: &OrderSide,
+    max_levels: usize,
+) -> Vec<VenueLevel> {
+    let mut levels: Vec<_> = levels.collect();
+    levels.sort_by(|(_, a), (_, b)| match side {
+        OrderSide::Buy => b.price.total_cmp(&a.price),
+        OrderSide::Sell => a.price.total_cmp(&b.price),
+    });
+    let mut merged = Vec::<VenueLevel>::default();
+    for (venue, level) in levels {
+        let consolidated = match merged.last_mut() {
+            Some(consolidated) if consolidated.price == level.price => consolidated,
+            _ => {
+                if merged.len() == max_levels {
+                    break;
+                }
+                merged.push(VenueLevel {
+                    price: level.price,
+                    amount: 0,
+                    venues: BTreeMap::default(),
+                });
+                merged.last_mut().expect("pushed")
+            }
+        };
+        consolidated.amount += level.amount;
+        *consolidated.venues.entry(venue.clone()).or_default() += level.amount;
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Aggregator;
+    use crate::{
+        depth::{DepthSnapshot, Level},
+        shadow::ShadowBook,
+        Market, OrderSide, LOB,
+    };
+
+    #[test]
+    fn attributes_venues() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 1.0, OrderSide::Buy).unwrap();
+        lob.submit_order(2, 50, 1.5, OrderSide::Sell).unwrap();
+        let level = |price, amount| Level {
+            price,
+            amount,
+            orders: 1,
+        };
+        let external = ShadowBook::from_depth(DepthSnapshot {
+            bids: vec![level(1.25, 10), level(1.0, 30)],
+            asks: vec![level(1.5, 20), level(1.75, 5)],
+        });
+
+        let depth = Aggregator::default()
+            .venue("local", &lob)
+            .venue("ext", &external)
+            .depth(2);
+        let bids: Vec<_> = depth
+            .bids
+            .iter()
+            .map(|level| (level.price, level.amount))
+            .collect();
+        assert_eq!(bids, vec![(1.25, 10), (1.0, 130)]);
+        assert_eq!(depth.asks[0].amount, 70);
+        assert_eq!(depth.asks[0].venues.get("local"), Some(&50));
+        assert_eq!(depth.asks[0].venues.get("ext"), Some(&20));
+        assert_eq!(depth.asks[1].venues.len(), 1);
+    }
+}
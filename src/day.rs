@@ -0,0 +1,202 @@
+//! Trading days
+//!
+//! Orders flagged `day` rest only until the end of the session. `Market::end_of_day` expires
+//! them, publishing each as `Event::Expired`, and rolls the session's statistics into the
+//! market's history, starting a new session with fresh counters including `trade_stats`:
+//! ```text
+//! > submit 1 sell 10 1.5 day
+//! < accept 0
+//! > end-of-day
+//! < expire 0 10
+//! ```
+//! Day orders parked during a halt expire too, each is released and rejected with
+//! `Error::Expired` rather than entering the next session.
+//!
+//! Session statistics cover the trades of the session which have not been busted, busting or
+//! correcting a trade of an earlier session does not change its rolled statistics.
+use std::collections::VecDeque;
+
+use crate::{book::BookBackend, Error, Event, Market, Order, OrderFlags, OrderId, OrderRequest};
+
+/// Trading statistics of a session
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct SessionStats {
+    /// First, highest, lowest and last trade prices, `None` without trades
+    pub open: Option<f32>,
+    pub high: Option<f32>,
+    pub low: Option<f32>,
+    pub close: Option<f32>,
+    pub trades: u64,
+    pub volume: u64,
+}
+
+/// The current session starts at the ledger's `stats_from` trade
+#[derive(Debug, Default)]
+pub(crate) struct DayState {
+    /// Statistics of the completed sessions in order
+    pub history: Vec<SessionStats>,
+}
+
+impl Market {
+    /// Statistics of the current session
+    pub fn session_stats(&self) -> SessionStats {
+        let mut stats = SessionStats::default();
+        for trade in self.trades()[self.ledger.stats_from() as usize..]
+            .iter()
+            .filter(|trade| !trade.busted)
+        {
+            stats.open.get_or_insert(trade.price);
+            stats.high = Some(stats.high.map_or(trade.price, |high| high.max(trade.price)));
+            stats.low = Some(stats.low.map_or(trade.price, |low| low.min(trade.price)));
+            stats.close = Some(trade.price);
+            stats.trades += 1;
+            stats.volume += trade.amount as u64;
+        }
+        stats
+    }
+    /// Statistics of the completed sessions in order
+    pub fn session_history(&self) -> &[SessionStats] {
+        &self.day.history
+    }
+    /// Expire all resting and parked day orders and roll the session statistics, returning the
+    /// expiries
+    pub fn end_of_day(&mut self) -> Vec<Event> {
+        let is_day = |flags: OrderFlags| flags.contains(OrderFlags::DAY);
+        let order_ids: Vec<OrderId> = self
+            .buys
+            .iter()
+            .map(Order::inner)
+            .chain(self.sells.iter().map(Order::inner))
+            .filter(|order| is_day(order.flags))
+            .map(|order| order.nonce)
+            .collect();
+        let mut expired: Vec<Event> = order_ids
+            .into_iter()
+            .filter_map(|order_id| self.cancel_order(order_id))
+            .map(Event::Expired)
+            .collect();
+        let (parked, kept): (VecDeque<OrderRequest>, _) = std::mem::take(&mut self.halt.parked)
+            .into_iter()
+            .partition(|request| is_day(request.flags));
+        self.halt.parked = kept;
+        expired.extend(
+            parked
+                .into_iter()
+                .flat_map(|request| [Event::Released(request), Event::Rejected(Error::Expired)]),
+        );
+        let stats = self.session_stats();
+        self.day.history.push(stats);
+        self.ledger.roll_stats();
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionStats;
+    use crate::{
+        halt::HaltPolicy, ledger::TradeStats, Command, Error, Event, Market, MarketConfig,
+        OrderFlags, OrderRequest, OrderSide,
+    };
+
+    #[test]
+    fn end_of_day_expires_day_orders() {
+        let mut lob = Market::default();
+        let day = OrderRequest::new(1, 10, 1.5, OrderSide::Sell).with_flags(OrderFlags::DAY);
+        lob.execute(&Command::Submit(day));
+        lob.execute(&Command::Submit(OrderRequest::new(
+            1,
+            10,
+            1.6,
+            OrderSide::Sell,
+        )));
+        let buy = OrderRequest::new(2, 4, 2.0, OrderSide::Buy).with_flags(OrderFlags::DAY);
+        lob.execute(&Command::Submit(buy));
+
+        let events: Vec<String> = lob
+            .execute(&Command::EndOfDay)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(events, vec!["expire 0 6"]);
+        assert_eq!(lob.depth(1).asks[0].price, 1.6);
+        assert!(matches!(
+            lob.execute(&Command::Cancel(0))[..],
+            [Event::Rejected(_)]
+        ));
+        assert_eq!(lob.trade_stats(), &TradeStats::default());
+
+        // busting a trade of the rolled session leaves the new session's counters alone
+        lob.bust_trade(0, false).unwrap();
+        assert_eq!(lob.trade_stats(), &TradeStats::default());
+    }
+
+    #[test]
+    fn end_of_day_expires_parked_day_orders() {
+        let mut lob = Market::new(MarketConfig {
+            halt: HaltPolicy::Park,
+            ..Default::default()
+        });
+        lob.execute(&Command::Halt);
+        let day = OrderRequest::new(1, 10, 1.5, OrderSide::Sell).with_flags(OrderFlags::DAY);
+        lob.execute(&Command::Submit(day.clone()));
+        let gtc = OrderRequest::new(2, 5, 1.4, OrderSide::Buy);
+        lob.execute(&Command::Submit(gtc.clone()));
+
+        assert_eq!(
+            lob.end_of_day(),
+            vec![Event::Released(day), Event::Rejected(Error::Expired)]
+        );
+        assert_eq!(lob.parked().collect::<Vec<_>>(), vec![&gtc]);
+        lob.execute(&Command::Resume);
+        assert!(lob.depth(1).asks.is_empty());
+        assert_eq!(lob.depth(1).bids[0].amount, 5);
+    }
+
+    #[test]
+    fn sessions_roll_statistics() {
+        let mut lob = Market::default();
+        lob.execute(&Command::Submit(OrderRequest::new(
+            1,
+            30,
+            1.5,
+            OrderSide::Sell,
+        )));
+        lob.execute(&Command::Submit(OrderRequest::new(
+            1,
+            30,
+            1.2,
+            OrderSide::Sell,
+        )));
+        for amount in [10, 25] {
+            lob.execute(&Command::Submit(OrderRequest::new(
+                2,
+                amount,
+                1.5,
+                OrderSide::Buy,
+            )));
+        }
+        lob.end_of_day();
+        assert_eq!(
+            lob.session_history(),
+            &[SessionStats {
+                open: Some(1.2),
+                high: Some(1.5),
+                low: Some(1.2),
+                close: Some(1.5),
+                trades: 3,
+                volume: 35,
+            }]
+        );
+        assert_eq!(lob.session_stats(), SessionStats::default());
+
+        lob.execute(&Command::Submit(OrderRequest::new(
+            2,
+            5,
+            1.5,
+            OrderSide::Buy,
+        )));
+        assert_eq!(lob.session_stats().volume, 5);
+        assert_eq!(lob.session_stats().open, Some(1.5));
+    }
+}
@@ -0,0 +1,298 @@
+//! Aggregated book depth
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use crate::{book::BookBackend, Market, Order, OrderId, OrderSide};
+
+/// Aggregated resting liquidity at a price
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Level {
+    pub price: f32,
+    /// Total resting amount
+    pub amount: u64,
+    /// Number of resting orders
+    pub orders: usize,
+}
+
+/// Price levels of both sides of the book, best price first
+#[derive(PartialEq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DepthSnapshot {
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+/// Resting amount of each trader at a price
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TraderLevel {
+    pub price: f32,
+    /// Total resting amount by trader id
+    pub traders: BTreeMap<u32, u64>,
+}
+
+/// Price levels of both sides broken down by trader, best price first
+#[derive(PartialEq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TraderDepth {
+    pub bids: Vec<TraderLevel>,
+    pub asks: Vec<TraderLevel>,
+}
+
+/// Depth queries shared by books with aggregated price levels
+pub trait DepthQuery {
+    /// Snapshot the best `max_levels` price levels of each side
+    fn depth(&self, max_levels: usize) -> DepthSnapshot;
+    fn best_bid(&self) -> Option<Level> {
+        self.depth(1).bids.pop()
+    }
+    fn best_ask(&self) -> Option<Level> {
+        self.depth(1).asks.pop()
+    }
+    /// Midpoint of the best bid and ask
+    fn mid_price(&self) -> Option<f32> {
+        let depth = self.depth(1);
+        Some((depth.bids.first()?.price + depth.asks.first()?.price) / 2.0)
+    }
+}
+
+/// A difference between two price levels
+#[derive(PartialEq, Clone, Debug)]
+pub enum LevelChange {
+    /// A price level which did not exist before
+    Added { side: OrderSide, level: Level },
+    /// A price level which no longer exists
+    Removed { side: OrderSide, level: Level },
+    /// A price level whose size changed
+    Changed {
+        side: OrderSide,
+        from: Level,
+        to: Level,
+    },
+}
+
+impl DepthSnapshot {
+    /// Changes required to turn `self` into `other`, bids first then asks in price priority
+    pub fn diff(&self, other: &DepthSnapshot) -> Vec<LevelChange> {
+        let mut changes = Vec::<LevelChange>::default();
+        diff_side(&self.bids, &other.bids, OrderSide::Buy, &mut changes);
+        diff_side(&self.asks, &other.asks, OrderSide::Sell, &mut changes);
+        changes
+    }
+}
+
+/// Merge two sides sorted in priority order recording any differences
+fn diff_side(from: &[Level], to: &[Level], side: OrderSide, changes: &mut Vec<LevelChange>) {
+    // ordering of prices from best to worst
+    let priority = |a: &Level, b: &Level| match side {
+        OrderSide::Buy => b.price.total_cmp(&a.price),
+        OrderSide::Sell => a.price.total_cmp(&b.price),
+    };
+    let (mut from, mut to) = (from.iter().peekable(), to.iter().peekable());
+    loop {
+        let ordering = match (from.peek(), to.peek()) {
+            (Some(a), Some(b)) => priority(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        match ordering {
+            Ordering::Less => changes.push(LevelChange::Removed {
+                side: side.clone(),
+                level: from.next().cloned().expect("peeked"),
+            }),
+            Ordering::Greater => changes.push(LevelChange::Added {
+                side: side.clone(),
+                level: to.next().cloned().expect("peeked"),
+            }),
+            Ordering::Equal => {
+                let (a, b) = (from.next().expect("peeked"), to.next().expect("peeked"));
+                if a != b {
+                    changes.push(LevelChange::Changed {
+                        side: side.clone(),
+                        from: a.clone(),
+                        to: b.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Aggregate the first `max_levels` price levels of `book`
+fn levels<T: Order>(book: &impl BookBackend<T>, max_levels: usize) -> Vec<Level> {
+    let mut levels = Vec::<Level>::default();
+    for order in book.iter().map(Order::inner) {
+        match levels.last_mut() {
+            Some(level) if level.price == order.price => {
+                level.amount += order.amount as u64;
+                level.orders += 1;
+            }
+            _ => {
+                if levels.len() == max_levels {
+                    break;
+                }
+                levels.push(Level {
+                    price: order.price,
+                    amount: order.amount as u64,
+                    orders: 1,
+                });
+            }
+        }
+    }
+    levels
+}
+
+/// Amount resting ahead of `order_id` at its price level
+fn queue_position<T: Order>(book: &impl BookBackend<T>, order_id: OrderId) -> Option<u64> {
+    let price = book.get(order_id)?.inner().price;
+    Some(
+        book.iter()
+            .map(Order::inner)
+            .skip_while(|order| order.price != price)
+            .take_while(|order| order.nonce != order_id)
+            .map(|order| order.amount as u64)
+            .sum(),
+    )
+}
+
+/// Break down the first `max_levels` price levels of `book` by trader
+fn trader_levels<T: Order>(book: &impl BookBackend<T>, max_levels: usize) -> Vec<TraderLevel> {
+    let mut levels = Vec::<TraderLevel>::default();
+    for order in book.iter().map(Order::inner) {
+        let level = match levels.last_mut() {
+            Some(level) if level.price == order.price => level,
+            _ => {
+                if levels.len() == max_levels {
+                    break;
+                }
+                levels.push(TraderLevel {
+                    price: order.price,
+                    traders: BTreeMap::default(),
+                });
+                levels.last_mut().expect("pushed")
+            }
+        };
+        *level.traders.entry(order.trader_id).or_default() += order.amount as u64;
+    }
+    levels
+}
+
+impl Market {
+    /// Snapshot the best `max_levels` price levels of each side
+    pub fn depth(&self, max_levels: usize) -> DepthSnapshot {
+        DepthSnapshot {
+            bids: levels(&self.buys, max_levels),
+            asks: levels(&self.sells, max_levels),
+        }
+    }
+}
+
+impl DepthQuery for Market {
+    fn depth(&self, max_levels: usize) -> DepthSnapshot {
+        Market::depth(self, max_levels)
+    }
+}
+
+impl Market {
+    /// Amount resting ahead of `order_id` in time priority at its price level, `None` if the
+    /// order is not resting
+    pub fn queue_position(&self, order_id: OrderId) -> Option<u64> {
+        queue_position(&self.buys, order_id).or_else(|| queue_position(&self.sells, order_id))
+    }
+    /// Resting amount of each trader in the best `max_levels` price levels of each side
+    pub fn depth_by_trader(&self, max_levels: usize) -> TraderDepth {
+        TraderDepth {
+            bids: trader_levels(&self.buys, max_levels),
+            asks: trader_levels(&self.sells, max_levels),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Level, LevelChange};
+    use crate::{Market, OrderSide, LOB};
+
+    fn level(price: f32, amount: u64, orders: usize) -> Level {
+        Level {
+            price,
+            amount,
+            orders,
+        }
+    }
+
+    #[test]
+    fn aggregates_levels() {
+        let mut lob = Market::default();
+        for (amount, price) in [(100, 2.0), (50, 2.0), (10, 1.0), (5, 0.5)] {
+            lob.submit_order(1, amount, price, OrderSide::Buy).unwrap();
+        }
+        lob.submit_order(2, 20, 3.0, OrderSide::Sell).unwrap();
+
+        let depth = lob.depth(2);
+        assert_eq!(depth.bids, vec![level(2.0, 150, 2), level(1.0, 10, 1)]);
+        assert_eq!(depth.asks, vec![level(3.0, 20, 1)]);
+    }
+
+    #[test]
+    fn breaks_down_by_trader() {
+        let mut lob = Market::default();
+        for (trader, amount, price) in [(1, 100, 2.0), (2, 50, 2.0), (1, 25, 2.0), (3, 10, 1.0)] {
+            lob.submit_order(trader, amount, price, OrderSide::Buy)
+                .unwrap();
+        }
+        let depth = lob.depth_by_trader(usize::MAX);
+        assert_eq!(depth.bids.len(), 2);
+        assert_eq!(depth.bids[0].traders, [(1, 125), (2, 50)].into());
+        assert_eq!(depth.bids[1].traders, [(3, 10)].into());
+        assert!(depth.asks.is_empty());
+    }
+
+    #[test]
+    fn queue_positions() {
+        let mut lob = Market::default();
+        for (amount, price) in [(100, 2.0), (50, 2.0), (10, 1.0), (20, 2.0)] {
+            lob.submit_order(1, amount, price, OrderSide::Sell).unwrap();
+        }
+        assert_eq!(lob.queue_position(3), Some(150));
+        assert_eq!(lob.queue_position(2), Some(0));
+
+        lob.cancel_order(1);
+        lob.submit_order(2, 40, 2.0, OrderSide::Buy).unwrap();
+        assert_eq!(lob.queue_position(3), Some(70));
+        assert_eq!(lob.queue_position(2), None);
+    }
+
+    #[test]
+    fn diff_snapshots() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 2.0, OrderSide::Buy).unwrap();
+        lob.submit_order(1, 100, 1.0, OrderSide::Buy).unwrap();
+        lob.submit_order(2, 20, 3.0, OrderSide::Sell).unwrap();
+        let before = lob.depth(usize::MAX);
+
+        lob.submit_order(3, 120, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(4, 10, 2.5, OrderSide::Sell).unwrap();
+        let after = lob.depth(usize::MAX);
+
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                LevelChange::Removed {
+                    side: OrderSide::Buy,
+                    level: level(2.0, 100, 1),
+                },
+                LevelChange::Added {
+                    side: OrderSide::Sell,
+                    level: level(2.0, 20, 1),
+                },
+                LevelChange::Added {
+                    side: OrderSide::Sell,
+                    level: level(2.5, 10, 1),
+                },
+            ]
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+//! Market errors
+use std::fmt;
+
+use crate::{ledger::TradeId, OrderFlags, OrderId};
+
+/// Reasons the market can reject a request
+#[derive(PartialEq, Clone, Debug)]
+pub enum Error {
+    /// Order amount was zero
+    ZeroAmount,
+    /// No resting order with the given id
+    OrderNotFound(OrderId),
+    /// No market is listed for the symbol
+    UnknownSymbol(String),
+    /// The book has no capacity for another resting order
+    BookFull,
+    /// No trade with the given id, or it was already busted
+    TradeNotFound(TradeId),
+    /// The trader has the maximum number of resting orders allowed
+    OrderLimit,
+    /// Price is not finite, or negative without `MarketConfig::allow_negative_prices`
+    InvalidPrice(f32),
+    /// An order with the given id is already resting
+    DuplicateOrder(OrderId),
+    /// Loaded orders would cross the book
+    CrossedBook,
+    /// Rejected by a pre-trade hook for the given reason
+    Rejected(String),
+    /// The priority source stamped an order at or below an earlier order's priority
+    StalePriority(u64),
+    /// The order carries flags the market does not enforce
+    UnsupportedFlags(OrderFlags),
+    /// A post-only order would have traded on entry
+    PostOnlyCrosses,
+    /// The market is halted
+    Halted,
+    /// A day order parked during a halt expired at the end of the session
+    Expired,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroAmount => write!(f, "order amount is zero"),
+            Self::OrderNotFound(order_id) => write!(f, "order {order_id} not found"),
+            Self::UnknownSymbol(symbol) => write!(f, "unknown symbol: {symbol}"),
+            Self::BookFull => write!(f, "book is full"),
+            Self::TradeNotFound(trade_id) => write!(f, "trade {trade_id} not found"),
+            Self::OrderLimit => write!(f, "resting order limit reached"),
+            Self::InvalidPrice(price) => write!(f, "invalid price: {price}"),
+            Self::DuplicateOrder(order_id) => write!(f, "order {order_id} already exists"),
+            Self::CrossedBook => write!(f, "book would be crossed"),
+            Self::Rejected(reason) => write!(f, "rejected: {reason}"),
+            Self::StalePriority(priority) => write!(f, "priority {priority} is stale"),
+            Self::UnsupportedFlags(flags) => write!(f, "unsupported order flags: {flags}"),
+            Self::PostOnlyCrosses => write!(f, "post-only order would trade"),
+            Self::Halted => write!(f, "market is halted"),
+            Self::Expired => write!(f, "day order expired"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
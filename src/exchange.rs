@@ -0,0 +1,459 @@
+//! A collection of markets keyed by symbol
+//!
+//! Orders on every market pass a portfolio risk check which can limit each trader's notional
+//! exposure summed across all markets.
+//!
+//! Each market is listed with a description of its instrument so clients can discover what
+//! trades where, `list_markets` and `market_info` report it along with the market's status.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    book::BookBackend,
+    hooks::{PostTradeHook, PreTradeHook},
+    Command, Error, Event, Fill, LimitOrder, Market, Order, OrderId, OrderRequest, OrderResult,
+};
+
+/// Traders' exposure on each market and the limit of their total
+#[derive(Default, Debug)]
+struct Portfolio {
+    max_notional: Option<f64>,
+    /// Exposure by symbol then trader, as of each market's last change
+    exposures: BTreeMap<String, BTreeMap<u32, f64>>,
+}
+
+/// A trader's exposure on `market`: the notional of their resting orders plus the absolute
+/// cost of their net position
+fn exposure(market: &Market, trader_id: u32) -> f64 {
+    let resting: f64 = market
+        .orders_for(trader_id)
+        .iter()
+        .map(|order| order.amount as f64 * order.price.abs() as f64)
+        .sum();
+    resting + market.position(trader_id).balance.abs()
+}
+
+/// Exposure of every trader with resting orders or trades on `market`
+fn exposures(market: &Market) -> BTreeMap<u32, f64> {
+    let traders: BTreeSet<u32> = market
+        .buys
+        .iter()
+        .map(|order| order.inner().trader_id)
+        .chain(market.sells.iter().map(|order| order.inner().trader_id))
+        .chain(
+            market
+                .trades()
+                .iter()
+                .flat_map(|trade| [trade.buyer, trade.seller]),
+        )
+        .collect();
+    traders
+        .into_iter()
+        .map(|trader_id| (trader_id, exposure(market, trader_id)))
+        .collect()
+}
+
+/// Portfolio risk check installed on each listed market
+struct PortfolioRisk {
+    symbol: String,
+    portfolio: Arc<Mutex<Portfolio>>,
+}
+
+impl PreTradeHook for PortfolioRisk {
+    fn check(&mut self, market: &Market, request: &mut OrderRequest) -> Result<(), Error> {
+        let portfolio = self.portfolio.lock().expect("portfolio lock");
+        let Some(max_notional) = portfolio.max_notional else {
+            return Ok(());
+        };
+        let elsewhere: f64 = portfolio
+            .exposures
+            .iter()
+            .filter(|(symbol, _)| **symbol != self.symbol)
+            .filter_map(|(_, traders)| traders.get(&request.trader_id))
+            .sum();
+        let order = request.amount as f64 * request.price.abs() as f64;
+        if elsewhere + exposure(market, request.trader_id) + order > max_notional {
+            return Err(Error::Rejected("portfolio notional limit".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl PostTradeHook for PortfolioRisk {
+    fn after_trade(&mut self, market: &Market, request: &OrderRequest, result: &OrderResult) {
+        let mut portfolio = self.portfolio.lock().expect("portfolio lock");
+        let traders = portfolio.exposures.entry(self.symbol.clone()).or_default();
+        let counter_parties = result.fills.iter().map(|fill| fill.trader);
+        for trader_id in std::iter::once(request.trader_id).chain(counter_parties) {
+            traders.insert(trader_id, exposure(market, trader_id));
+        }
+    }
+}
+
+/// Description of a listed instrument, for discovery only and not enforced by the market
+#[derive(PartialEq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Instrument {
+    /// Asset traded
+    pub base: String,
+    /// Asset prices are quoted in
+    pub quote: String,
+    /// Minimum price increment
+    pub tick_size: f32,
+    /// Size of one unit of order amount in the base asset
+    pub lot_size: f64,
+}
+
+/// Whether a listed market is trading
+#[derive(PartialEq, Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum MarketStatus {
+    Open,
+    Halted,
+}
+
+/// A listed market's instrument and status
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MarketInfo {
+    pub symbol: String,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub instrument: Instrument,
+    pub status: MarketStatus,
+}
+
+/// A market, its instrument and trade tape
+struct Listing {
+    market: Market,
+    instrument: Instrument,
+    fills: Vec<Fill>,
+}
+
+impl Listing {
+    fn info(&self, symbol: &str) -> MarketInfo {
+        MarketInfo {
+            symbol: symbol.to_string(),
+            instrument: self.instrument.clone(),
+            status: if self.market.is_halted() {
+                MarketStatus::Halted
+            } else {
+                MarketStatus::Open
+            },
+        }
+    }
+    /// Add the fills among `events` to the tape
+    fn record_fills(&mut self, events: &[Event]) {
+        self.fills
+            .extend(events.iter().filter_map(|event| match event {
+                Event::Fill(fill) => Some(fill.clone()),
+                _ => None,
+            }));
+    }
+}
+
+/// Routes orders to markets by symbol, recording each market's fills
+#[derive(Default)]
+pub struct Exchange {
+    listings: BTreeMap<String, Listing>,
+    portfolio: Arc<Mutex<Portfolio>>,
+}
+
+impl Exchange {
+    /// Add an empty market for `symbol` with an undescribed instrument, returns false if it
+    /// already exists
+    pub fn add_market(&mut self, symbol: &str) -> bool {
+        self.add_market_with(symbol, Instrument::default())
+    }
+    /// Add an empty market for `symbol` trading `instrument`, returns false if it already exists
+    pub fn add_market_with(&mut self, symbol: &str, instrument: Instrument) -> bool {
+        if self.listings.contains_key(symbol) {
+            return false;
+        }
+        let mut market = Market::default();
+        let risk = || PortfolioRisk {
+            symbol: symbol.to_string(),
+            portfolio: Arc::clone(&self.portfolio),
+        };
+        market.add_pre_trade_hook(risk());
+        market.add_post_trade_hook(risk());
+        self.listings.insert(
+            symbol.to_string(),
+            Listing {
+                market,
+                instrument,
+                fills: vec![],
+            },
+        );
+        true
+    }
+    /// Reject orders which would take a trader's exposure summed across all markets above
+    /// `max_notional`, or remove the limit with `None`
+    pub fn set_portfolio_limit(&mut self, max_notional: Option<f64>) {
+        self.portfolio.lock().expect("portfolio lock").max_notional = max_notional;
+    }
+    /// A trader's exposure summed across all markets
+    pub fn portfolio_exposure(&self, trader_id: u32) -> f64 {
+        self.listings
+            .values()
+            .map(|listing| exposure(&listing.market, trader_id))
+            .sum()
+    }
+    /// Listed symbols in order
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.listings.keys().map(String::as_str)
+    }
+    /// Instrument and status of every listed market in symbol order
+    pub fn list_markets(&self) -> Vec<MarketInfo> {
+        self.listings
+            .iter()
+            .map(|(symbol, listing)| listing.info(symbol))
+            .collect()
+    }
+    pub fn market_info(&self, symbol: &str) -> Option<MarketInfo> {
+        self.listings
+            .get(symbol)
+            .map(|listing| listing.info(symbol))
+    }
+    pub fn market(&self, symbol: &str) -> Option<&Market> {
+        self.listings.get(symbol).map(|listing| &listing.market)
+    }
+    fn listing_mut(&mut self, symbol: &str) -> Result<&mut Listing, Error> {
+        self.listings
+            .get_mut(symbol)
+            .ok_or_else(|| Error::UnknownSymbol(symbol.to_string()))
+    }
+    /// Place an order on the `symbol` market
+    pub fn submit(&mut self, symbol: &str, request: OrderRequest) -> Result<OrderResult, Error> {
+        let listing = self.listing_mut(symbol)?;
+        let result = listing.market.place_order(request)?;
+        listing.fills.extend(result.fills.iter().cloned());
+        Ok(result)
+    }
+    /// Cancel a resting order on the `symbol` market
+    pub fn cancel(&mut self, symbol: &str, order_id: OrderId) -> Result<LimitOrder, Error> {
+        let listing = self.listing_mut(symbol)?;
+        let order = listing
+            .market
+            .cancel_order(order_id)
+            .ok_or(Error::OrderNotFound(order_id))?;
+        let exposure = exposure(&listing.market, order.trader_id);
+        self.portfolio
+            .lock()
+            .expect("portfolio lock")
+            .exposures
+            .entry(symbol.to_string())
+            .or_default()
+            .insert(order.trader_id, exposure);
+        Ok(order)
+    }
+    /// Execute `command` on the `symbol` market, returning its events
+    pub fn execute(&mut self, symbol: &str, command: &Command) -> Result<Vec<Event>, Error> {
+        let listing = self.listing_mut(symbol)?;
+        let events = listing.market.execute(command);
+        listing.record_fills(&events);
+        // cancels, amends, busts and expiries change exposure without running the hooks
+        let exposures = exposures(&listing.market);
+        self.portfolio
+            .lock()
+            .expect("portfolio lock")
+            .exposures
+            .insert(symbol.to_string(), exposures);
+        Ok(events)
+    }
+    /// Halt trading on the `symbol` market
+    pub fn halt(&mut self, symbol: &str) -> Result<(), Error> {
+        self.listing_mut(symbol)?.market.halt();
+        Ok(())
+    }
+    /// Resume trading on the `symbol` market, returning the events of its released orders
+    pub fn resume(&mut self, symbol: &str) -> Result<Vec<Event>, Error> {
+        let listing = self.listing_mut(symbol)?;
+        let events = listing.market.resume();
+        listing.record_fills(&events);
+        Ok(events)
+    }
+    /// Fills produced on the `symbol` market, oldest first
+    pub fn fills(&self, symbol: &str) -> Option<&[Fill]> {
+        self.listings
+            .get(symbol)
+            .map(|listing| listing.fills.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Exchange, Instrument, MarketInfo, MarketStatus};
+    use crate::{Command, Error, Event, OrderFlags, OrderRequest, OrderSide};
+
+    #[test]
+    fn routes_by_symbol() {
+        let mut exchange = Exchange::default();
+        assert!(exchange.add_market("BTC"));
+        assert!(exchange.add_market("ETH"));
+        assert!(!exchange.add_market("ETH"));
+
+        exchange
+            .submit("BTC", OrderRequest::new(1, 10, 2.0, OrderSide::Sell))
+            .unwrap();
+        let result = exchange
+            .submit("ETH", OrderRequest::new(2, 10, 2.0, OrderSide::Buy))
+            .unwrap();
+        assert!(result.fills.is_empty());
+        exchange
+            .submit("BTC", OrderRequest::new(2, 4, 2.0, OrderSide::Buy))
+            .unwrap();
+
+        assert_eq!(exchange.fills("BTC").map(<[_]>::len), Some(2));
+        assert_eq!(exchange.fills("ETH").map(<[_]>::len), Some(0));
+        assert_eq!(exchange.cancel("ETH", 0).map(|o| o.amount), Ok(10));
+        assert_eq!(
+            exchange.submit("XRP", OrderRequest::new(1, 1, 1.0, OrderSide::Buy)),
+            Err(Error::UnknownSymbol("XRP".to_string()))
+        );
+    }
+
+    #[test]
+    fn market_discovery() {
+        let mut exchange = Exchange::default();
+        let btc = Instrument {
+            base: "BTC".to_string(),
+            quote: "USD".to_string(),
+            tick_size: 0.5,
+            lot_size: 0.001,
+        };
+        assert!(exchange.add_market_with("BTC-USD", btc.clone()));
+        exchange.add_market("ETH-USD");
+        exchange.halt("ETH-USD").unwrap();
+
+        let markets = exchange.list_markets();
+        assert_eq!(
+            markets.iter().map(|info| info.status).collect::<Vec<_>>(),
+            vec![MarketStatus::Open, MarketStatus::Halted]
+        );
+        assert_eq!(
+            exchange.market_info("BTC-USD"),
+            Some(MarketInfo {
+                symbol: "BTC-USD".to_string(),
+                instrument: btc,
+                status: MarketStatus::Open,
+            })
+        );
+        assert_eq!(markets[1].instrument, Instrument::default());
+        assert_eq!(exchange.market_info("XRP-USD"), None);
+    }
+
+    #[test]
+    fn portfolio_limit() {
+        let mut exchange = Exchange::default();
+        exchange.add_market("BTC");
+        exchange.add_market("ETH");
+        exchange.set_portfolio_limit(Some(100.0));
+
+        exchange
+            .submit("BTC", OrderRequest::new(1, 20, 2.0, OrderSide::Buy))
+            .unwrap();
+        exchange
+            .submit("ETH", OrderRequest::new(2, 40, 1.5, OrderSide::Sell))
+            .unwrap();
+        // 40 resting on BTC and 60 for the ETH trade
+        exchange
+            .submit("ETH", OrderRequest::new(1, 40, 1.5, OrderSide::Buy))
+            .unwrap();
+        assert_eq!(exchange.portfolio_exposure(1), 100.0);
+        assert_eq!(
+            exchange.submit("BTC", OrderRequest::new(1, 1, 1.0, OrderSide::Buy)),
+            Err(Error::Rejected("portfolio notional limit".to_string()))
+        );
+
+        // cancelling frees exposure for other markets
+        exchange.cancel("BTC", 0).unwrap();
+        exchange
+            .submit("ETH", OrderRequest::new(1, 20, 1.0, OrderSide::Buy))
+            .unwrap();
+        exchange.set_portfolio_limit(None);
+        exchange
+            .submit("BTC", OrderRequest::new(1, 100, 1.0, OrderSide::Buy))
+            .unwrap();
+    }
+
+    #[test]
+    fn portfolio_limit_set_after_trading() {
+        let mut exchange = Exchange::default();
+        exchange.add_market("BTC");
+        exchange.add_market("ETH");
+        exchange
+            .submit("BTC", OrderRequest::new(1, 30, 2.0, OrderSide::Buy))
+            .unwrap();
+        exchange
+            .submit("ETH", OrderRequest::new(2, 20, 1.0, OrderSide::Sell))
+            .unwrap();
+        exchange
+            .submit("ETH", OrderRequest::new(1, 20, 1.0, OrderSide::Buy))
+            .unwrap();
+
+        // exposure from before the limit counts towards it
+        exchange.set_portfolio_limit(Some(85.0));
+        assert_eq!(
+            exchange.submit("ETH", OrderRequest::new(1, 10, 1.0, OrderSide::Buy)),
+            Err(Error::Rejected("portfolio notional limit".to_string()))
+        );
+        exchange.cancel("BTC", 0).unwrap();
+        exchange
+            .submit("ETH", OrderRequest::new(1, 10, 1.0, OrderSide::Buy))
+            .unwrap();
+    }
+
+    #[test]
+    fn portfolio_exposure_follows_commands() {
+        let mut exchange = Exchange::default();
+        exchange.add_market("BTC");
+        exchange.add_market("ETH");
+        exchange.set_portfolio_limit(Some(100.0));
+        let day = OrderRequest::new(1, 40, 2.0, OrderSide::Buy).with_flags(OrderFlags::DAY);
+        exchange.execute("BTC", &Command::Submit(day)).unwrap();
+        exchange
+            .execute(
+                "BTC",
+                &Command::Submit(OrderRequest::new(1, 10, 2.0, OrderSide::Buy)),
+            )
+            .unwrap();
+        assert_eq!(
+            exchange.submit("ETH", OrderRequest::new(1, 1, 1.0, OrderSide::Buy)),
+            Err(Error::Rejected("portfolio notional limit".to_string()))
+        );
+
+        // expiry and cancels through `execute` free exposure for other markets
+        let events = exchange.execute("BTC", &Command::EndOfDay).unwrap();
+        assert!(matches!(events[0], Event::Expired(_)));
+        exchange
+            .submit("ETH", OrderRequest::new(1, 60, 1.0, OrderSide::Buy))
+            .unwrap();
+        exchange.execute("BTC", &Command::Cancel(1)).unwrap();
+        exchange
+            .submit("ETH", OrderRequest::new(1, 20, 1.0, OrderSide::Buy))
+            .unwrap();
+        assert_eq!(exchange.portfolio_exposure(1), 80.0);
+
+        // an amend counts towards the limit without the amended order
+        exchange
+            .execute(
+                "ETH",
+                &Command::Amend {
+                    order_id: 0,
+                    amount: 80,
+                    price: 1.0,
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            exchange.submit("BTC", OrderRequest::new(1, 1, 1.0, OrderSide::Buy)),
+            Err(Error::Rejected("portfolio notional limit".to_string()))
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Arrow/Parquet export of fills, order history and depth heatmaps
+//!
+//! Columns use plain types (`side` is a "buy"/"sell" string) so files load directly into
+//! pandas/polars.
+use std::{fs::File, path::Path, sync::Arc};
+
+use arrow_array::{
+    ArrayRef, Float32Array, Int64Array, RecordBatch, StringArray, UInt32Array, UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use parquet::{arrow::ArrowWriter, errors::ParquetError};
+
+use crate::{audit::History, heatmap::HeatmapRecorder, ledger::Trade, Fill, OrderSide};
+
+/// Schema of `fills_batch`
+pub fn fills_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("trade_id", DataType::UInt64, false),
+        Field::new("timestamp", DataType::UInt64, false),
+        Field::new("order_id", DataType::UInt64, false),
+        Field::new("side", DataType::Utf8, false),
+        Field::new("amount", DataType::UInt32, false),
+        Field::new("price", DataType::Float32, false),
+        Field::new("trader", DataType::UInt32, false),
+        Field::new("counter_party", DataType::UInt32, false),
+    ])
+}
+
+/// Schema of `orders_batch`
+pub fn orders_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("order_id", DataType::UInt64, false),
+        Field::new("trader_id", DataType::UInt32, false),
+        Field::new("side", DataType::Utf8, false),
+        Field::new("amount", DataType::UInt32, false),
+        Field::new("price", DataType::Float32, false),
+    ])
+}
+
+/// Convert the fills of `trades` (e.g. `Market::trades`) into a record batch, the resting then
+/// the incoming order's fill of each trade
+///
+/// Busted trades are left out, `trade_id` and `timestamp` join rows back to the ledger
+pub fn fills_batch(trades: &[Trade]) -> Result<RecordBatch, ArrowError> {
+    let fills: Vec<(&Trade, Fill)> = trades
+        .iter()
+        .filter(|trade| !trade.busted)
+        .flat_map(|trade| {
+            let buy = Fill::new(
+                trade.amount,
+                trade.price,
+                OrderSide::Buy,
+                trade.buyer,
+                trade.seller,
+                trade.buy_order_id,
+            );
+            let sell = Fill::new(
+                trade.amount,
+                trade.price,
+                OrderSide::Sell,
+                trade.seller,
+                trade.buyer,
+                trade.sell_order_id,
+            );
+            match trade.aggressor {
+                OrderSide::Buy => [(trade, sell), (trade, buy)],
+                OrderSide::Sell => [(trade, buy), (trade, sell)],
+            }
+        })
+        .collect();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from_iter_values(
+            fills.iter().map(|(t, _)| t.trade_id),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            fills.iter().map(|(t, _)| t.timestamp),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            fills.iter().map(|(_, f)| f.order_id),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            fills.iter().map(|(_, f)| f.side.as_str()),
+        )),
+        Arc::new(UInt32Array::from_iter_values(
+            fills.iter().map(|(_, f)| f.amount),
+        )),
+        Arc::new(Float32Array::from_iter_values(
+            fills.iter().map(|(_, f)| f.price),
+        )),
+        Arc::new(UInt32Array::from_iter_values(
+            fills.iter().map(|(_, f)| f.trader),
+        )),
+        Arc::new(UInt32Array::from_iter_values(
+            fills.iter().map(|(_, f)| f.counter_party),
+        )),
+    ];
+    RecordBatch::try_new(Arc::new(fills_schema()), columns)
+}
+
+/// Convert the orders recorded in `history` into a record batch
+pub fn orders_batch(history: &History) -> Result<RecordBatch, ArrowError> {
+    let orders: Vec<_> = history.orders().collect();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from_iter_values(
+            orders.iter().map(|(id, _)| *id),
+        )),
+        Arc::new(UInt32Array::from_iter_values(
+            orders.iter().map(|(_, o)| o.trader_id),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            orders.iter().map(|(_, o)| o.side.as_str()),
+        )),
+        Arc::new(UInt32Array::from_iter_values(
+            orders.iter().map(|(_, o)| o.amount),
+        )),
+        Arc::new(Float32Array::from_iter_values(
+            orders.iter().map(|(_, o)| o.price),
+        )),
+    ];
+    RecordBatch::try_new(Arc::new(orders_schema()), columns)
+}
+
+/// Schema of `heatmap_batch`
+pub fn heatmap_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("timestamp", DataType::UInt64, false),
+        Field::new("price", DataType::Float32, false),
+        Field::new("size", DataType::Int64, false),
+    ])
+}
+
+/// Convert the cells of a depth heatmap into a record batch
+pub fn heatmap_batch(heatmap: &HeatmapRecorder) -> Result<RecordBatch, ArrowError> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from(heatmap.timestamps().to_vec())),
+        Arc::new(Float32Array::from(heatmap.prices().to_vec())),
+        Arc::new(Int64Array::from(heatmap.sizes().to_vec())),
+    ];
+    RecordBatch::try_new(Arc::new(heatmap_schema()), columns)
+}
+
+/// Write `batch` to a parquet file at `path`
+pub fn write_parquet(path: impl AsRef<Path>, batch: &RecordBatch) -> Result<(), ParquetError> {
+    let file = File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+    writer.write(batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{
+        cast::AsArray,
+        types::{UInt32Type, UInt64Type},
+    };
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::{fills_batch, orders_batch, write_parquet};
+    use crate::{audit::History, clock::SimClock, Market, OrderRequest, OrderSide};
+
+    #[test]
+    fn export_history() {
+        let mut lob = Market::default();
+        lob.set_clock(SimClock::new(42));
+        let mut history = History::default();
+        for request in [
+            OrderRequest::new(1, 100, 1.0, OrderSide::Sell),
+            OrderRequest::new(2, 60, 1.0, OrderSide::Buy),
+        ] {
+            let result = lob.place_order(request.clone()).unwrap();
+            history.record(&request, &result);
+        }
+
+        let orders = orders_batch(&history).unwrap();
+        assert_eq!(orders.num_rows(), 2);
+        let fills = fills_batch(lob.trades()).unwrap();
+        assert_eq!(fills.num_rows(), 2);
+
+        let path =
+            std::env::temp_dir().join(format!("simple-lob-fills-{}.parquet", std::process::id()));
+        write_parquet(&path, &fills).unwrap();
+        let batches: Vec<_> =
+            ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap())
+                .unwrap()
+                .build()
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(batches, vec![fills.clone()]);
+        let column = |name: &str| batches[0].column_by_name(name).unwrap().clone();
+        let u64s = |name: &str| column(name).as_primitive::<UInt64Type>().values().to_vec();
+        assert_eq!(u64s("trade_id"), vec![0, 0]);
+        assert_eq!(u64s("timestamp"), vec![42, 42]);
+        assert_eq!(u64s("order_id"), vec![0, 1]);
+        let amounts = column("amount");
+        assert_eq!(
+            amounts.as_primitive::<UInt32Type>().values().as_ref(),
+            &[60, 60]
+        );
+    }
+}
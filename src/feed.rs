@@ -0,0 +1,248 @@
+//! Binary market data feed encoding
+//!
+//! ITCH-style messages of a type byte followed by fixed-size big-endian fields, written
+//! directly into a caller's buffer so they can go straight to a network send buffer:
+//! ```text
+//! 'A' accepted    order_id u64
+//! 'E' executed    order_id u64, side u8, amount u32, price f32, flags u8
+//! 'X' cancelled   order_id u64, amount u32, price f32, also expired orders
+//! 'U' amended     order_id u64, new_order_id u64
+//! 'B' trade bust  trade_id u64
+//! 'C' correction  trade_id u64, amount u32, price f32
+//! 'L' level delta action u8, side u8, price f32, amount u64, orders u32
+//! ```
+//! Sides are `B`/`S`, level actions `A`dded, `C`hanged or `D`eleted and the execution flags
+//! bits are added liquidity, auction, hidden and STP decrement from the lowest. Rejections
+//! and parked orders are private to the submitter and have no feed message.
+//! ```
+//! use simple_lob::{feed::FeedEncode, Event};
+//!
+//! let mut buf = [0_u8; 64];
+//! let len = Event::Accepted(7).encode_into(&mut buf).unwrap();
+//! assert_eq!(&buf[..len], &[b'A', 0, 0, 0, 0, 0, 0, 0, 7]);
+//! ```
+use std::fmt;
+
+use crate::{depth::LevelChange, Event, Liquidity, OrderSide};
+
+/// Errors encoding a feed message
+#[derive(PartialEq, Clone, Debug)]
+pub enum FeedError {
+    /// The buffer cannot hold the message, nothing was written
+    BufferTooSmall { needed: usize, available: usize },
+}
+
+impl fmt::Display for FeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BufferTooSmall { needed, available } => {
+                write!(f, "buffer too small: need {needed} bytes, have {available}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FeedError {}
+
+/// Types with a feed message
+pub trait FeedEncode {
+    /// Length of the encoded message, zero if it has no feed message
+    fn encoded_len(&self) -> usize;
+    /// Write the message at the start of `buf` returning the bytes written
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, FeedError>;
+    /// Append the message to `buf`
+    #[cfg(feature = "bytes")]
+    fn encode_bytes(&self, buf: &mut bytes::BytesMut) {
+        let start = buf.len();
+        buf.resize(start + self.encoded_len(), 0);
+        self.encode_into(&mut buf[start..])
+            .expect("buffer was sized to the message");
+    }
+}
+
+/// Cursor over a buffer already checked to fit the message
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(buf: &'a mut [u8], needed: usize) -> Result<Self, FeedError> {
+        if buf.len() < needed {
+            return Err(FeedError::BufferTooSmall {
+                needed,
+                available: buf.len(),
+            });
+        }
+        Ok(Writer { buf, pos: 0 })
+    }
+    fn put(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+        self
+    }
+    fn side(&mut self, side: &OrderSide) -> &mut Self {
+        self.put(match side {
+            OrderSide::Buy => b"B",
+            OrderSide::Sell => b"S",
+        })
+    }
+    fn price(&mut self, price: f32) -> &mut Self {
+        self.put(&price.to_be_bytes())
+    }
+}
+
+impl FeedEncode for Event {
+    fn encoded_len(&self) -> usize {
+        match self {
+            Event::Accepted(_) | Event::TradeBust(_) => 9,
+            Event::Fill(_) => 19,
+            Event::Cancelled(_)
+            | Event::Expired(_)
+            | Event::Amended { .. }
+            | Event::TradeCorrected(_) => 17,
+            Event::Rejected(_) | Event::Parked(_) | Event::Released(_) => 0,
+        }
+    }
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, FeedError> {
+        let mut writer = Writer::new(buf, self.encoded_len())?;
+        match self {
+            Event::Accepted(order_id) => writer.put(b"A").put(&order_id.to_be_bytes()),
+            Event::Fill(fill) => {
+                let flags = &fill.flags;
+                let bits = (flags.liquidity == Liquidity::Added) as u8
+                    | (flags.auction as u8) << 1
+                    | (flags.hidden as u8) << 2
+                    | (flags.stp_decrement as u8) << 3;
+                writer
+                    .put(b"E")
+                    .put(&fill.order_id.to_be_bytes())
+                    .side(&fill.side)
+                    .put(&fill.amount.to_be_bytes())
+                    .price(fill.price)
+                    .put(&[bits])
+            }
+            Event::Cancelled(order) | Event::Expired(order) => writer
+                .put(b"X")
+                .put(&order.nonce.to_be_bytes())
+                .put(&order.amount.to_be_bytes())
+                .price(order.price),
+            Event::Amended {
+                order_id,
+                new_order_id,
+            } => writer
+                .put(b"U")
+                .put(&order_id.to_be_bytes())
+                .put(&new_order_id.to_be_bytes()),
+            Event::TradeBust(trade) => writer.put(b"B").put(&trade.trade_id.to_be_bytes()),
+            Event::TradeCorrected(correction) => writer
+                .put(b"C")
+                .put(&correction.trade_id.to_be_bytes())
+                .put(&correction.amount.to_be_bytes())
+                .price(correction.price),
+            Event::Rejected(_) | Event::Parked(_) | Event::Released(_) => &mut writer,
+        };
+        Ok(writer.pos)
+    }
+}
+
+impl FeedEncode for LevelChange {
+    fn encoded_len(&self) -> usize {
+        19
+    }
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, FeedError> {
+        let (action, side, level) = match self {
+            LevelChange::Added { side, level } => (b"A", side, level),
+            LevelChange::Changed { side, to, .. } => (b"C", side, to),
+            LevelChange::Removed { side, level } => (b"D", side, level),
+        };
+        let mut writer = Writer::new(buf, self.encoded_len())?;
+        writer
+            .put(b"L")
+            .put(action)
+            .side(side)
+            .price(level.price)
+            .put(&level.amount.to_be_bytes())
+            .put(&(level.orders as u32).to_be_bytes());
+        Ok(writer.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FeedEncode, FeedError};
+    use crate::{
+        depth::{Level, LevelChange},
+        Command, Market, OrderRequest, OrderSide,
+    };
+
+    #[test]
+    fn encodes_events_in_place() {
+        let mut market = Market::default();
+        market.execute(&Command::Submit(OrderRequest::new(
+            1,
+            5,
+            2.0,
+            OrderSide::Sell,
+        )));
+        let events = market.execute(&Command::Submit(OrderRequest::new(
+            2,
+            5,
+            2.0,
+            OrderSide::Buy,
+        )));
+
+        let mut buf = [0_u8; 64];
+        let mut len = 0;
+        for event in &events {
+            len += event.encode_into(&mut buf[len..]).unwrap();
+        }
+        assert_eq!(len, 9 + 19 + 19);
+        // the resting seller's execution
+        let resting = &buf[9..28];
+        assert_eq!(resting[0], b'E');
+        assert_eq!(&resting[1..9], &0_u64.to_be_bytes());
+        assert_eq!(resting[9], b'S');
+        assert_eq!(&resting[10..14], &5_u32.to_be_bytes());
+        assert_eq!(&resting[14..18], &2.0_f32.to_be_bytes());
+        assert_eq!(resting[18], 1);
+
+        assert_eq!(
+            events[1].encode_into(&mut buf[..18]),
+            Err(FeedError::BufferTooSmall {
+                needed: 19,
+                available: 18
+            })
+        );
+    }
+
+    #[test]
+    fn encodes_level_deltas() {
+        let change = LevelChange::Changed {
+            side: OrderSide::Buy,
+            from: Level {
+                price: 1.5,
+                amount: 10,
+                orders: 1,
+            },
+            to: Level {
+                price: 1.5,
+                amount: 25,
+                orders: 2,
+            },
+        };
+        let mut buf = [0_u8; 19];
+        assert_eq!(change.encode_into(&mut buf), Ok(19));
+        assert_eq!(&buf[..3], b"LCB");
+        assert_eq!(&buf[7..15], &25_u64.to_be_bytes());
+        assert_eq!(&buf[15..], &2_u32.to_be_bytes());
+
+        #[cfg(feature = "bytes")]
+        {
+            let mut bytes = bytes::BytesMut::with_capacity(64);
+            change.encode_bytes(&mut bytes);
+            change.encode_bytes(&mut bytes);
+            assert_eq!(&bytes[19..], &buf[..]);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Fixed capacity market
+//!
+//! `FixedMarket` keeps its orders in inline arrays sized at compile time. Nothing is allocated
+//! after construction: fills are passed to a callback rather than collected, and orders which
+//! would need to rest on a full side are rejected before any matching takes place.
+//!
+//! ```
+//! use simple_lob::{FixedMarket, OrderRequest, OrderSide};
+//!
+//! let mut market = FixedMarket::<64>::new();
+//! market.place_order(OrderRequest::new(1, 10, 1.0, OrderSide::Sell), |_| {}).unwrap();
+//! let mut filled = 0;
+//! market
+//!     .place_order(OrderRequest::new(2, 4, 1.0, OrderSide::Buy), |fill| filled += fill.amount)
+//!     .unwrap();
+//! assert_eq!(filled, 8);
+//! ```
+use crate::{
+    BuyLimitOrder, Error, Fill, LimitOrder, Order, OrderId, OrderRequest, OrderSide, SellLimitOrder,
+};
+
+/// One side of the book holding at most `N` orders
+///
+/// Orders are sorted worst first so the best order is matched and removed from the end
+struct FixedBook<T: Order, const N: usize> {
+    orders: [T; N],
+    len: usize,
+}
+
+impl<T: Order + Default, const N: usize> FixedBook<T, N> {
+    fn new() -> Self {
+        FixedBook {
+            orders: std::array::from_fn(|_| T::default()),
+            len: 0,
+        }
+    }
+    fn is_full(&self) -> bool {
+        self.len == N
+    }
+    fn position(&self, order_id: OrderId) -> Option<usize> {
+        self.orders[..self.len]
+            .iter()
+            .position(|order| order.inner().nonce == order_id)
+    }
+    fn get(&self, order_id: OrderId) -> Option<&T> {
+        self.position(order_id).map(|idx| &self.orders[idx])
+    }
+    fn remove(&mut self, order_id: OrderId) -> Option<T> {
+        let idx = self.position(order_id)?;
+        self.orders[idx..self.len].rotate_left(1);
+        self.len -= 1;
+        Some(std::mem::take(&mut self.orders[self.len]))
+    }
+    /// Insert an order at its priority, fails if the book is full
+    fn insert_order(&mut self, order: &T) -> Result<(), Error> {
+        if self.is_full() {
+            return Err(Error::BookFull);
+        }
+        let idx = self.orders[..self.len].partition_point(|resting| resting > order);
+        self.orders[self.len] = order.clone();
+        self.orders[idx..=self.len].rotate_right(1);
+        self.len += 1;
+        Ok(())
+    }
+    /// Whether `order` would be completely filled by the resting orders
+    fn fills_completely(&self, order: &T::Opposite) -> bool {
+        let mut order = order.clone();
+        for resting in self.orders[..self.len].iter().rev() {
+            if resting.clone().try_fill(&mut order).is_none() {
+                break;
+            }
+            if order.is_zero() {
+                return true;
+            }
+        }
+        false
+    }
+    /// Match `order` against the book passing each fill to `on_fill`
+    fn submit_order(&mut self, order: &mut T::Opposite, on_fill: &mut impl FnMut(Fill)) {
+        while self.len > 0 && !order.is_zero() {
+            let best = &mut self.orders[self.len - 1];
+            if !best.crosses(order) {
+                break;
+            }
+            best.fill_into(order, on_fill);
+            if best.is_zero() {
+                self.len -= 1;
+            }
+        }
+    }
+}
+
+/// A market holding at most `N` resting orders on each side without allocating
+pub struct FixedMarket<const N: usize> {
+    /// Order nonce
+    nonce: u64,
+    buys: FixedBook<BuyLimitOrder, N>,
+    sells: FixedBook<SellLimitOrder, N>,
+}
+
+impl<const N: usize> Default for FixedMarket<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FixedMarket<N> {
+    pub fn new() -> Self {
+        FixedMarket {
+            nonce: 0,
+            buys: FixedBook::new(),
+            sells: FixedBook::new(),
+        }
+    }
+    /// Place an order passing any fills to `on_fill`, returns the assigned order id
+    /// Orders with zero amount, or which would rest on a full side, are rejected
+    pub fn place_order(
+        &mut self,
+        request: OrderRequest,
+        mut on_fill: impl FnMut(Fill),
+    ) -> Result<OrderId, Error> {
+        if request.amount == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        let order_id = self.nonce;
+        let order = LimitOrder {
+            price: request.price,
+            amount: request.amount,
+            trader_id: request.trader_id,
+            nonce: order_id,
+            flags: request.flags,
+        };
+
+        match request.side {
+            OrderSide::Buy => {
+                let mut order = order.into();
+                if self.buys.is_full() && !self.sells.fills_completely(&order) {
+                    return Err(Error::BookFull);
+                }
+                self.sells.submit_order(&mut order, &mut on_fill);
+                if !order.is_zero() {
+                    self.buys.insert_order(&order)?;
+                }
+            }
+            OrderSide::Sell => {
+                let mut order = order.into();
+                if self.sells.is_full() && !self.buys.fills_completely(&order) {
+                    return Err(Error::BookFull);
+                }
+                self.buys.submit_order(&mut order, &mut on_fill);
+                if !order.is_zero() {
+                    self.sells.insert_order(&order)?;
+                }
+            }
+        }
+
+        self.nonce += 1;
+        Ok(order_id)
+    }
+    /// Cancel a resting order, returning it if it was found
+    pub fn cancel_order(&mut self, order_id: OrderId) -> Option<LimitOrder> {
+        self.buys
+            .remove(order_id)
+            .map(|order| order.inner().clone())
+            .or_else(|| {
+                self.sells
+                    .remove(order_id)
+                    .map(|order| order.inner().clone())
+            })
+    }
+    /// Get a resting order by id
+    pub fn get_order(&self, order_id: OrderId) -> Option<&LimitOrder> {
+        self.buys
+            .get(order_id)
+            .map(Order::inner)
+            .or_else(|| self.sells.get(order_id).map(Order::inner))
+    }
+    /// Number of resting orders on `side`
+    pub fn len(&self, side: OrderSide) -> usize {
+        match side {
+            OrderSide::Buy => self.buys.len,
+            OrderSide::Sell => self.sells.len,
+        }
+    }
+    /// Maximum resting orders per side
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FixedMarket;
+    use crate::{Error, Market, OrderRequest, OrderSide};
+
+    #[test]
+    fn matches_like_market() {
+        let requests = [
+            OrderRequest::new(1, 10, 1.0, OrderSide::Buy),
+            OrderRequest::new(2, 5, 1.5, OrderSide::Buy),
+            OrderRequest::new(3, 5, 1.0, OrderSide::Buy),
+            OrderRequest::new(4, 12, 1.0, OrderSide::Sell),
+            OrderRequest::new(5, 20, 0.5, OrderSide::Sell),
+            OrderRequest::new(6, 4, 2.0, OrderSide::Sell),
+            OrderRequest::new(7, 30, 2.0, OrderSide::Buy),
+        ];
+        let mut fixed = FixedMarket::<8>::new();
+        let mut market = Market::default();
+        for request in requests {
+            let mut fills = vec![];
+            let order_id = fixed
+                .place_order(request.clone(), |fill| fills.push(fill))
+                .unwrap();
+            let expected = market.place_order(request).unwrap();
+            assert_eq!(order_id, expected.order_id);
+            assert_eq!(fills, expected.fills);
+        }
+        for order_id in 0..7 {
+            assert_eq!(fixed.get_order(order_id), market.get_order(order_id));
+        }
+    }
+
+    #[test]
+    fn rejects_when_full() {
+        let mut market = FixedMarket::<2>::new();
+        for price in [1.0, 2.0] {
+            market
+                .place_order(OrderRequest::new(1, 10, price, OrderSide::Sell), |_| {})
+                .unwrap();
+        }
+        let full = OrderRequest::new(1, 10, 3.0, OrderSide::Sell);
+        assert_eq!(
+            market.place_order(full.clone(), |_| {}),
+            Err(Error::BookFull)
+        );
+
+        // an order filled completely never rests
+        market
+            .place_order(OrderRequest::new(2, 10, 1.0, OrderSide::Buy), |_| {})
+            .unwrap();
+        assert_eq!(market.len(OrderSide::Sell), 1);
+        assert_eq!(market.place_order(full, |_| {}), Ok(3));
+        assert_eq!(market.cancel_order(3).map(|order| order.amount), Some(10));
+        assert_eq!(market.len(OrderSide::Sell), 1);
+    }
+}
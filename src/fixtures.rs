@@ -0,0 +1,224 @@
+//! Declarative book fixtures for tests
+//!
+//! Build a market's resting orders directly instead of submitting setup orders, either with
+//! `BookFixture` or its text form, one price level per line as `<trader>x<amount>` orders in
+//! time priority:
+//! ```
+//! use simple_lob::fixtures::BookFixture;
+//!
+//! let market = "
+//!     sell 1.5: 1x100 2x50
+//!     buy 1.0: 3x20
+//! "
+//! .parse::<BookFixture>()
+//! .unwrap()
+//! .build();
+//! assert_eq!(market.get_order(1).map(|order| order.amount), Some(50));
+//! ```
+//! Orders are assigned ids in the order they are listed.
+use std::{fmt, str::FromStr};
+
+use crate::{book::BookBackend, LimitOrder, Market, MarketConfig, Order, OrderSide};
+
+/// A price level and its `(trader, amount)` orders
+type FixtureLevel = (OrderSide, f32, Vec<(u32, u32)>);
+
+/// Resting orders to build a market from
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct BookFixture {
+    levels: Vec<FixtureLevel>,
+}
+
+impl BookFixture {
+    /// Add a bid level with `(trader, amount)` orders in time priority
+    pub fn buy(mut self, price: f32, orders: &[(u32, u32)]) -> Self {
+        self.levels.push((OrderSide::Buy, price, orders.to_vec()));
+        self
+    }
+    /// Add an ask level with `(trader, amount)` orders in time priority
+    pub fn sell(mut self, price: f32, orders: &[(u32, u32)]) -> Self {
+        self.levels.push((OrderSide::Sell, price, orders.to_vec()));
+        self
+    }
+    /// The resting orders of `market`, asks then bids from the highest price
+    pub fn of(market: &Market) -> Self {
+        let mut asks = levels(OrderSide::Sell, market.sells.iter().map(Order::inner));
+        asks.reverse();
+        asks.extend(levels(OrderSide::Buy, market.buys.iter().map(Order::inner)));
+        BookFixture { levels: asks }
+    }
+    /// Levels in the order of `of`, fixtures of the same book are equal once sorted
+    pub fn sorted(mut self) -> Self {
+        self.levels.sort_by(|(a_side, a, _), (b_side, b, _)| {
+            (*a_side == OrderSide::Buy)
+                .cmp(&(*b_side == OrderSide::Buy))
+                .then(b.total_cmp(a))
+        });
+        self
+    }
+    /// Whether the best bid is at or above the best ask
+    pub fn is_crossed(&self) -> bool {
+        let prices = |side: OrderSide| {
+            self.levels
+                .iter()
+                .filter(move |(level_side, _, orders)| *level_side == side && !orders.is_empty())
+                .map(|(_, price, _)| *price)
+        };
+        match (
+            prices(OrderSide::Buy).reduce(f32::max),
+            prices(OrderSide::Sell).reduce(f32::min),
+        ) {
+            (Some(bid), Some(ask)) => bid >= ask,
+            _ => false,
+        }
+    }
+    /// Build a market with the default config
+    pub fn build(self) -> Market {
+        self.build_with(MarketConfig::default())
+    }
+    /// Build a market holding the fixture's orders
+    /// Panics if the book would be crossed
+    pub fn build_with(self, config: MarketConfig) -> Market {
+        assert!(!self.is_crossed(), "fixture book is crossed");
+        let mut market = Market::new(config);
+        for (side, price, orders) in self.levels {
+            for (trader_id, amount) in orders {
+                let order = LimitOrder {
+                    price,
+                    nonce: market.nonce,
+                    amount,
+                    trader_id,
+                    ..Default::default()
+                };
+                match side {
+                    OrderSide::Buy => market.buys.insert_order(&order.into()),
+                    OrderSide::Sell => market.sells.insert_order(&order.into()),
+                }
+                .expect("order ids are unique");
+                market.nonce += 1;
+            }
+        }
+        market
+    }
+}
+
+/// Group `orders` in priority order into levels
+fn levels<'a>(side: OrderSide, orders: impl Iterator<Item = &'a LimitOrder>) -> Vec<FixtureLevel> {
+    let mut levels: Vec<FixtureLevel> = vec![];
+    for order in orders {
+        match levels.last_mut() {
+            Some((_, price, level)) if *price == order.price => {
+                level.push((order.trader_id, order.amount))
+            }
+            _ => levels.push((
+                side.clone(),
+                order.price,
+                vec![(order.trader_id, order.amount)],
+            )),
+        }
+    }
+    levels
+}
+
+/// A fixture line could not be parsed
+#[derive(PartialEq, Clone, Debug)]
+pub struct FixtureParseError(pub String);
+
+impl fmt::Display for FixtureParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid fixture line: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for FixtureParseError {}
+
+impl fmt::Display for BookFixture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (side, price, orders) in &self.levels {
+            write!(f, "{} {price}:", side.as_str())?;
+            for (trader_id, amount) in orders {
+                write!(f, " {trader_id}x{amount}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BookFixture {
+    type Err = FixtureParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fixture = BookFixture::default();
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let invalid = || FixtureParseError(line.to_string());
+            let (level, orders) = line.split_once(':').ok_or_else(invalid)?;
+            let (side, price) = match level.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                [side, price] => (
+                    OrderSide::try_from(*side).map_err(|_| invalid())?,
+                    price.parse().map_err(|_| invalid())?,
+                ),
+                _ => return Err(invalid()),
+            };
+            let orders = orders
+                .split_whitespace()
+                .map(|order| {
+                    let (trader, amount) = order.split_once('x')?;
+                    Some((trader.parse().ok()?, amount.parse().ok()?))
+                })
+                .collect::<Option<Vec<(u32, u32)>>>()
+                .ok_or_else(invalid)?;
+            fixture.levels.push((side, price, orders));
+        }
+        Ok(fixture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BookFixture;
+    use crate::{depth::Level, OrderSide, LOB};
+
+    #[test]
+    fn builds_book() {
+        let fixture: BookFixture = "
+            sell 1.5: 1x100 2x50
+            sell 1.25: 3x10
+            buy 1.0: 4x20
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(
+            fixture,
+            BookFixture::default()
+                .sell(1.5, &[(1, 100), (2, 50)])
+                .sell(1.25, &[(3, 10)])
+                .buy(1.0, &[(4, 20)])
+        );
+
+        let mut market = fixture.build();
+        assert_eq!(
+            market.depth(1).asks,
+            vec![Level {
+                price: 1.25,
+                amount: 10,
+                orders: 1
+            }]
+        );
+        let fills = market.submit_order(5, 60, 1.5, OrderSide::Buy).unwrap();
+        assert_eq!(fills[2].order_id, 0);
+        assert_eq!(
+            BookFixture::of(&market).to_string(),
+            "sell 1.5: 1x50 2x50\nbuy 1: 4x20\n"
+        );
+        assert!("buy 1.0: 4".parse::<BookFixture>().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "crossed")]
+    fn rejects_crossed_book() {
+        BookFixture::default()
+            .sell(1.0, &[(1, 10)])
+            .buy(1.5, &[(2, 10)])
+            .build();
+    }
+}
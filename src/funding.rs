@@ -0,0 +1,114 @@
+//! Periodic funding settlement for perpetual-style markets
+//!
+//! With `MarketConfig::funding` set, every elapsed funding interval of the market clock charges
+//! each trader `position * reference price * rate`, so longs pay shorts when the rate is
+//! positive. Positions net to zero so payments do too. Intervals are aligned to multiples of the
+//! interval since time zero.
+use std::time::Duration;
+
+use crate::Market;
+
+/// Funding schedule
+#[derive(PartialEq, Clone, Debug)]
+pub struct Funding {
+    pub interval: Duration,
+    /// Fraction of position value paid each interval
+    pub rate: f64,
+}
+
+/// Funding paid by a trader at the end of an interval, negative when received
+#[derive(PartialEq, Clone, Debug)]
+pub struct FundingPayment {
+    /// Market time of the interval end in nanoseconds
+    pub timestamp: u64,
+    pub trader_id: u32,
+    pub amount: f64,
+}
+
+/// Settlement progress of a market
+#[derive(Default, Debug)]
+pub(crate) struct FundingState {
+    /// End of the last settled interval
+    settled: Option<u64>,
+    payments: Vec<FundingPayment>,
+}
+
+impl Market {
+    /// Settle funding of every interval elapsed since the last settlement, returning the
+    /// number of payments made
+    ///
+    /// Called before each order, call directly to settle while the market is idle
+    pub fn settle_funding(&mut self) -> usize {
+        let Some(Funding { interval, rate }) = self.config.funding.clone() else {
+            return 0;
+        };
+        let interval = (interval.as_nanos() as u64).max(1);
+        let boundary = self.now() / interval * interval;
+        let settled = *self.funding.settled.get_or_insert(boundary);
+        let (Some(price), true) = (self.reference_price(), boundary > settled) else {
+            return 0;
+        };
+        self.funding.settled = Some(boundary);
+        let before = self.funding.payments.len();
+        for timestamp in (settled + interval..=boundary).step_by(interval as usize) {
+            for (trader_id, position) in self.ledger.open_positions() {
+                let amount = position as f64 * price as f64 * rate;
+                self.funding.payments.push(FundingPayment {
+                    timestamp,
+                    trader_id,
+                    amount,
+                });
+            }
+        }
+        for payment in &self.funding.payments[before..] {
+            self.ledger.transfer(payment.trader_id, -payment.amount);
+        }
+        self.funding.payments.len() - before
+    }
+    /// All funding payments, oldest first
+    pub fn funding_payments(&self) -> &[FundingPayment] {
+        &self.funding.payments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Funding;
+    use crate::{clock::SimClock, Market, MarketConfig, OrderSide, LOB};
+
+    #[test]
+    fn longs_pay_shorts() {
+        let hour = Duration::from_secs(3600);
+        let clock = SimClock::new(0);
+        let mut lob = Market::new(MarketConfig {
+            funding: Some(Funding {
+                interval: hour,
+                rate: 0.01,
+            }),
+            ..Default::default()
+        });
+        lob.set_clock(clock.clone());
+        lob.submit_order(1, 100, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 100, 2.0, OrderSide::Buy).unwrap();
+        clock.advance(hour / 2);
+        assert_eq!(lob.settle_funding(), 0);
+
+        clock.advance(hour * 2);
+        assert_eq!(lob.settle_funding(), 4);
+        assert_eq!(lob.position(2).balance, -200.0 - 4.0);
+        assert_eq!(lob.position(1).balance, 200.0 + 4.0);
+        let last = lob.funding_payments().last().unwrap();
+        assert_eq!(
+            (last.timestamp, last.trader_id),
+            (2 * 3600 * 1_000_000_000, 2)
+        );
+        assert_eq!(last.amount, 2.0);
+
+        // settled before the next order
+        clock.advance(hour);
+        lob.submit_order(3, 1, 1.0, OrderSide::Buy).unwrap();
+        assert_eq!(lob.funding_payments().len(), 6);
+    }
+}
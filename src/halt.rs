@@ -0,0 +1,131 @@
+//! Trading halts
+//!
+//! A halted market stops matching. Cancels, busts and corrections still apply while new orders
+//! and amends are rejected with `Error::Halted`, unless the config's `HaltPolicy` is `Park`.
+//! Then orders submitted through `Market::execute`, including those still queued for ingress
+//! when the halt began, are parked and published as `Event::Parked`. Resuming releases them
+//! into matching in arrival order, each published as `Event::Released` followed by its events:
+//! ```text
+//! > halt
+//! > submit 1 buy 10 1.5
+//! < park 1 buy 10 1.5
+//! > resume
+//! < release 1 buy 10 1.5
+//! < accept 0
+//! ```
+//! Parked orders are not part of snapshots or the state hash.
+use std::collections::VecDeque;
+
+use crate::{Event, Market, OrderRequest};
+
+/// Handling of orders submitted while the market is halted
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum HaltPolicy {
+    /// Reject them with `Error::Halted`
+    #[default]
+    Reject,
+    /// Park them until trading resumes
+    Park,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct HaltState {
+    pub halted: bool,
+    /// Orders awaiting release in arrival order
+    pub parked: VecDeque<OrderRequest>,
+}
+
+impl Market {
+    pub fn is_halted(&self) -> bool {
+        self.halt.halted
+    }
+    /// Stop matching until `resume`
+    pub fn halt(&mut self) {
+        self.halt.halted = true;
+    }
+    /// Resume matching, releasing parked orders in arrival order and returning their events
+    pub fn resume(&mut self) -> Vec<Event> {
+        self.halt.halted = false;
+        let mut events = vec![];
+        while let Some(request) = self.halt.parked.pop_front() {
+            events.push(Event::Released(request.clone()));
+            events.extend(self.submit_events(request));
+        }
+        events
+    }
+    /// Orders parked during the halt in arrival order
+    pub fn parked(&self) -> impl Iterator<Item = &OrderRequest> {
+        self.halt.parked.iter()
+    }
+    /// Park `request` if the market is halted and parking orders, otherwise return it
+    pub(crate) fn try_park(&mut self, request: OrderRequest) -> Result<Event, OrderRequest> {
+        if !self.halt.halted || self.config.halt != HaltPolicy::Park {
+            return Err(request);
+        }
+        self.halt.parked.push_back(request.clone());
+        Ok(Event::Parked(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HaltPolicy;
+    use crate::{Command, Error, Event, Market, MarketConfig, OrderRequest, OrderSide};
+
+    #[test]
+    fn halted_orders_are_rejected() {
+        let mut lob = Market::default();
+        let sell = Command::Submit(OrderRequest::new(1, 10, 1.5, OrderSide::Sell));
+        lob.execute(&sell);
+        lob.execute(&Command::Halt);
+        assert_eq!(lob.execute(&sell), vec![Event::Rejected(Error::Halted)]);
+        assert_eq!(
+            lob.execute(&Command::Amend {
+                order_id: 0,
+                amount: 5,
+                price: 1.5
+            }),
+            vec![Event::Rejected(Error::Halted)]
+        );
+        assert!(matches!(
+            lob.execute(&Command::Cancel(0))[..],
+            [Event::Cancelled(_)]
+        ));
+        assert!(lob.execute(&Command::Resume).is_empty());
+    }
+
+    #[test]
+    fn parked_orders_release_in_arrival_order() {
+        let mut lob = Market::new(MarketConfig {
+            halt: HaltPolicy::Park,
+            ..Default::default()
+        });
+        lob.execute(&Command::Halt);
+        let sell = OrderRequest::new(1, 10, 1.5, OrderSide::Sell);
+        let buy = OrderRequest::new(2, 4, 1.5, OrderSide::Buy);
+        for request in [&sell, &buy] {
+            let events = lob.execute(&Command::Submit(request.clone()));
+            assert_eq!(events, vec![Event::Parked(request.clone())]);
+        }
+        assert_eq!(lob.parked().count(), 2);
+        assert!(lob.depth(1).asks.is_empty());
+
+        let events: Vec<String> = lob
+            .execute(&Command::Resume)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "release 1 sell 10 1.5",
+                "accept 0",
+                "release 2 buy 4 1.5",
+                "accept 1",
+                "fill 0 1 2 sell 4 1.5",
+                "fill 1 2 1 buy 4 1.5",
+            ]
+        );
+        assert_eq!(lob.parked().count(), 0);
+    }
+}
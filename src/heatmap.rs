@@ -0,0 +1,111 @@
+//! Time series of book depth for heatmaps
+//!
+//! `HeatmapRecorder` samples the aggregated depth of a market at a fixed interval into three
+//! parallel columns, one row per (time, price) cell. Sizes are signed: bids positive and asks
+//! negative, so both sides fit a single diverging color scale.
+use std::time::Duration;
+
+use crate::{depth::Level, Market};
+
+/// Columnar buffer of sampled depth
+#[derive(PartialEq, Clone, Debug)]
+pub struct HeatmapRecorder {
+    /// Nanoseconds between samples
+    interval: u64,
+    /// Price levels sampled per side
+    max_levels: usize,
+    /// Market time of the last sample
+    last_sample: Option<u64>,
+    timestamps: Vec<u64>,
+    prices: Vec<f32>,
+    sizes: Vec<i64>,
+}
+
+impl HeatmapRecorder {
+    /// Sample up to `max_levels` levels per side every `interval`
+    pub fn new(interval: Duration, max_levels: usize) -> Self {
+        HeatmapRecorder {
+            interval: interval.as_nanos() as u64,
+            max_levels,
+            last_sample: None,
+            timestamps: Vec::default(),
+            prices: Vec::default(),
+            sizes: Vec::default(),
+        }
+    }
+    /// Record the market's depth if an interval has passed since the last sample
+    /// Returns whether a sample was taken
+    pub fn sample(&mut self, market: &Market) -> bool {
+        let now = market.now();
+        if self
+            .last_sample
+            .is_some_and(|last| now < last.saturating_add(self.interval))
+        {
+            return false;
+        }
+        self.last_sample = Some(now);
+        let depth = market.depth(self.max_levels);
+        let cells = depth
+            .bids
+            .iter()
+            .map(|level| (level, 1))
+            .chain(depth.asks.iter().map(|level| (level, -1)));
+        for (Level { price, amount, .. }, sign) in cells {
+            self.timestamps.push(now);
+            self.prices.push(*price);
+            self.sizes.push(*amount as i64 * sign);
+        }
+        true
+    }
+    /// Sample time of each cell in nanoseconds
+    pub fn timestamps(&self) -> &[u64] {
+        &self.timestamps
+    }
+    pub fn prices(&self) -> &[f32] {
+        &self.prices
+    }
+    /// Resting amount of each cell, negative for asks
+    pub fn sizes(&self) -> &[i64] {
+        &self.sizes
+    }
+    /// Number of recorded cells
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::HeatmapRecorder;
+    use crate::{clock::SimClock, Market, OrderSide, LOB};
+
+    #[test]
+    fn samples_at_interval() {
+        let clock = SimClock::new(0);
+        let mut lob = Market::default();
+        lob.set_clock(clock.clone());
+        let mut heatmap = HeatmapRecorder::new(Duration::from_secs(1), 2);
+
+        lob.submit_order(1, 100, 1.0, OrderSide::Buy).unwrap();
+        lob.submit_order(2, 50, 1.5, OrderSide::Sell).unwrap();
+        assert!(heatmap.sample(&lob));
+
+        lob.submit_order(3, 20, 2.0, OrderSide::Sell).unwrap();
+        clock.advance(Duration::from_millis(500));
+        assert!(!heatmap.sample(&lob));
+        clock.advance(Duration::from_millis(500));
+        assert!(heatmap.sample(&lob));
+
+        assert_eq!(
+            heatmap.timestamps(),
+            &[0, 0, 1_000_000_000, 1_000_000_000, 1_000_000_000]
+        );
+        assert_eq!(heatmap.prices(), &[1.0, 1.5, 1.0, 1.5, 2.0]);
+        assert_eq!(heatmap.sizes(), &[100, -50, 100, -50, -20]);
+    }
+}
@@ -0,0 +1,182 @@
+//! Pre-trade and post-trade hooks
+//!
+//! Pre-trade hooks run in order before an order matches and may adjust or reject it, post-trade
+//! hooks run after an order is accepted. The market's built-in price and order cap checks are
+//! installed as the first pre-trade hooks. Self-trade prevention is not a hook: it withdraws
+//! same group resting orders for the duration of matching and restores or cancels them once the
+//! remainder is known, which a hook seeing only `&Market` before matching cannot do:
+//! ```
+//! use simple_lob::{hooks::PreTradeHook, Error, Market, OrderRequest, OrderSide};
+//!
+//! /// Reject orders larger than a fixed size
+//! struct MaxSize(u32);
+//!
+//! impl PreTradeHook for MaxSize {
+//!     fn check(&mut self, _market: &Market, request: &mut OrderRequest) -> Result<(), Error> {
+//!         if request.amount > self.0 {
+//!             return Err(Error::Rejected("order too large".to_string()));
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut market = Market::default();
+//! market.add_pre_trade_hook(MaxSize(100));
+//! assert!(market.place_order(OrderRequest::new(1, 500, 1.0, OrderSide::Buy)).is_err());
+//! ```
+use crate::{Error, Market, OrderRequest, OrderResult, OrderSide};
+
+/// Inspects orders before they match
+pub trait PreTradeHook: Send {
+    /// Adjust `request` or reject it with an error
+    fn check(&mut self, market: &Market, request: &mut OrderRequest) -> Result<(), Error>;
+}
+
+/// Observes accepted orders after matching
+pub trait PostTradeHook: Send {
+    fn after_trade(&mut self, market: &Market, request: &OrderRequest, result: &OrderResult);
+}
+
+/// Rejects prices which are not finite, or negative without
+/// `MarketConfig::allow_negative_prices`, normalizing negative zero so zero is a single price level
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PriceCheck;
+
+impl PreTradeHook for PriceCheck {
+    fn check(&mut self, market: &Market, request: &mut OrderRequest) -> Result<(), Error> {
+        let price = request.price;
+        if !price.is_finite() || (price < 0.0 && !market.config.allow_negative_prices) {
+            return Err(Error::InvalidPrice(price));
+        }
+        request.price = price + 0.0;
+        Ok(())
+    }
+}
+
+/// Rejects orders of traders which already have the maximum resting orders allowed
+/// Counted before matching, so also applies to orders which would not rest
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OrderCaps;
+
+impl PreTradeHook for OrderCaps {
+    fn check(&mut self, market: &Market, request: &mut OrderRequest) -> Result<(), Error> {
+        let trader_id = request.trader_id;
+        if let Some(max) = market.config.max_orders_per_level {
+            let at_level = |(_, price): &(_, f32)| *price == request.price;
+            let count = match request.side {
+                OrderSide::Buy => market.buys.order_ids_of(trader_id).filter(at_level).count(),
+                OrderSide::Sell => market
+                    .sells
+                    .order_ids_of(trader_id)
+                    .filter(at_level)
+                    .count(),
+            };
+            if count >= max {
+                return Err(Error::OrderLimit);
+            }
+        }
+        if let Some(max) = market.config.max_orders_per_trader {
+            if market.buys.count_of(trader_id) + market.sells.count_of(trader_id) >= max {
+                return Err(Error::OrderLimit);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Hooks installed on a market in invocation order
+pub(crate) struct Hooks {
+    pub pre_trade: Vec<Box<dyn PreTradeHook>>,
+    pub post_trade: Vec<Box<dyn PostTradeHook>>,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Hooks {
+            pre_trade: vec![Box::new(PriceCheck), Box::new(OrderCaps)],
+            post_trade: vec![],
+        }
+    }
+}
+
+impl Market {
+    /// Run `hook` before matching each order, after the hooks already installed
+    pub fn add_pre_trade_hook(&mut self, hook: impl PreTradeHook + 'static) {
+        self.hooks.pre_trade.push(Box::new(hook));
+    }
+    /// Run `hook` after each accepted order, after the hooks already installed
+    pub fn add_post_trade_hook(&mut self, hook: impl PostTradeHook + 'static) {
+        self.hooks.post_trade.push(Box::new(hook));
+    }
+    /// Run the pre-trade hooks on `request`, stopping at the first rejection
+    pub(crate) fn pre_trade(&mut self, request: &mut OrderRequest) -> Result<(), Error> {
+        // hooks are taken out of the market while they inspect it
+        let mut hooks = std::mem::take(&mut self.hooks.pre_trade);
+        let checked = hooks
+            .iter_mut()
+            .try_for_each(|hook| hook.check(self, request));
+        self.hooks.pre_trade = hooks;
+        checked
+    }
+    pub(crate) fn post_trade(&mut self, request: &OrderRequest, result: &OrderResult) {
+        let mut hooks = std::mem::take(&mut self.hooks.post_trade);
+        for hook in hooks.iter_mut() {
+            hook.after_trade(self, request, result);
+        }
+        self.hooks.post_trade = hooks;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::{PostTradeHook, PreTradeHook};
+    use crate::{Error, Market, OrderRequest, OrderResult, OrderSide, LOB};
+
+    /// Rounds prices down to a tick and rejects a blocked trader
+    struct Enrich;
+
+    impl PreTradeHook for Enrich {
+        fn check(&mut self, _market: &Market, request: &mut OrderRequest) -> Result<(), Error> {
+            if request.trader_id == 9 {
+                return Err(Error::Rejected("blocked".to_string()));
+            }
+            request.price = (request.price * 4.0).floor() / 4.0;
+            Ok(())
+        }
+    }
+
+    struct Traded(mpsc::Sender<(u32, usize)>);
+
+    impl PostTradeHook for Traded {
+        fn after_trade(&mut self, market: &Market, request: &OrderRequest, result: &OrderResult) {
+            assert!(market.trades().len() * 2 >= result.fills.len());
+            self.0
+                .send((request.trader_id, result.fills.len()))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn hooks_run_in_order() {
+        let mut lob = Market::default();
+        let (tx, rx) = mpsc::channel();
+        lob.add_pre_trade_hook(Enrich);
+        lob.add_post_trade_hook(Traded(tx));
+
+        lob.submit_order(1, 10, 1.3, OrderSide::Sell).unwrap();
+        assert_eq!(lob.get_order(0).map(|order| order.price), Some(1.25));
+        lob.submit_order(2, 10, 1.25, OrderSide::Buy).unwrap();
+        assert_eq!(
+            lob.submit_order(9, 10, 1.0, OrderSide::Buy),
+            Err(Error::Rejected("blocked".to_string()))
+        );
+        // built-in checks run first
+        assert!(matches!(
+            lob.submit_order(9, 10, f32::NAN, OrderSide::Buy),
+            Err(Error::InvalidPrice(_))
+        ));
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![(1, 0), (2, 2)]);
+    }
+}
@@ -0,0 +1,191 @@
+//! Order entry throttling
+//!
+//! `IngressQueue` buffers commands ahead of the matcher in one bounded queue per `Priority`
+//! class. Commands are taken highest class first, FIFO within a class, so cancels still reach
+//! the book during a burst of new orders. When a class queue is full its `ShedPolicy` decides
+//! which command is dropped.
+//!
+//! A cancel may overtake the submit of the order it targets, in which case it is rejected as
+//! not found, the same as a cancel racing a fill.
+use std::collections::VecDeque;
+
+use crate::Command;
+
+/// Priority class of a command, highest first
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Priority {
+    /// Cancels, halts and resumes
+    Cancel,
+    /// Amends, busts and corrections
+    Modify,
+    New,
+}
+
+impl Priority {
+    const ALL: [Priority; 3] = [Priority::Cancel, Priority::Modify, Priority::New];
+
+    pub fn of(command: &Command) -> Self {
+        match command {
+            Command::Cancel(_) | Command::Halt | Command::Resume | Command::EndOfDay => {
+                Priority::Cancel
+            }
+            Command::Amend { .. } | Command::Bust { .. } | Command::Correct { .. } => {
+                Priority::Modify
+            }
+            Command::Submit(_) => Priority::New,
+        }
+    }
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Which command to drop when a class queue is full
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum ShedPolicy {
+    /// Reject the incoming command
+    #[default]
+    RejectNewest,
+    /// Drop the oldest queued command of the class, keeping the freshest
+    DropOldest,
+}
+
+/// Queue depths by priority class
+#[derive(PartialEq, Clone, Debug)]
+pub struct IngressConfig {
+    pub cancel_depth: usize,
+    pub modify_depth: usize,
+    pub new_depth: usize,
+    pub shed: ShedPolicy,
+}
+
+impl Default for IngressConfig {
+    fn default() -> Self {
+        IngressConfig {
+            cancel_depth: 4096,
+            modify_depth: 1024,
+            new_depth: 1024,
+            shed: ShedPolicy::default(),
+        }
+    }
+}
+
+impl IngressConfig {
+    fn depth(&self, priority: Priority) -> usize {
+        match priority {
+            Priority::Cancel => self.cancel_depth,
+            Priority::Modify => self.modify_depth,
+            Priority::New => self.new_depth,
+        }
+    }
+}
+
+/// Bounded priority queues of commands awaiting execution
+#[derive(Debug, Default)]
+pub struct IngressQueue {
+    config: IngressConfig,
+    queues: [VecDeque<Command>; 3],
+    /// Commands shed by class
+    shed: [u64; 3],
+}
+
+impl IngressQueue {
+    pub fn new(config: IngressConfig) -> Self {
+        IngressQueue {
+            config,
+            ..Default::default()
+        }
+    }
+    /// Queue `command`, returning the command shed if its class queue was full
+    pub fn push(&mut self, command: Command) -> Option<Command> {
+        let priority = Priority::of(&command);
+        let queue = &mut self.queues[priority.index()];
+        if queue.len() < self.config.depth(priority) {
+            queue.push_back(command);
+            return None;
+        }
+        self.shed[priority.index()] += 1;
+        if self.config.shed == ShedPolicy::DropOldest {
+            if let Some(oldest) = queue.pop_front() {
+                queue.push_back(command);
+                return Some(oldest);
+            }
+        }
+        Some(command)
+    }
+    /// Take the next command, highest priority first
+    pub fn pop(&mut self) -> Option<Command> {
+        self.queues.iter_mut().find_map(VecDeque::pop_front)
+    }
+    /// Number of queued commands of `priority`
+    pub fn queued(&self, priority: Priority) -> usize {
+        self.queues[priority.index()].len()
+    }
+    /// Number of commands of `priority` shed so far
+    pub fn shed(&self, priority: Priority) -> u64 {
+        self.shed[priority.index()]
+    }
+    pub fn len(&self) -> usize {
+        Priority::ALL.iter().map(|p| self.queued(*p)).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IngressConfig, IngressQueue, Priority, ShedPolicy};
+    use crate::{Command, Event, OrderRequest, OrderSide, SharedMarket};
+
+    fn submit(amount: u32) -> Command {
+        Command::Submit(OrderRequest::new(1, amount, 1.0, OrderSide::Buy))
+    }
+
+    #[test]
+    fn cancels_first_and_sheds_when_full() {
+        let config = IngressConfig {
+            new_depth: 2,
+            ..Default::default()
+        };
+        let mut queue = IngressQueue::new(config.clone());
+        assert_eq!(queue.push(submit(1)), None);
+        assert_eq!(queue.push(submit(2)), None);
+        assert_eq!(queue.push(submit(3)), Some(submit(3)));
+        assert_eq!(queue.push(Command::Cancel(7)), None);
+        assert_eq!(queue.shed(Priority::New), 1);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.pop(), Some(Command::Cancel(7)));
+        assert_eq!(queue.pop(), Some(submit(1)));
+
+        let mut queue = IngressQueue::new(IngressConfig {
+            shed: ShedPolicy::DropOldest,
+            ..config
+        });
+        queue.push(submit(1));
+        queue.push(submit(2));
+        assert_eq!(queue.push(submit(3)), Some(submit(1)));
+        assert_eq!(queue.pop(), Some(submit(2)));
+        assert_eq!(queue.pop(), Some(submit(3)));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn shared_market_drains_by_priority() {
+        let market = SharedMarket::default().with_ingress(IngressConfig {
+            new_depth: 1,
+            ..Default::default()
+        });
+        market.execute(&submit(5));
+        assert_eq!(market.enqueue(submit(1)), None);
+        assert!(market.enqueue(submit(2)).is_some());
+        assert_eq!(market.enqueue(Command::Cancel(0)), None);
+
+        let processed = market.process(usize::MAX);
+        let seqs: Vec<_> = processed.iter().map(|(seq, ..)| *seq).collect();
+        assert_eq!(seqs, vec![1, 2]);
+        assert!(matches!(processed[0].2[..], [Event::Cancelled(_)]));
+        assert!(market.with_ingress_queue(IngressQueue::is_empty));
+        assert_eq!(market.with(|market| market.depth(1).bids[0].amount), 1);
+    }
+}
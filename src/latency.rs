@@ -0,0 +1,204 @@
+//! Per-operation latency histograms
+//!
+//! Enabled by the `latency` feature, the market records how long each submit, cancel and match
+//! loop takes, see `Market::latency_report`.
+use std::{fmt, time::Duration};
+
+use crate::Market;
+
+/// Sub-buckets per power of two, values are recorded with ~6% precision
+const SUB_BITS: u32 = 4;
+const SUB_BUCKETS: u64 = 1 << SUB_BITS;
+const BUCKETS: usize = ((64 - SUB_BITS + 1) as u64 * SUB_BUCKETS) as usize;
+
+/// HDR style log-linear histogram of nanosecond durations
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    counts: Box<[u64]>,
+    count: u64,
+    sum: u128,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            counts: vec![0; BUCKETS].into_boxed_slice(),
+            count: 0,
+            sum: 0,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn index(value: u64) -> usize {
+        if value < SUB_BUCKETS {
+            return value as usize;
+        }
+        let exponent = 63 - value.leading_zeros();
+        let sub = (value >> (exponent - SUB_BITS)) & (SUB_BUCKETS - 1);
+        ((exponent - SUB_BITS + 1) as u64 * SUB_BUCKETS + sub) as usize
+    }
+    /// Lowest value recorded into bucket `index`
+    fn value(index: usize) -> u64 {
+        let index = index as u64;
+        if index < SUB_BUCKETS {
+            return index;
+        }
+        let exponent = index / SUB_BUCKETS + SUB_BITS as u64 - 1;
+        (SUB_BUCKETS + index % SUB_BUCKETS) << (exponent - SUB_BITS as u64)
+    }
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+        self.counts[Self::index(nanos)] += 1;
+        self.count += 1;
+        self.sum += nanos as u128;
+        self.max = self.max.max(nanos);
+    }
+    /// Number of recorded values
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.sum / self.count as u128) as u64)
+    }
+    /// Value at or below which `percentile`% of recorded values fall
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        let rank = ((percentile / 100.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_nanos(Self::value(index).min(self.max));
+            }
+        }
+        self.max()
+    }
+    /// Summarize the distribution
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            count: self.count,
+            mean: self.mean(),
+            p50: self.percentile(50.0),
+            p90: self.percentile(90.0),
+            p99: self.percentile(99.0),
+            p999: self.percentile(99.9),
+            max: self.max(),
+        }
+    }
+}
+
+/// Summary statistics of a latency histogram
+#[derive(PartialEq, Clone, Debug)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+}
+
+impl fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "n: {} mean: {:?} p50: {:?} p90: {:?} p99: {:?} p99.9: {:?} max: {:?}",
+            self.count, self.mean, self.p50, self.p90, self.p99, self.p999, self.max
+        )
+    }
+}
+
+/// Latency histograms kept by the market
+#[derive(Clone, Debug, Default)]
+pub struct LatencyStats {
+    pub submit: Histogram,
+    pub cancel: Histogram,
+    /// Matching an incoming order against the book
+    pub matching: Histogram,
+}
+
+/// Latency summary of each market operation
+#[derive(PartialEq, Clone, Debug)]
+pub struct LatencyReport {
+    pub submit: LatencySummary,
+    pub cancel: LatencySummary,
+    pub matching: LatencySummary,
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "submit   {}", self.submit)?;
+        writeln!(f, "cancel   {}", self.cancel)?;
+        write!(f, "matching {}", self.matching)
+    }
+}
+
+impl Market {
+    /// Summarize operation latencies recorded so far
+    pub fn latency_report(&self) -> LatencyReport {
+        LatencyReport {
+            submit: self.latency.submit.summary(),
+            cancel: self.latency.cancel.summary(),
+            matching: self.latency.matching.summary(),
+        }
+    }
+    /// Raw latency histograms
+    pub fn latency(&self) -> &LatencyStats {
+        &self.latency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Histogram;
+    use crate::{Market, OrderSide, LOB};
+
+    #[test]
+    fn bucket_bounds() {
+        for value in [0, 1, 15, 16, 17, 31, 32, 1_000, 123_456_789, u64::MAX] {
+            let index = Histogram::index(value);
+            assert!(Histogram::value(index) <= value);
+            // within the precision of a sub-bucket
+            assert!(value - Histogram::value(index) <= value / 16);
+        }
+    }
+
+    #[test]
+    fn percentiles() {
+        let mut histogram = Histogram::default();
+        for nanos in 1..=1_000 {
+            histogram.record(Duration::from_nanos(nanos));
+        }
+        assert_eq!(histogram.count(), 1_000);
+        assert_eq!(histogram.max(), Duration::from_nanos(1_000));
+        let p50 = histogram.percentile(50.0).as_nanos();
+        assert!((470..=500).contains(&p50), "{p50}");
+        let p99 = histogram.percentile(99.0).as_nanos();
+        assert!((930..=990).contains(&p99), "{p99}");
+    }
+
+    #[test]
+    fn market_records_operations() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 10, 1.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 10, 1.0, OrderSide::Buy).unwrap();
+        lob.submit_order(2, 10, 1.0, OrderSide::Buy).unwrap();
+        lob.cancel_order(2);
+
+        let report = lob.latency_report();
+        assert_eq!(report.submit.count, 3);
+        assert_eq!(report.matching.count, 3);
+        assert_eq!(report.cancel.count, 1);
+    }
+}
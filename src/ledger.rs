@@ -0,0 +1,614 @@
+//! Trades, trader positions and trade statistics
+//!
+//! The market numbers every match between two orders as a `Trade`. Trades can be busted or
+//! corrected after the fact, reversing or adjusting their effect on positions and statistics.
+use std::collections::BTreeMap;
+
+use crate::{
+    book::BookBackend, Error, Fill, LimitOrder, Market, Order, OrderId, OrderSide, Rounding,
+    TradePrice,
+};
+
+/// Trade identifier, assigned in execution order
+pub type TradeId = u64;
+
+/// A match between a buy and a sell order
+#[derive(PartialEq, Clone, Debug)]
+pub struct Trade {
+    pub trade_id: TradeId,
+    /// Market time of the trade in nanoseconds
+    pub timestamp: u64,
+    pub price: f32,
+    /// Limit price of the resting order, differs from `price` under some `TradePrice` rules
+    pub maker_price: f32,
+    pub amount: u32,
+    /// Side of the incoming order which removed liquidity
+    pub aggressor: OrderSide,
+    pub buyer: u32,
+    pub buy_order_id: OrderId,
+    pub seller: u32,
+    pub sell_order_id: OrderId,
+    /// The trade was busted and no longer counts towards positions or statistics
+    pub busted: bool,
+}
+
+impl Trade {
+    pub fn notional(&self) -> f64 {
+        self.price as f64 * self.amount as f64
+    }
+}
+
+/// An adjustment to the amount or price of a published trade
+#[derive(PartialEq, Clone, Debug)]
+pub struct Correction {
+    /// The corrected trade
+    pub trade_id: TradeId,
+    pub amount: u32,
+    pub price: f32,
+    pub previous_amount: u32,
+    pub previous_price: f32,
+}
+
+/// A trader's net position and cash balance
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Position {
+    /// Net amount bought
+    pub position: i64,
+    /// Net cash received
+    pub balance: f64,
+}
+
+/// A trader's profit and loss, using average cost
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Pnl {
+    /// Net amount bought
+    pub position: i64,
+    /// Average price of the open position
+    pub average_price: f64,
+    /// Profit locked in by trades reducing the position
+    pub realized: f64,
+    /// Profit of the open position at the reference price
+    pub unrealized: f64,
+}
+
+impl Pnl {
+    pub fn total(&self) -> f64 {
+        self.realized + self.unrealized
+    }
+    /// Apply a trade of `amount`, positive when buying, at `price`
+    fn trade(&mut self, amount: i64, price: f64) {
+        let position = self.position + amount;
+        if self.position == 0 || self.position.signum() == amount.signum() {
+            // opening or adding to the position
+            self.average_price = (self.average_price * self.position as f64
+                + price * amount as f64)
+                / position as f64;
+        } else {
+            let closed = amount.abs().min(self.position.abs());
+            self.realized +=
+                (price - self.average_price) * (closed * self.position.signum()) as f64;
+            if position == 0 {
+                self.average_price = 0.0;
+            } else if position.signum() != self.position.signum() {
+                // flipped, the remainder opens at the trade price
+                self.average_price = price;
+            }
+        }
+        self.position = position;
+    }
+}
+
+/// Executions of one order aggregated across its fills
+#[derive(PartialEq, Clone, Debug)]
+pub struct ExecutionSummary {
+    pub order_id: OrderId,
+    /// Total amount traded
+    pub filled: u64,
+    /// Amount weighted average trade price, zero if nothing traded
+    pub average_price: f64,
+    /// Number of trades
+    pub fills: usize,
+    /// Amount still resting on the book
+    pub remaining: u32,
+}
+
+/// Totals over the trades of the current session which have not been busted
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct TradeStats {
+    pub trades: u64,
+    pub volume: u64,
+    pub notional: f64,
+}
+
+/// Trade history kept by the market
+#[derive(Default, Debug)]
+pub(crate) struct Ledger {
+    trades: Vec<Trade>,
+    positions: BTreeMap<u32, Position>,
+    stats: TradeStats,
+    /// First trade counted by `stats`, later trades belong to the current session
+    stats_from: TradeId,
+    /// Rounding of trade notionals
+    rounding: Option<Rounding>,
+}
+
+impl Ledger {
+    pub fn new(rounding: Option<Rounding>) -> Self {
+        Ledger {
+            rounding,
+            ..Default::default()
+        }
+    }
+    /// Rebuild a ledger from its trades, positions and the first trade of the current session
+    pub fn restore(
+        rounding: Option<Rounding>,
+        trades: Vec<Trade>,
+        positions: BTreeMap<u32, Position>,
+        stats_from: TradeId,
+    ) -> Self {
+        let mut ledger = Ledger {
+            trades,
+            positions,
+            stats_from,
+            rounding,
+            ..Default::default()
+        };
+        let session = ledger.trades.get(stats_from as usize..).unwrap_or_default();
+        for trade in session.iter().filter(|trade| !trade.busted) {
+            let notional = ledger.notional(trade);
+            ledger.stats.trades += 1;
+            ledger.stats.volume += trade.amount as u64;
+            ledger.stats.notional += notional;
+        }
+        ledger
+    }
+    pub fn positions(&self) -> &BTreeMap<u32, Position> {
+        &self.positions
+    }
+    /// First trade of the current session
+    pub fn stats_from(&self) -> TradeId {
+        self.stats_from
+    }
+    /// Notional of `trade` after rounding
+    fn notional(&self, trade: &Trade) -> f64 {
+        match &self.rounding {
+            Some(rounding) => rounding.round(trade.notional()),
+            None => trade.notional(),
+        }
+    }
+    /// Record the trades of an order at `aggressor_price` which matched producing `fills` at
+    /// resting prices, pricing them by `trade_price`, returning the recorded trades
+    pub fn record(
+        &mut self,
+        fills: &[Fill],
+        aggressor: &OrderSide,
+        aggressor_price: f32,
+        trade_price: TradePrice,
+        timestamp: u64,
+    ) -> &[Trade] {
+        let first = self.trades.len();
+        // fills come in (resting, incoming) pairs
+        for pair in fills.chunks_exact(2) {
+            let (buy, sell) = match pair[0].side {
+                OrderSide::Buy => (&pair[0], &pair[1]),
+                OrderSide::Sell => (&pair[1], &pair[0]),
+            };
+            let trade = Trade {
+                trade_id: self.trades.len() as TradeId,
+                timestamp,
+                price: trade_price.price(buy.price, aggressor_price),
+                maker_price: buy.price,
+                amount: buy.amount,
+                aggressor: aggressor.clone(),
+                buyer: buy.trader,
+                buy_order_id: buy.order_id,
+                seller: sell.trader,
+                sell_order_id: sell.order_id,
+                busted: false,
+            };
+            self.apply(&trade, 1);
+            self.trades.push(trade);
+        }
+        &self.trades[first..]
+    }
+    /// Add (`sign` 1) or reverse (`sign` -1) the effect of `trade`
+    fn apply(&mut self, trade: &Trade, sign: i64) {
+        let notional = self.notional(trade);
+        let (amount, notional) = (trade.amount as i64 * sign, notional * sign as f64);
+        let buyer = self.positions.entry(trade.buyer).or_default();
+        buyer.position += amount;
+        buyer.balance -= notional;
+        let seller = self.positions.entry(trade.seller).or_default();
+        seller.position -= amount;
+        seller.balance += notional;
+        if trade.trade_id < self.stats_from {
+            return;
+        }
+        self.stats.trades = self.stats.trades.wrapping_add_signed(sign);
+        self.stats.volume = self.stats.volume.wrapping_add_signed(amount);
+        self.stats.notional += notional;
+    }
+    /// Start counting `stats` afresh from the next trade
+    pub fn roll_stats(&mut self) {
+        self.stats = TradeStats::default();
+        self.stats_from = self.trades.len() as TradeId;
+    }
+    /// Traders with a non-zero position
+    pub fn open_positions(&self) -> impl Iterator<Item = (u32, i64)> + '_ {
+        self.positions
+            .iter()
+            .filter(|(_, position)| position.position != 0)
+            .map(|(trader_id, position)| (*trader_id, position.position))
+    }
+    /// Credit `amount` of cash to `trader_id`, e.g. funding
+    pub fn transfer(&mut self, trader_id: u32, amount: f64) {
+        self.positions.entry(trader_id).or_default().balance += amount;
+    }
+    /// Mark a trade busted and reverse it, returning the trade
+    fn bust(&mut self, trade_id: TradeId) -> Option<Trade> {
+        let trade = self
+            .trades
+            .get_mut(trade_id as usize)
+            .filter(|trade| !trade.busted)?;
+        trade.busted = true;
+        let trade = trade.clone();
+        self.apply(&trade, -1);
+        Some(trade)
+    }
+    /// Replace the amount and price of a trade, returning the correction
+    fn correct(&mut self, trade_id: TradeId, amount: u32, price: f32) -> Option<Correction> {
+        let trade = self
+            .trades
+            .get_mut(trade_id as usize)
+            .filter(|trade| !trade.busted)?;
+        let previous = trade.clone();
+        trade.amount = amount;
+        trade.price = price;
+        let trade = trade.clone();
+        self.apply(&previous, -1);
+        self.apply(&trade, 1);
+        Some(Correction {
+            trade_id,
+            amount,
+            price,
+            previous_amount: previous.amount,
+            previous_price: previous.price,
+        })
+    }
+}
+
+impl Market {
+    /// All trades in execution order, including busted trades
+    pub fn trades(&self) -> &[Trade] {
+        &self.ledger.trades
+    }
+    pub fn trade(&self, trade_id: TradeId) -> Option<&Trade> {
+        self.ledger.trades.get(trade_id as usize)
+    }
+    /// Net position and balance of `trader_id`
+    pub fn position(&self, trader_id: u32) -> Position {
+        self.ledger
+            .positions
+            .get(&trader_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+    pub fn trade_stats(&self) -> &TradeStats {
+        &self.ledger.stats
+    }
+    /// Mid price of the book, or the last trade price if either side is empty
+    pub fn reference_price(&self) -> Option<f32> {
+        match (self.buys.front(), self.sells.front()) {
+            (Some(bid), Some(ask)) => Some((bid.inner().price + ask.inner().price) / 2.0),
+            _ => self
+                .trades()
+                .iter()
+                .rev()
+                .find(|trade| !trade.busted)
+                .map(|trade| trade.price),
+        }
+    }
+    /// Realized and unrealized profit of `trader_id` from their trades, valuing the open position
+    /// at the reference price
+    pub fn pnl(&self, trader_id: u32) -> Pnl {
+        let mut pnl = Pnl::default();
+        for trade in self.trades().iter().filter(|trade| !trade.busted) {
+            let amount = trade.amount as i64;
+            if trade.buyer == trader_id {
+                pnl.trade(amount, trade.price as f64);
+            }
+            if trade.seller == trader_id {
+                pnl.trade(-amount, trade.price as f64);
+            }
+        }
+        if let Some(price) = self.reference_price() {
+            pnl.unrealized = (price as f64 - pnl.average_price) * pnl.position as f64;
+        }
+        pnl
+    }
+    /// Filled amount, average price and remaining amount of `order_id` from its trades which
+    /// have not been busted, `None` if the order neither traded nor rests
+    ///
+    /// Scans the trade history, amended orders are summarized under each assigned id
+    pub fn execution_summary(&self, order_id: OrderId) -> Option<ExecutionSummary> {
+        let (mut filled, mut notional, mut fills) = (0_u64, 0.0, 0);
+        for trade in self.trades().iter().filter(|trade| {
+            !trade.busted && (trade.buy_order_id == order_id || trade.sell_order_id == order_id)
+        }) {
+            filled += trade.amount as u64;
+            notional += trade.notional();
+            fills += 1;
+        }
+        let remaining = self.get_order(order_id).map(|order| order.amount);
+        if fills == 0 && remaining.is_none() {
+            return None;
+        }
+        Some(ExecutionSummary {
+            order_id,
+            filled,
+            average_price: if filled > 0 {
+                notional / filled as f64
+            } else {
+                0.0
+            },
+            fills,
+            remaining: remaining.unwrap_or_default(),
+        })
+    }
+    /// Bust a trade, reversing its effect on positions and statistics
+    ///
+    /// With `restore_liquidity` the traded amount is credited back to the resting order at its
+    /// limit price `Trade::maker_price`, keeping its flags and time priority. The restore is
+    /// rejected with `Error::OrderNotFound` and the trade left standing if the order no longer
+    /// rests, as after a cancel or a full fill, since recreating it could cross the book
+    pub fn bust_trade(
+        &mut self,
+        trade_id: TradeId,
+        restore_liquidity: bool,
+    ) -> Result<Trade, Error> {
+        let trade = self
+            .trade(trade_id)
+            .filter(|trade| !trade.busted)
+            .ok_or(Error::TradeNotFound(trade_id))?;
+        let restore = if restore_liquidity {
+            let (side, order_id) = match trade.aggressor {
+                OrderSide::Buy => (OrderSide::Sell, trade.sell_order_id),
+                OrderSide::Sell => (OrderSide::Buy, trade.buy_order_id),
+            };
+            let rests = match side {
+                OrderSide::Buy => self.buys.get_at(order_id, trade.maker_price).is_some(),
+                OrderSide::Sell => self.sells.get_at(order_id, trade.maker_price).is_some(),
+            };
+            if !rests {
+                return Err(Error::OrderNotFound(order_id));
+            }
+            let resting = match side {
+                OrderSide::Buy => self.buys.remove(order_id).map(|o| o.inner().clone()),
+                OrderSide::Sell => self.sells.remove(order_id).map(|o| o.inner().clone()),
+            };
+            Some((side, resting.expect("order rests")))
+        } else {
+            None
+        };
+        let trade = self.ledger.bust(trade_id).expect("trade is not busted");
+        self.rebucket();
+        if let Some((side, order)) = restore {
+            let order = LimitOrder {
+                amount: order.amount + trade.amount,
+                price: trade.maker_price,
+                ..order
+            };
+            match side {
+                OrderSide::Buy => self.buys.insert_order(&order.into()),
+                OrderSide::Sell => self.sells.insert_order(&order.into()),
+            }
+            .expect("orderbook has capacity");
+        }
+        Ok(trade)
+    }
+    /// Correct the amount and price of a trade, adjusting positions and statistics
+    /// The book is unaffected, use `bust_trade` to remove a trade entirely
+    pub fn correct_trade(
+        &mut self,
+        trade_id: TradeId,
+        amount: u32,
+        price: f32,
+    ) -> Result<Correction, Error> {
+        if amount == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        let correction = self
+            .ledger
+            .correct(trade_id, amount, price)
+            .ok_or(Error::TradeNotFound(trade_id))?;
+        self.rebucket();
+        Ok(correction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExecutionSummary, Pnl, Position};
+    use crate::{
+        depth::Level, Error, Market, MarketConfig, OrderFlags, OrderRequest, OrderSide, TradePrice,
+        LOB,
+    };
+
+    #[test]
+    fn tracks_positions() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 30, 2.0, OrderSide::Buy).unwrap();
+        lob.submit_order(3, 20, 2.5, OrderSide::Buy).unwrap();
+
+        assert_eq!(lob.trades().len(), 2);
+        assert_eq!(lob.trades()[1].buyer, 3);
+        assert_eq!(lob.trades()[1].price, 2.0);
+        assert_eq!(
+            lob.position(1),
+            Position {
+                position: -50,
+                balance: 100.0
+            }
+        );
+        assert_eq!(lob.position(2).position, 30);
+        assert_eq!(lob.trade_stats().volume, 50);
+    }
+
+    #[test]
+    fn summarizes_executions() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 10, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 30, 2.5, OrderSide::Sell).unwrap();
+        let result = lob
+            .place_order(crate::OrderRequest::new(3, 50, 2.5, OrderSide::Buy))
+            .unwrap();
+        assert_eq!(
+            lob.execution_summary(result.order_id),
+            Some(ExecutionSummary {
+                order_id: 2,
+                filled: 40,
+                average_price: (20.0 + 75.0) / 40.0,
+                fills: 2,
+                remaining: 10,
+            })
+        );
+        assert_eq!(
+            lob.execution_summary(0).map(|summary| summary.remaining),
+            Some(0)
+        );
+
+        lob.bust_trade(1, false).unwrap();
+        assert_eq!(lob.execution_summary(1), None);
+        assert_eq!(
+            lob.execution_summary(2).map(|summary| summary.filled),
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn bust_reverses_trade() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 30, 2.0, OrderSide::Buy).unwrap();
+        lob.submit_order(3, 70, 2.0, OrderSide::Buy).unwrap();
+        assert!(lob.get_order(0).is_none());
+        // the fully filled order cannot be restored, the trade stands
+        assert_eq!(lob.bust_trade(0, true), Err(Error::OrderNotFound(0)));
+        assert_eq!(lob.trade_stats().trades, 2);
+
+        let trade = lob.bust_trade(1, false).unwrap();
+        assert!(trade.busted);
+        assert_eq!(lob.position(3), Position::default());
+        assert_eq!(lob.position(1).position, -30);
+        assert_eq!(lob.trade_stats().trades, 1);
+        assert!(lob.get_order(0).is_none());
+        assert_eq!(lob.bust_trade(1, false), Err(Error::TradeNotFound(1)));
+    }
+
+    #[test]
+    fn bust_restores_live_liquidity() {
+        let mut lob = Market::default();
+        let request =
+            OrderRequest::new(1, 100, 2.0, OrderSide::Sell).with_flags(OrderFlags::POST_ONLY);
+        lob.place_order(request).unwrap();
+        lob.submit_order(4, 10, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 30, 2.0, OrderSide::Buy).unwrap();
+
+        // restored liquidity keeps the order's flags and time priority
+        lob.bust_trade(0, true).unwrap();
+        let order = lob.get_order(0).unwrap();
+        assert_eq!((order.amount, order.flags), (100, OrderFlags::POST_ONLY));
+        assert_eq!(lob.trade_stats().volume, 0);
+        let fills = lob.submit_order(3, 100, 2.0, OrderSide::Buy).unwrap();
+        assert_eq!(fills[0].order_id, 0);
+
+        // the order has since been filled and a bid rests at its price
+        lob.submit_order(3, 20, 2.0, OrderSide::Buy).unwrap();
+        assert_eq!(lob.bust_trade(1, true), Err(Error::OrderNotFound(0)));
+        assert_eq!(lob.depth(1).bids[0].amount, 10);
+
+        // a cancelled order is not recreated
+        lob.submit_order(1, 50, 2.5, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 20, 2.5, OrderSide::Buy).unwrap();
+        lob.cancel_order(5).unwrap();
+        assert_eq!(lob.bust_trade(3, true), Err(Error::OrderNotFound(5)));
+        assert!(lob.depth(1).asks.is_empty());
+        assert!(!lob.trade(3).unwrap().busted);
+    }
+
+    #[test]
+    fn bust_restores_at_maker_price() {
+        let mut lob = Market::new(MarketConfig {
+            trade_price: TradePrice::Midpoint,
+            ..Default::default()
+        });
+        lob.submit_order(1, 100, 1.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 30, 2.0, OrderSide::Buy).unwrap();
+        let trade = lob.trade(0).unwrap();
+        assert_eq!((trade.price, trade.maker_price), (1.5, 1.0));
+
+        lob.bust_trade(0, true).unwrap();
+        assert_eq!(
+            lob.depth(1).asks[0],
+            Level {
+                price: 1.0,
+                amount: 100,
+                orders: 1
+            }
+        );
+    }
+
+    #[test]
+    fn correct_adjusts_positions() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 30, 2.0, OrderSide::Buy).unwrap();
+
+        let correction = lob.correct_trade(0, 20, 1.5).unwrap();
+        assert_eq!(
+            (correction.previous_amount, correction.previous_price),
+            (30, 2.0)
+        );
+        assert_eq!(
+            lob.position(2),
+            Position {
+                position: 20,
+                balance: -30.0
+            }
+        );
+        assert_eq!(lob.trade(0).map(|trade| trade.amount), Some(20));
+        assert_eq!(lob.trade_stats().notional, 30.0);
+        assert_eq!(lob.correct_trade(0, 0, 1.5), Err(Error::ZeroAmount));
+        assert_eq!(lob.correct_trade(1, 1, 1.5), Err(Error::TradeNotFound(1)));
+    }
+
+    #[test]
+    fn realized_and_unrealized_pnl() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 100, 2.0, OrderSide::Buy).unwrap();
+        lob.submit_order(1, 50, 3.0, OrderSide::Buy).unwrap();
+        lob.submit_order(2, 60, 3.0, OrderSide::Sell).unwrap();
+        // trader 2 bought 100 at 2 and sold 50 at 3, 10 rest at 3 with no bids
+        assert_eq!(lob.reference_price(), Some(3.0));
+        assert_eq!(
+            lob.pnl(2),
+            Pnl {
+                position: 50,
+                average_price: 2.0,
+                realized: 50.0,
+                unrealized: 50.0,
+            }
+        );
+        assert_eq!(lob.pnl(1).total(), -100.0);
+
+        // flipping short opens at the trade price
+        lob.submit_order(3, 100, 2.5, OrderSide::Buy).unwrap();
+        assert_eq!(lob.reference_price(), Some(2.75));
+        lob.submit_order(2, 70, 2.5, OrderSide::Sell).unwrap();
+        let pnl = lob.pnl(2);
+        assert_eq!((pnl.position, pnl.average_price), (-20, 2.5));
+        assert_eq!(pnl.realized, 75.0);
+    }
+}
@@ -1,9 +1,81 @@
 //! Simple limit order book
 
-use std::collections::VecDeque;
-
+pub mod algos;
+pub mod analytics;
+pub mod audit;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod book;
+pub mod bus;
+pub mod clearing;
+pub mod clock;
+mod command;
+pub mod compaction;
+pub mod conformance;
+pub mod consolidated;
+pub mod day;
+pub mod depth;
+mod error;
+mod exchange;
+#[cfg(feature = "arrow")]
+pub mod export;
+pub mod feed;
+mod fixed;
+pub mod fixtures;
+pub mod funding;
+pub mod halt;
+pub mod heatmap;
+pub mod hooks;
+pub mod ingress;
+#[cfg(feature = "latency")]
+pub mod latency;
+pub mod ledger;
 mod order;
-pub use order::{BuyLimitOrder, Fill, LimitOrder, Order, OrderSide, SellLimitOrder};
+mod policy;
+pub mod priority;
+#[cfg(feature = "json")]
+pub mod protocol;
+pub mod replay;
+pub mod router;
+#[cfg(feature = "scenario")]
+pub mod scenario;
+#[cfg(feature = "json")]
+pub mod seed;
+pub mod session;
+pub mod shadow;
+mod shared;
+pub mod sim;
+mod snapshot;
+pub mod storage;
+pub mod stress;
+pub mod wire;
+use book::{BookBackend, OrderBook};
+pub use command::{Command, CommandParseError, Event};
+pub use error::Error;
+pub use exchange::{Exchange, Instrument, MarketInfo, MarketStatus};
+pub use fixed::FixedMarket;
+pub use order::{
+    BuyLimitOrder, Fill, FillFlags, LimitOrder, Liquidity, Order, OrderFlags, OrderId,
+    OrderRequest, OrderSide, SellLimitOrder,
+};
+pub use policy::{
+    BudgetRemainder, ConfigError, MarketConfig, MatchBudget, MatchingPolicy, ResidualAllocation,
+    Rounding, RoundingMode, StpAction, TradePrice,
+};
+pub use shared::SharedMarket;
+pub use snapshot::{Snapshot, SnapshotError, SnapshotParseError};
+
+/// Evaluate `$expr` recording its duration into `$histogram` when the `latency` feature is enabled
+macro_rules! timed {
+    ($histogram:expr, $expr:expr) => {{
+        #[cfg(feature = "latency")]
+        let start = std::time::Instant::now();
+        let result = $expr;
+        #[cfg(feature = "latency")]
+        $histogram.record(start.elapsed());
+        result
+    }};
+}
 
 /// Provides a limit order book API
 pub trait LOB {
@@ -15,123 +87,419 @@ pub trait LOB {
         price: f32,
         side: OrderSide,
     ) -> Result<Vec<Fill>, Self::Error>;
-}
-
-#[derive(Default, Debug)]
-struct OrderBook<T: Order>(VecDeque<T>);
-
-impl<T: Order> OrderBook<T> {
-    pub fn front(&self) -> Option<&T> {
-        self.0.front()
-    }
-    pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
-    }
-    /// Insert an order into the book at the correct location
-    pub fn insert_order(&mut self, order: &T) -> Result<(), ()> {
-        if let Err(idx) = self.0.binary_search(order) {
-            self.0.insert(idx, order.clone());
-            Ok(())
-        } else {
-            Err(())
-        }
-    }
-    /// Submit an order to the book
-    /// Returning fills and remaining unfilled order if any
-    pub fn submit_order<'a>(
-        &mut self,
-        order: &'a mut T::Opposite,
-    ) -> (Vec<Fill>, Option<&'a T::Opposite>) {
-        // try add the order to the book absorbing any resting liquidity
-        let mut fills = Vec::<Fill>::default();
-        let mut remove_count = 0;
-        for resting_order in self.0.iter_mut() {
-            if let Some((fill_0, fill_1)) = resting_order.try_fill(order) {
-                fills.push(fill_0);
-                fills.push(fill_1);
-                if resting_order.is_zero() {
-                    remove_count += 1;
-                }
-            } else {
-                break;
-            }
-            if order.is_zero() {
-                break;
-            }
-        }
-
-        // Remove filled orders from the book
-        if remove_count > 0 {
-            let _ = self.0.drain(0..remove_count);
-        }
-
-        if order.is_zero() {
-            (fills, None)
-        } else {
-            (fills, Some(order))
-        }
+    /// Submit an order from an `OrderRequest`
+    fn submit(&mut self, request: OrderRequest) -> Result<Vec<Fill>, Self::Error> {
+        self.submit_order(
+            request.trader_id,
+            request.amount,
+            request.price,
+            request.side,
+        )
     }
 }
 
+/// Order flags enforced by `Market`, orders with other flags are rejected
+const SUPPORTED_FLAGS: OrderFlags = OrderFlags::POST_ONLY
+    .union(OrderFlags::ANTI_INTERNALIZE)
+    .union(OrderFlags::DAY);
+
 #[derive(Default)]
 pub struct Market {
-    /// Order nonce
+    config: MarketConfig,
+    /// Order nonce, the lowest priority not yet assigned
     nonce: u64,
+    priority: priority::MarketPriority,
     buys: OrderBook<BuyLimitOrder>,
     sells: OrderBook<SellLimitOrder>,
+    ledger: ledger::Ledger,
+    top: policy::TopOrders,
+    clock: clock::MarketClock,
+    bus: bus::EventBus,
+    hooks: hooks::Hooks,
+    funding: funding::FundingState,
+    halt: halt::HaltState,
+    day: day::DayState,
+    vpin: analytics::VpinBuckets,
+    /// RNG breaking allocation ties, seeded by `MarketConfig::tiebreak_seed`
+    /// Unset unless seeded or drawn from by the matching policy
+    tiebreak: Option<rand::rngs::StdRng>,
+    #[cfg(feature = "latency")]
+    latency: latency::LatencyStats,
 }
 
-impl LOB for Market {
-    type Error = ();
-    fn submit_order(
-        &mut self,
-        trader_id: u32,
-        amount: u32,
-        price: f32,
-        side: OrderSide,
-    ) -> Result<Vec<Fill>, Self::Error> {
-        if amount == 0 {
-            return Ok(vec![]);
+/// Outcome of placing an order on the market
+#[derive(PartialEq, Debug)]
+pub struct OrderResult {
+    /// Id assigned to the order
+    pub order_id: OrderId,
+    pub fills: Vec<Fill>,
+    /// Quantity taken off the book without trading: a remainder of the order that did not
+    /// rest, same group orders cancelled by self trade prevention, or the amount amended away
+    pub cancelled: Vec<LimitOrder>,
+}
+
+impl Market {
+    /// A market with `config` after checking its settings are valid
+    pub fn with_config(config: MarketConfig) -> Result<Self, policy::ConfigError> {
+        config.validate()?;
+        Ok(Self::new(config))
+    }
+    /// A market with `config` used as given, see `with_config` to validate it
+    pub fn new(config: MarketConfig) -> Self {
+        Market {
+            ledger: ledger::Ledger::new(config.quote_rounding.clone()),
+            tiebreak: match (config.tiebreak_seed, config.matching.is_random()) {
+                (Some(seed), _) => Some(rand::SeedableRng::seed_from_u64(seed)),
+                (None, true) => Some(rand::SeedableRng::seed_from_u64(0)),
+                (None, false) => None,
+            },
+            config,
+            ..Default::default()
         }
+    }
+    pub fn config(&self) -> &MarketConfig {
+        &self.config
+    }
+    /// Replace the clock used to timestamp trades
+    pub fn set_clock(&mut self, clock: impl clock::Clock + 'static) {
+        self.clock = clock::MarketClock(Box::new(clock));
+    }
+    /// Replace the source of order priorities, which are also the order ids
+    pub fn set_priority_source(&mut self, source: impl priority::PrioritySource + 'static) {
+        self.priority = priority::MarketPriority(Box::new(source));
+    }
+    /// Current market time in nanoseconds
+    pub fn now(&self) -> u64 {
+        self.clock.0.now()
+    }
+    /// Place an order returning its assigned id and any fills
+    /// Orders with zero amount are rejected
+    pub fn place_order(&mut self, request: OrderRequest) -> Result<OrderResult, Error> {
+        timed!(self.latency.submit, self.match_order(request))
+    }
+    fn match_order(&mut self, mut request: OrderRequest) -> Result<OrderResult, Error> {
+        if self.halt.halted {
+            return Err(Error::Halted);
+        }
+        if request.amount == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        self.check_flags(&request)?;
+        self.settle_funding();
+        self.pre_trade(&mut request)?;
 
-        let order = LimitOrder {
-            price,
-            amount,
-            trader_id,
-            nonce: self.nonce,
+        let order_id = self.priority.0.next(self.nonce);
+        if order_id < self.nonce {
+            return Err(Error::StalePriority(order_id));
+        }
+        let mut order = LimitOrder {
+            price: request.price,
+            amount: request.amount,
+            trader_id: request.trader_id,
+            nonce: order_id,
+            flags: request.flags,
+        };
+        // same group orders sit out matching and keep their priority
+        let internal = if request.flags.contains(OrderFlags::ANTI_INTERNALIZE) {
+            self.withdraw_group(&request.side.opposite(), request.trader_id)
+        } else {
+            vec![]
+        };
+        // post-only orders are checked against the book they would match
+        if request.flags.contains(OrderFlags::POST_ONLY) && self.crosses(&request) {
+            self.restore_group(&request.side.opposite(), internal);
+            return Err(Error::PostOnlyCrosses);
+        }
+        // stop sweeping where the matching budget runs out
+        let limit = match request.side {
+            OrderSide::Buy => self.config.budget.limit(
+                self.sells.iter().map(Order::inner),
+                request.amount,
+                |price| price <= request.price,
+            ),
+            OrderSide::Sell => self.config.budget.limit(
+                self.buys.iter().map(Order::inner),
+                request.amount,
+                |price| price >= request.price,
+            ),
+        };
+        let mut rests = true;
+        if let Some(limit) = limit {
+            order.price = limit.price;
+            if let Some(amount) = limit.amount {
+                order.amount = amount;
+            }
+            rests = limit.amount.is_none()
+                && self.config.budget.remainder == policy::BudgetRemainder::Rest;
+        }
+        // a remainder resting across withdrawn same group orders would cross the book
+        let rest_price = order.price;
+        let crosses_rest = |resting: &LimitOrder| match request.side {
+            OrderSide::Buy => resting.price <= rest_price,
+            OrderSide::Sell => resting.price >= rest_price,
         };
+        if self.config.stp_action == StpAction::CancelIncoming && internal.iter().any(crosses_rest)
+        {
+            rests = false;
+        }
 
-        let fills = match side {
+        let mut fills = match request.side {
             OrderSide::Buy => {
                 let mut order = order.into();
-                let (fills, unfilled) = self.sells.submit_order(&mut order);
-                if let Some(unfilled) = unfilled {
+                let (fills, unfilled) = timed!(
+                    self.latency.matching,
+                    policy::submit_order(
+                        &mut self.sells,
+                        &mut order,
+                        &self.config.matching,
+                        self.top.sell,
+                        self.tiebreak.as_mut(),
+                    )
+                );
+                if let Some(unfilled) = unfilled.filter(|_| rests) {
+                    let improves = self
+                        .buys
+                        .front()
+                        .is_none_or(|best| unfilled.inner().price > best.inner().price);
                     self.buys
                         .insert_order(unfilled)
                         .expect("orderbook has capacity");
+                    if improves {
+                        self.top.buy = Some(order_id);
+                    }
                 }
                 fills
             }
             OrderSide::Sell => {
                 let mut order = order.into();
-                let (fills, unfilled) = self.buys.submit_order(&mut order);
-                if let Some(unfilled) = unfilled {
+                let (fills, unfilled) = timed!(
+                    self.latency.matching,
+                    policy::submit_order(
+                        &mut self.buys,
+                        &mut order,
+                        &self.config.matching,
+                        self.top.buy,
+                        self.tiebreak.as_mut(),
+                    )
+                );
+                if let Some(unfilled) = unfilled.filter(|_| rests) {
+                    let improves = self
+                        .sells
+                        .front()
+                        .is_none_or(|best| unfilled.inner().price < best.inner().price);
                     self.sells
                         .insert_order(unfilled)
                         .expect("orderbook has capacity");
+                    if improves {
+                        self.top.sell = Some(order_id);
+                    }
                 }
                 fills
             }
         };
+        let rested = match request.side {
+            OrderSide::Buy => self.buys.get_at(order_id, rest_price).map(Order::inner),
+            OrderSide::Sell => self.sells.get_at(order_id, rest_price).map(Order::inner),
+        }
+        .map(|order| order.amount);
+        let (mut cancelled, internal) = if rested.is_some() {
+            // only `StpAction::CancelResting` rests across same group orders, which are cancelled
+            internal.into_iter().partition(crosses_rest)
+        } else {
+            (vec![], internal)
+        };
+        self.restore_group(&request.side.opposite(), internal);
+        let filled: u32 = fills
+            .iter()
+            .filter(|fill| fill.order_id == order_id)
+            .map(|fill| fill.amount)
+            .sum();
+        let unrested = request.amount - filled - rested.unwrap_or_default();
+        if unrested > 0 {
+            cancelled.push(LimitOrder {
+                price: request.price,
+                amount: unrested,
+                trader_id: request.trader_id,
+                nonce: order_id,
+                flags: request.flags,
+            });
+        }
+        let trades = self.ledger.record(
+            &fills,
+            &request.side,
+            request.price,
+            self.config.trade_price,
+            self.clock.0.now(),
+        );
+        for trade in trades {
+            self.vpin.add(&self.config.vpin, trade);
+        }
+        // books fill at the resting price
+        if self.config.trade_price != TradePrice::Resting {
+            for fill in fills.iter_mut() {
+                fill.price = self.config.trade_price.price(fill.price, request.price);
+            }
+        }
 
-        self.nonce += 1;
-        Ok(fills)
+        self.nonce = order_id + 1;
+        self.auto_compact();
+        let result = OrderResult {
+            order_id,
+            fills,
+            cancelled,
+        };
+        self.post_trade(&request, &result);
+        Ok(result)
+    }
+    /// Reject flags the market does not enforce
+    fn check_flags(&self, request: &OrderRequest) -> Result<(), Error> {
+        let unsupported = request.flags & !SUPPORTED_FLAGS;
+        if !unsupported.is_empty() {
+            return Err(Error::UnsupportedFlags(unsupported));
+        }
+        Ok(())
+    }
+    /// Whether `request` would trade with the book on entry
+    fn crosses(&self, request: &OrderRequest) -> bool {
+        match request.side {
+            OrderSide::Buy => self
+                .sells
+                .front()
+                .is_some_and(|best| best.inner().price <= request.price),
+            OrderSide::Sell => self
+                .buys
+                .front()
+                .is_some_and(|best| best.inner().price >= request.price),
+        }
+    }
+    /// Cancel a resting order, returning it if it was found
+    pub fn cancel_order(&mut self, order_id: OrderId) -> Option<LimitOrder> {
+        let cancelled = timed!(
+            self.latency.cancel,
+            self.buys
+                .remove(order_id)
+                .map(|order| order.inner().clone())
+                .or_else(|| {
+                    self.sells
+                        .remove(order_id)
+                        .map(|order| order.inner().clone())
+                })
+        );
+        self.auto_compact();
+        cancelled
+    }
+    /// Resting orders of `trader_id`, bids then asks in time priority
+    pub fn orders_for(&self, trader_id: u32) -> Vec<&LimitOrder> {
+        self.buys
+            .orders_of(trader_id)
+            .map(Order::inner)
+            .chain(self.sells.orders_of(trader_id).map(Order::inner))
+            .collect()
+    }
+    /// Cancel every resting order of `trader_id`, e.g. as a kill switch, returning the orders
+    pub fn cancel_all(&mut self, trader_id: u32) -> Vec<LimitOrder> {
+        let order_ids: Vec<OrderId> = self
+            .buys
+            .order_ids_of(trader_id)
+            .chain(self.sells.order_ids_of(trader_id))
+            .map(|(order_id, _)| order_id)
+            .collect();
+        order_ids
+            .into_iter()
+            .filter_map(|order_id| self.cancel_order(order_id))
+            .collect()
+    }
+    /// Amend the amount and price of a resting order
+    /// Reducing the amount at the same price keeps queue priority, otherwise the order is
+    /// replaced and assigned a new id
+    pub fn amend_order(
+        &mut self,
+        order_id: OrderId,
+        amount: u32,
+        price: f32,
+    ) -> Result<OrderResult, Error> {
+        if self.halt.halted {
+            return Err(Error::Halted);
+        }
+        if amount == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        let (side, order) = if let Some(order) = self.buys.remove(order_id) {
+            (OrderSide::Buy, order.inner().clone())
+        } else if let Some(order) = self.sells.remove(order_id) {
+            (OrderSide::Sell, order.inner().clone())
+        } else {
+            return Err(Error::OrderNotFound(order_id));
+        };
+
+        if order.price == price && amount <= order.amount {
+            let cancelled = if amount < order.amount {
+                vec![LimitOrder {
+                    amount: order.amount - amount,
+                    ..order.clone()
+                }]
+            } else {
+                vec![]
+            };
+            let order = LimitOrder { amount, ..order };
+            match side {
+                OrderSide::Buy => self.buys.insert_order(&order.into()),
+                OrderSide::Sell => self.sells.insert_order(&order.into()),
+            }
+            .expect("orderbook has capacity");
+            return Ok(OrderResult {
+                order_id,
+                fills: vec![],
+                cancelled,
+            });
+        }
+
+        let request =
+            OrderRequest::new(order.trader_id, amount, price, side.clone()).with_flags(order.flags);
+        match self.place_order(request) {
+            Ok(mut result) => {
+                result.cancelled.insert(0, order);
+                Ok(result)
+            }
+            Err(err) => {
+                // a rejected replacement leaves the original order in place
+                self.restore_group(&side, vec![order]);
+                Err(err)
+            }
+        }
+    }
+    /// Get a resting order by id
+    pub fn get_order(&self, order_id: OrderId) -> Option<&LimitOrder> {
+        self.buys
+            .get(order_id)
+            .map(Order::inner)
+            .or_else(|| self.sells.get(order_id).map(Order::inner))
+    }
+}
+
+impl LOB for Market {
+    type Error = Error;
+    fn submit_order(
+        &mut self,
+        trader_id: u32,
+        amount: u32,
+        price: f32,
+        side: OrderSide,
+    ) -> Result<Vec<Fill>, Self::Error> {
+        if amount == 0 {
+            return Ok(vec![]);
+        }
+        self.place_order(OrderRequest::new(trader_id, amount, price, side))
+            .map(|result| result.fills)
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::{BuyLimitOrder, Fill, LimitOrder, Market, OrderSide, SellLimitOrder, LOB};
+    use crate::{
+        book::BookBackend, BuyLimitOrder, Error, Fill, LimitOrder, Liquidity, Market, MarketConfig,
+        Order, OrderFlags, OrderRequest, OrderSide, SellLimitOrder, StpAction, LOB,
+    };
 
     #[test]
     fn orders_sort_by_price_then_nonce() {
@@ -141,6 +509,7 @@ pub mod tests {
                 nonce: 2,
                 price: 2.0,
                 amount: 1,
+                ..Default::default()
             }
             .into(),
             LimitOrder {
@@ -148,6 +517,7 @@ pub mod tests {
                 nonce: 1,
                 price: 2.0,
                 amount: 1,
+                ..Default::default()
             }
             .into(),
             LimitOrder {
@@ -155,6 +525,7 @@ pub mod tests {
                 nonce: 3,
                 price: 1.0,
                 amount: 1,
+                ..Default::default()
             }
             .into(),
         ];
@@ -168,6 +539,7 @@ pub mod tests {
                     nonce: 1,
                     price: 2.0,
                     amount: 1,
+                    ..Default::default()
                 }
                 .into(),
                 LimitOrder {
@@ -175,6 +547,7 @@ pub mod tests {
                     nonce: 2,
                     price: 2.0,
                     amount: 1,
+                    ..Default::default()
                 }
                 .into(),
                 LimitOrder {
@@ -182,6 +555,7 @@ pub mod tests {
                     nonce: 3,
                     price: 1.0,
                     amount: 1,
+                    ..Default::default()
                 }
                 .into(),
             ]
@@ -193,6 +567,7 @@ pub mod tests {
                 nonce: 2,
                 price: 2.0,
                 amount: 1,
+                ..Default::default()
             }
             .into(),
             LimitOrder {
@@ -200,6 +575,7 @@ pub mod tests {
                 nonce: 1,
                 price: 2.0,
                 amount: 1,
+                ..Default::default()
             }
             .into(),
             LimitOrder {
@@ -207,6 +583,7 @@ pub mod tests {
                 nonce: 3,
                 price: 1.0,
                 amount: 1,
+                ..Default::default()
             }
             .into(),
         ];
@@ -220,6 +597,7 @@ pub mod tests {
                     nonce: 3,
                     price: 1.0,
                     amount: 1,
+                    ..Default::default()
                 }
                 .into(),
                 LimitOrder {
@@ -227,6 +605,7 @@ pub mod tests {
                     nonce: 1,
                     price: 2.0,
                     amount: 1,
+                    ..Default::default()
                 }
                 .into(),
                 LimitOrder {
@@ -234,6 +613,7 @@ pub mod tests {
                     nonce: 2,
                     price: 2.0,
                     amount: 1,
+                    ..Default::default()
                 }
                 .into(),
             ]
@@ -258,10 +638,12 @@ pub mod tests {
         assert_eq!(
             fills.as_slice(),
             &[
-                Fill::new(500, 5.0, OrderSide::Buy, 5, seller_id,),
-                Fill::new(500, 5.0, OrderSide::Sell, seller_id, 5,),
-                Fill::new(50, 4.0, OrderSide::Buy, 4, seller_id,),
-                Fill::new(50, 4.0, OrderSide::Sell, seller_id, 4,),
+                Fill::new(500, 5.0, OrderSide::Buy, 5, seller_id, 4)
+                    .with_liquidity(Liquidity::Added),
+                Fill::new(500, 5.0, OrderSide::Sell, seller_id, 5, 5),
+                Fill::new(50, 4.0, OrderSide::Buy, 4, seller_id, 3)
+                    .with_liquidity(Liquidity::Added),
+                Fill::new(50, 4.0, OrderSide::Sell, seller_id, 4, 5),
             ]
         );
         let _fills = lob.submit_order(seller_id, 1050, 1_f32 * 1.0_f32, OrderSide::Sell);
@@ -275,6 +657,7 @@ pub mod tests {
                     price: 1_f32,
                     amount: 100,
                     nonce: 6,
+                    ..Default::default()
                 }
                 .into()
             )
@@ -299,10 +682,12 @@ pub mod tests {
         assert_eq!(
             fills.as_slice(),
             &[
-                Fill::new(100, 1.0, OrderSide::Sell, 1, buyer_id,),
-                Fill::new(100, 1.0, OrderSide::Buy, buyer_id, 1),
-                Fill::new(50, 2.0, OrderSide::Sell, 2, buyer_id,),
-                Fill::new(50, 2.0, OrderSide::Buy, buyer_id, 2),
+                Fill::new(100, 1.0, OrderSide::Sell, 1, buyer_id, 0)
+                    .with_liquidity(Liquidity::Added),
+                Fill::new(100, 1.0, OrderSide::Buy, buyer_id, 1, 5),
+                Fill::new(50, 2.0, OrderSide::Sell, 2, buyer_id, 1)
+                    .with_liquidity(Liquidity::Added),
+                Fill::new(50, 2.0, OrderSide::Buy, buyer_id, 2, 5),
             ]
         );
 
@@ -317,12 +702,175 @@ pub mod tests {
                     price: 5_f32,
                     amount: 100,
                     nonce: 6,
+                    ..Default::default()
                 }
                 .into()
             )
         );
     }
 
+    #[test]
+    fn cancel_resting_order() {
+        let mut lob = Market::default();
+
+        assert_eq!(lob.submit_order(1, 100, 4.0, OrderSide::Buy), Ok(vec![]));
+        assert_eq!(lob.submit_order(2, 100, 5.0, OrderSide::Sell), Ok(vec![]));
+
+        assert_eq!(
+            lob.cancel_order(0),
+            Some(LimitOrder {
+                trader_id: 1,
+                price: 4.0,
+                amount: 100,
+                nonce: 0,
+                ..Default::default()
+            })
+        );
+        assert_eq!(lob.cancel_order(0), None);
+        assert!(lob.buys.is_empty());
+
+        // cancelled liquidity can no longer be matched
+        let fills = lob.submit_order(3, 100, 4.0, OrderSide::Sell).unwrap();
+        assert!(fills.is_empty());
+        assert!(lob.cancel_order(1).is_some());
+    }
+
+    #[test]
+    fn post_only_ignores_own_group() {
+        let mut lob = Market::new(MarketConfig {
+            stp_groups: [(1, 7), (2, 7)].into(),
+            stp_action: StpAction::CancelResting,
+            ..Default::default()
+        });
+        lob.submit_order(1, 100, 4.0, OrderSide::Buy).unwrap();
+        let post_only = || {
+            OrderRequest::new(2, 10, 4.0, OrderSide::Sell)
+                .with_flags(OrderFlags::POST_ONLY | OrderFlags::ANTI_INTERNALIZE)
+        };
+        // only the own group's order crosses, it sits out matching so the order may rest
+        let order_id = lob.place_order(post_only()).unwrap().order_id;
+        assert_eq!(lob.get_order(order_id).map(|order| order.amount), Some(10));
+        assert_eq!(lob.get_order(0), None);
+
+        lob.cancel_order(order_id);
+        lob.submit_order(1, 100, 4.0, OrderSide::Buy).unwrap();
+        lob.submit_order(3, 100, 4.0, OrderSide::Buy).unwrap();
+        assert_eq!(lob.place_order(post_only()), Err(Error::PostOnlyCrosses));
+        // the withdrawn group order is back at its priority
+        let fills = lob.submit_order(4, 10, 4.0, OrderSide::Sell).unwrap();
+        assert_eq!(fills[0].trader, 1);
+    }
+
+    #[test]
+    fn amend_keeps_flags() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 4.0, OrderSide::Buy).unwrap();
+        let request =
+            OrderRequest::new(2, 10, 5.0, OrderSide::Sell).with_flags(OrderFlags::POST_ONLY);
+        let order_id = lob.place_order(request).unwrap().order_id;
+
+        assert_eq!(
+            lob.amend_order(order_id, 10, 4.0),
+            Err(Error::PostOnlyCrosses)
+        );
+        assert_eq!(lob.get_order(order_id).map(|order| order.price), Some(5.0));
+
+        let order_id = lob.amend_order(order_id, 20, 6.0).unwrap().order_id;
+        assert_eq!(
+            lob.get_order(order_id).map(|order| order.flags),
+            Some(OrderFlags::POST_ONLY)
+        );
+    }
+
+    #[test]
+    fn order_flags() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 4.0, OrderSide::Buy).unwrap();
+
+        let post_only = |price| {
+            OrderRequest::new(2, 10, price, OrderSide::Sell).with_flags(OrderFlags::POST_ONLY)
+        };
+        assert_eq!(lob.place_order(post_only(4.0)), Err(Error::PostOnlyCrosses));
+        let hidden = OrderRequest::new(2, 10, 5.0, OrderSide::Sell)
+            .with_flags(OrderFlags::HIDDEN | OrderFlags::ANTI_INTERNALIZE);
+        assert_eq!(
+            lob.place_order(hidden),
+            Err(Error::UnsupportedFlags(OrderFlags::HIDDEN))
+        );
+
+        // flags are carried on the resting order and its fills
+        let order_id = lob.place_order(post_only(5.0)).unwrap().order_id;
+        assert_eq!(
+            lob.get_order(order_id).unwrap().flags,
+            OrderFlags::POST_ONLY
+        );
+        let fills = lob.submit_order(3, 10, 5.0, OrderSide::Buy).unwrap();
+        assert_eq!(fills[0].flags.order, OrderFlags::POST_ONLY);
+        assert!(fills[1].flags.order.is_empty());
+    }
+
+    #[test]
+    fn trader_order_index() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 4.0, OrderSide::Buy).unwrap();
+        lob.submit_order(2, 100, 5.0, OrderSide::Sell).unwrap();
+        lob.submit_order(1, 50, 6.0, OrderSide::Sell).unwrap();
+        lob.submit_order(1, 10, 3.0, OrderSide::Buy).unwrap();
+
+        let ids = |lob: &Market, trader_id| -> Vec<u64> {
+            lob.orders_for(trader_id).iter().map(|o| o.nonce).collect()
+        };
+        assert_eq!(ids(&lob, 1), vec![0, 3, 2]);
+
+        // filled orders leave the index, partially filled ones stay
+        lob.submit_order(3, 100, 4.0, OrderSide::Sell).unwrap();
+        assert_eq!(ids(&lob, 1), vec![3, 2]);
+        lob.submit_order(4, 120, 6.0, OrderSide::Buy).unwrap();
+        assert!(ids(&lob, 2).is_empty());
+        assert_eq!(lob.orders_for(1)[1].amount, 30);
+
+        let cancelled: Vec<_> = lob.cancel_all(1).iter().map(|o| o.nonce).collect();
+        assert_eq!(cancelled, vec![3, 2]);
+        assert!(lob.orders_for(1).is_empty());
+        assert!(lob.buys.is_empty());
+    }
+
+    #[test]
+    fn amend_resting_order() {
+        let mut lob = Market::default();
+
+        for trader_id in 1..=2 {
+            assert_eq!(
+                lob.submit_order(trader_id, 100, 4.0, OrderSide::Buy),
+                Ok(vec![])
+            );
+        }
+
+        // reducing keeps priority and id
+        let result = lob.amend_order(0, 60, 4.0).unwrap();
+        assert_eq!(result.order_id, 0);
+        assert_eq!(lob.buys.front().map(|o| o.inner().amount), Some(60));
+
+        // increasing loses priority
+        let result = lob.amend_order(0, 150, 4.0).unwrap();
+        assert_eq!(result.order_id, 2);
+        assert_eq!(lob.buys.front().map(|o| o.inner().nonce), Some(1));
+
+        // repricing can match
+        assert_eq!(lob.submit_order(3, 10, 5.0, OrderSide::Sell), Ok(vec![]));
+        let result = lob.amend_order(1, 100, 5.0).unwrap();
+        assert_eq!(
+            result.fills,
+            vec![
+                Fill::new(10, 5.0, OrderSide::Sell, 3, 2, 3).with_liquidity(Liquidity::Added),
+                Fill::new(10, 5.0, OrderSide::Buy, 2, 3, 4),
+            ]
+        );
+
+        assert_eq!(lob.amend_order(9, 1, 1.0), Err(Error::OrderNotFound(9)));
+        assert_eq!(lob.amend_order(2, 0, 1.0), Err(Error::ZeroAmount));
+    }
+
     #[test]
     fn unfilled_buy() {
         let mut lob = Market::default();
@@ -343,6 +891,7 @@ pub mod tests {
                     price: 4.0,
                     amount: 100,
                     nonce: 1,
+                    ..Default::default()
                 }
                 .into()
             )
@@ -369,6 +918,7 @@ pub mod tests {
                     price: 5.0,
                     amount: 100,
                     nonce: 1,
+                    ..Default::default()
                 }
                 .into()
             )
@@ -1,16 +1,41 @@
 //! Order types
-use std::cmp::Ordering;
+use std::{cmp::Ordering, fmt};
+
+/// Unique order identifier, assigned from the market's order nonce
+pub type OrderId = u64;
 
 /// Common API for limit orders
 pub trait Order: Clone + Ord {
     type Opposite: Order;
+    /// The underlying limit order
+    fn inner(&self) -> &LimitOrder;
     /// Whether the order's value is zero
     fn is_zero(&self) -> bool;
+    /// Whether the prices of this order and `other` cross so they can trade
+    fn crosses(&self, other: &Self::Opposite) -> bool;
+    /// Fill this order with `other` without checking prices cross, passing this order's fill
+    /// then `other`'s to `sink`
+    fn fill_into(&mut self, other: &mut Self::Opposite, sink: &mut impl FnMut(Fill));
     /// Try fill this order with `other`
-    fn try_fill(&mut self, other: &mut Self::Opposite) -> Option<(Fill, Fill)>;
+    fn try_fill(&mut self, other: &mut Self::Opposite) -> Option<(Fill, Fill)> {
+        if !self.crosses(other) {
+            return None;
+        }
+        let (mut fill_0, mut fill_1) = (None, None);
+        self.fill_into(other, &mut |fill| match fill_0 {
+            None => fill_0 = Some(fill),
+            Some(_) => fill_1 = Some(fill),
+        });
+        fill_0.zip(fill_1)
+    }
 }
 
 #[derive(PartialEq, PartialOrd, Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum OrderSide {
     Buy,
     Sell,
@@ -23,26 +48,213 @@ impl OrderSide {
             Self::Sell => Self::Buy,
         }
     }
+    /// Lowercase name of the side e.g. "buy"
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Buy => "buy",
+            Self::Sell => "sell",
+        }
+    }
+}
+
+/// Set of boolean order behaviors, carried on the order for its lifetime
+///
+/// Flags have a text form of their names separated by spaces, e.g. `post-only hidden`
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub struct OrderFlags(u16);
+
+impl OrderFlags {
+    /// Reject the order rather than trade on entry
+    pub const POST_ONLY: Self = Self(1);
+    /// Rest without being displayed in depth
+    pub const HIDDEN: Self = Self(1 << 1);
+    /// Only trade to reduce the trader's position
+    pub const REDUCE_ONLY: Self = Self(1 << 2);
+    /// Fill the whole amount at once or not at all
+    pub const ALL_OR_NONE: Self = Self(1 << 3);
+    /// Only match in auctions
+    pub const AUCTION_ONLY: Self = Self(1 << 4);
+    /// Skip resting orders of the same STP group instead of trading with them
+    pub const ANTI_INTERNALIZE: Self = Self(1 << 5);
+    /// Expire at the end of the trading day
+    pub const DAY: Self = Self(1 << 6);
+
+    const NAMES: [(Self, &'static str); 7] = [
+        (Self::POST_ONLY, "post-only"),
+        (Self::HIDDEN, "hidden"),
+        (Self::REDUCE_ONLY, "reduce-only"),
+        (Self::ALL_OR_NONE, "aon"),
+        (Self::AUCTION_ONLY, "auction-only"),
+        (Self::ANTI_INTERNALIZE, "anti-internalize"),
+        (Self::DAY, "day"),
+    ];
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+    /// Whether every flag of `other` is set
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+    /// The flag with text form `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find_map(|(flag, flag_name)| (*flag_name == name).then_some(*flag))
+    }
+    /// The flags with text forms `names`
+    pub fn from_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        names.into_iter().try_fold(Self::empty(), |flags, name| {
+            Some(flags | Self::from_name(name)?)
+        })
+    }
+}
+
+impl std::ops::BitOr for OrderFlags {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self {
+        self.union(other)
+    }
+}
+
+impl std::ops::BitAnd for OrderFlags {
+    type Output = Self;
+    fn bitand(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+impl std::ops::Not for OrderFlags {
+    type Output = Self;
+    fn not(self) -> Self {
+        Self(!self.0)
+    }
+}
+
+impl fmt::Display for OrderFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| name);
+        if let Some(name) = names.next() {
+            write!(f, "{name}")?;
+        }
+        names.try_for_each(|name| write!(f, " {name}"))
+    }
+}
+
+/// A request to place a limit order
+#[derive(PartialEq, Clone, Debug)]
+pub struct OrderRequest {
+    pub trader_id: u32,
+    pub amount: u32,
+    pub price: f32,
+    pub side: OrderSide,
+    pub flags: OrderFlags,
+}
+
+impl OrderRequest {
+    pub fn new(trader_id: u32, amount: u32, price: f32, side: OrderSide) -> Self {
+        OrderRequest {
+            trader_id,
+            amount,
+            price,
+            side,
+            flags: OrderFlags::empty(),
+        }
+    }
+    /// Add `flags` to the order
+    pub fn with_flags(self, flags: OrderFlags) -> Self {
+        OrderRequest {
+            flags: self.flags | flags,
+            ..self
+        }
+    }
+    /// Only match against resting orders of other STP groups
+    pub fn with_anti_internalize(self) -> Self {
+        self.with_flags(OrderFlags::ANTI_INTERNALIZE)
+    }
+}
+
+/// Whether a fill's order provided or took liquidity
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum Liquidity {
+    /// The order was resting on the book
+    Added,
+    /// The order was incoming
+    #[default]
+    Removed,
+}
+
+/// How an execution came about, for settlement and fee purposes
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct FillFlags {
+    pub liquidity: Liquidity,
+    /// Executed in an auction
+    pub auction: bool,
+    /// Executed against hidden liquidity
+    pub hidden: bool,
+    /// Part of a self-trade-prevention decrement rather than a trade
+    pub stp_decrement: bool,
+    /// Flags of the filled order
+    pub order: OrderFlags,
 }
 
 // An event denoting a matched order
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Fill {
     pub side: OrderSide,
     pub amount: u32,
     pub price: f32,
     pub trader: u32,
     pub counter_party: u32,
+    /// Id of the order belonging to `trader`
+    pub order_id: OrderId,
+    pub flags: FillFlags,
 }
 
 impl Fill {
-    pub fn new(amount: u32, price: f32, side: OrderSide, trader: u32, counter_party: u32) -> Self {
+    pub fn new(
+        amount: u32,
+        price: f32,
+        side: OrderSide,
+        trader: u32,
+        counter_party: u32,
+        order_id: OrderId,
+    ) -> Self {
         Fill {
             amount,
             price,
             side,
             trader,
             counter_party,
+            order_id,
+            flags: FillFlags::default(),
+        }
+    }
+    pub fn with_liquidity(self, liquidity: Liquidity) -> Self {
+        Fill {
+            flags: FillFlags {
+                liquidity,
+                ..self.flags
+            },
+            ..self
         }
     }
 }
@@ -54,6 +266,7 @@ pub struct LimitOrder {
     pub nonce: u64,
     pub amount: u32,
     pub trader_id: u32,
+    pub flags: OrderFlags,
 }
 #[derive(PartialEq, Clone, Debug, Default)]
 pub struct BuyLimitOrder(LimitOrder);
@@ -75,100 +288,104 @@ impl From<LimitOrder> for SellLimitOrder {
 
 impl Order for BuyLimitOrder {
     type Opposite = SellLimitOrder;
+    fn inner(&self) -> &LimitOrder {
+        &self.0
+    }
     #[inline(always)]
     fn is_zero(&self) -> bool {
         self.0.amount == 0
     }
-    fn try_fill(&mut self, other: &mut Self::Opposite) -> Option<(Fill, Fill)> {
-        if self.0.price >= other.0.price {
-            self.0.try_fill(&mut other.0, OrderSide::Buy)
-        } else {
-            None
-        }
+    #[inline(always)]
+    fn crosses(&self, other: &Self::Opposite) -> bool {
+        self.0.price >= other.0.price
+    }
+    #[inline]
+    fn fill_into(&mut self, other: &mut Self::Opposite, sink: &mut impl FnMut(Fill)) {
+        self.0.fill_into(&mut other.0, OrderSide::Buy, sink)
     }
 }
 
 impl Order for SellLimitOrder {
     type Opposite = BuyLimitOrder;
+    fn inner(&self) -> &LimitOrder {
+        &self.0
+    }
+    #[inline(always)]
     fn is_zero(&self) -> bool {
         self.0.amount == 0
     }
-    fn try_fill(&mut self, other: &mut Self::Opposite) -> Option<(Fill, Fill)> {
-        if self.0.price <= other.0.price {
-            self.0.try_fill(&mut other.0, OrderSide::Sell)
-        } else {
-            None
-        }
+    #[inline(always)]
+    fn crosses(&self, other: &Self::Opposite) -> bool {
+        self.0.price <= other.0.price
+    }
+    #[inline]
+    fn fill_into(&mut self, other: &mut Self::Opposite, sink: &mut impl FnMut(Fill)) {
+        self.0.fill_into(&mut other.0, OrderSide::Sell, sink)
     }
 }
 
 impl LimitOrder {
-    /// Try fill `self` with `other`, returns the resulting `Fill` events if any
-    fn try_fill(&mut self, other: &mut LimitOrder, side: OrderSide) -> Option<(Fill, Fill)> {
-        let fill_amount = match self.amount.cmp(&other.amount) {
-            Ordering::Equal | Ordering::Less => {
-                let amount = self.amount;
-                other.amount -= amount;
-                self.amount = 0;
-                amount
-            }
-            Ordering::Greater => {
-                let amount = other.amount;
-                self.amount -= amount;
-                other.amount = 0;
-                amount
-            }
-        };
-
-        Some((
-            Fill::new(
-                fill_amount,
-                self.price,
-                side.clone(),
-                self.trader_id,
-                other.trader_id,
-            ),
-            Fill::new(
-                fill_amount,
-                self.price,
-                side.opposite(),
-                other.trader_id,
-                self.trader_id,
-            ),
-        ))
+    /// Fill `self`, resting on `side`, with `other` passing the resulting `Fill` events to `sink`
+    #[inline]
+    fn fill_into(&mut self, other: &mut LimitOrder, side: OrderSide, sink: &mut impl FnMut(Fill)) {
+        // the smaller order is consumed, computed without branching on which
+        let fill_amount = self.amount.min(other.amount);
+        self.amount -= fill_amount;
+        other.amount -= fill_amount;
+
+        let other_side = side.opposite();
+        let mut resting = Fill::new(
+            fill_amount,
+            self.price,
+            side,
+            self.trader_id,
+            other.trader_id,
+            self.nonce,
+        )
+        .with_liquidity(Liquidity::Added);
+        resting.flags.order = self.flags;
+        sink(resting);
+        let mut incoming = Fill::new(
+            fill_amount,
+            self.price,
+            other_side,
+            other.trader_id,
+            self.trader_id,
+            other.nonce,
+        );
+        incoming.flags.order = other.flags;
+        sink(incoming);
     }
 }
 
 impl PartialOrd for BuyLimitOrder {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self.0.price.total_cmp(&other.0.price) {
-            Ordering::Equal => self.0.nonce.partial_cmp(&other.0.nonce),
-            Ordering::Greater => Some(Ordering::Less),
-            Ordering::Less => Some(Ordering::Greater),
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for BuyLimitOrder {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other)
-            .expect("only valid floats are given")
+        match self.0.price.total_cmp(&other.0.price) {
+            Ordering::Equal => self.0.nonce.cmp(&other.0.nonce),
+            Ordering::Greater => Ordering::Less,
+            Ordering::Less => Ordering::Greater,
+        }
     }
 }
 
 impl PartialOrd for SellLimitOrder {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self.0.price.total_cmp(&other.0.price) {
-            Ordering::Equal => self.0.nonce.partial_cmp(&other.0.nonce),
-            order => Some(order),
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for SellLimitOrder {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other)
-            .expect("only valid floats are given")
+        match self.0.price.total_cmp(&other.0.price) {
+            Ordering::Equal => self.0.nonce.cmp(&other.0.nonce),
+            order => order,
+        }
     }
 }
 
@@ -0,0 +1,858 @@
+//! Market configuration and matching policies
+//!
+//! The matching policy decides how an incoming order is allocated between resting orders at the
+//! same price, price priority always applies first.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom};
+
+use crate::{
+    analytics::VpinConfig, book::BookBackend, compaction::Compaction, funding::Funding,
+    halt::HaltPolicy, Fill, LimitOrder, Market, Order, OrderId, OrderSide,
+};
+
+/// Allocation of incoming orders within a price level
+#[derive(PartialEq, Clone, Debug, Default)]
+pub enum MatchingPolicy {
+    /// First come first served
+    #[default]
+    PriceTime,
+    /// Larger resting orders first, earlier orders first between equal sizes
+    SizeTime,
+    /// Share each level in proportion to resting size, lots left over by rounding shares down
+    /// are allocated by `residual`
+    ProRata {
+        /// Fraction of the incoming amount first allocated to the order which established the
+        /// best price (top of book priority), zero to disable
+        top_allocation: f32,
+        residual: ResidualAllocation,
+    },
+}
+
+impl MatchingPolicy {
+    /// Whether matching draws from the market's tiebreak RNG regardless of seeding
+    pub(crate) fn is_random(&self) -> bool {
+        matches!(
+            self,
+            MatchingPolicy::ProRata {
+                residual: ResidualAllocation::Random,
+                ..
+            }
+        )
+    }
+}
+
+/// Rule pricing a trade between a resting and an incoming order
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum TradePrice {
+    /// The resting order's price, the incoming order receives any price improvement
+    #[default]
+    Resting,
+    /// The incoming order's price, the resting order receives any price improvement
+    Aggressor,
+    /// Midway between the two prices, splitting the improvement
+    Midpoint,
+}
+
+impl TradePrice {
+    pub fn price(&self, resting: f32, aggressor: f32) -> f32 {
+        match self {
+            TradePrice::Resting => resting,
+            TradePrice::Aggressor => aggressor,
+            TradePrice::Midpoint => (resting + aggressor) / 2.0,
+        }
+    }
+}
+
+/// Resolution of an anti-internalizing order whose remainder would rest across resting orders
+/// of its own STP group, which sat out matching
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum StpAction {
+    /// Cancel the incoming order's remainder
+    #[default]
+    CancelIncoming,
+    /// Cancel the crossed resting orders of the group
+    CancelResting,
+}
+
+/// Handling of the remainder of an order which exhausted its `MatchBudget`
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum BudgetRemainder {
+    /// Rest at the price of the last level swept, or cancel if orders remain at that level
+    #[default]
+    Rest,
+    /// Cancel the remainder
+    Cancel,
+}
+
+/// Limits on the matching work of a single incoming order, unlimited by default
+///
+/// Budgets of zero allow one fill. Fills are counted in time priority, size time and pro-rata
+/// levels may spread the budgeted amount over more orders.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct MatchBudget {
+    /// Maximum resting orders filled
+    pub max_fills: Option<usize>,
+    /// Maximum price levels swept
+    pub max_levels: Option<usize>,
+    pub remainder: BudgetRemainder,
+}
+
+/// Where an incoming order's budget runs out
+#[derive(PartialEq, Debug)]
+pub(crate) struct BudgetLimit {
+    /// Price of the last level within budget
+    pub price: f32,
+    /// Amount within budget if it ran out part way through that level
+    pub amount: Option<u32>,
+}
+
+impl MatchBudget {
+    /// Find where the budget runs out sweeping `resting` orders in priority order with an
+    /// order for `amount`, `None` if it does not before the order fills or they stop crossing
+    pub(crate) fn limit<'a>(
+        &self,
+        resting: impl Iterator<Item = &'a LimitOrder>,
+        amount: u32,
+        crosses: impl Fn(f32) -> bool,
+    ) -> Option<BudgetLimit> {
+        if self.max_fills.is_none() && self.max_levels.is_none() {
+            return None;
+        }
+        let (mut fills, mut levels, mut swept) = (0, 0, 0_u64);
+        let mut last = None;
+        for order in resting.take_while(|order| crosses(order.price)) {
+            if swept >= amount as u64 {
+                return None;
+            }
+            let new_level = last != Some(order.price);
+            let out_of_levels =
+                new_level && self.max_levels.is_some_and(|max| levels >= max.max(1));
+            let out_of_fills = self.max_fills.is_some_and(|max| fills >= max.max(1));
+            if out_of_levels || out_of_fills {
+                return Some(BudgetLimit {
+                    price: last.expect("budgets allow one fill"),
+                    amount: (!new_level).then_some(swept as u32),
+                });
+            }
+            if new_level {
+                levels += 1;
+                last = Some(order.price);
+            }
+            fills += 1;
+            swept += order.amount as u64;
+        }
+        None
+    }
+}
+
+/// Rule allocating the residual lots of a pro-rata level, one lot per order
+#[derive(PartialEq, Clone, Debug, Default)]
+pub enum ResidualAllocation {
+    /// Earliest orders first
+    #[default]
+    Fifo,
+    /// Orders whose share lost the largest fraction to rounding first, earliest between equals
+    LargestRemainder,
+    /// Random orders drawn from the market RNG seeded by `MarketConfig::tiebreak_seed`, or zero
+    /// if unset
+    Random,
+}
+
+/// Rounding rule for quote currency amounts
+#[derive(PartialEq, Clone, Debug, Default)]
+pub enum RoundingMode {
+    /// Round half to even, banker's rounding
+    #[default]
+    HalfEven,
+    /// Round half away from zero
+    HalfUp,
+    /// Round towards negative infinity
+    Floor,
+}
+
+/// Precision quote currency amounts are rounded to
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Rounding {
+    /// Decimal places of the quote currency
+    pub decimals: u32,
+    pub mode: RoundingMode,
+}
+
+impl Rounding {
+    pub fn round(&self, value: f64) -> f64 {
+        let scale = 10_f64.powi(self.decimals as i32);
+        let scaled = value * scale;
+        let rounded = match self.mode {
+            RoundingMode::HalfEven => scaled.round_ties_even(),
+            RoundingMode::HalfUp => scaled.round(),
+            RoundingMode::Floor => scaled.floor(),
+        };
+        rounded / scale
+    }
+}
+
+/// Market wide settings
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct MarketConfig {
+    pub matching: MatchingPolicy,
+    /// Self trade prevention group of each trader, traders not listed form their own group
+    pub stp_groups: BTreeMap<u32, u32>,
+    /// Resolution of anti-internalizing orders which would cross their own group
+    pub stp_action: StpAction,
+    /// Maximum resting orders of a trader at one price level
+    pub max_orders_per_level: Option<usize>,
+    /// Maximum resting orders of a trader across both sides of the book
+    pub max_orders_per_trader: Option<usize>,
+    /// Accept orders with negative prices, e.g. for spreads
+    pub allow_negative_prices: bool,
+    /// Rounding of trade notionals and fees, unrounded if `None`
+    pub quote_rounding: Option<Rounding>,
+    /// Volume bucketing of the VPIN order flow toxicity metric
+    pub vpin: VpinConfig,
+    /// Periodic funding settlement, disabled if `None`
+    pub funding: Option<Funding>,
+    /// Seed of the market's RNG, breaking size time ties randomly when set and drawing
+    /// `ResidualAllocation::Random` pro-rata residuals
+    ///
+    /// Runs with the same seed and orders produce identical fills
+    pub tiebreak_seed: Option<u64>,
+    /// Automatic book compaction, only by `Market::compact` if `None`
+    pub compaction: Option<Compaction>,
+    /// Handling of orders submitted while halted
+    pub halt: HaltPolicy,
+    /// Price of trades between resting and incoming orders
+    pub trade_price: TradePrice,
+    /// Matching work allowed per incoming order
+    pub budget: MatchBudget,
+}
+
+/// An invalid `MarketConfig` setting
+#[derive(PartialEq, Clone, Debug)]
+pub enum ConfigError {
+    /// Pro-rata top allocation outside 0.0..=1.0
+    TopAllocation(f32),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TopAllocation(fraction) => {
+                write!(f, "top allocation {fraction} is outside 0.0..=1.0")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl MarketConfig {
+    /// Check settings are within their valid ranges
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let MatchingPolicy::ProRata { top_allocation, .. } = self.matching {
+            if !(0.0..=1.0).contains(&top_allocation) {
+                return Err(ConfigError::TopAllocation(top_allocation));
+            }
+        }
+        Ok(())
+    }
+    /// Self trade prevention group of `trader_id`
+    pub fn stp_group(&self, trader_id: u32) -> u32 {
+        self.stp_groups
+            .get(&trader_id)
+            .copied()
+            .unwrap_or(trader_id)
+    }
+    /// Round a quote currency amount by `quote_rounding`
+    pub fn round_quote(&self, value: f64) -> f64 {
+        self.quote_rounding
+            .as_ref()
+            .map_or(value, |rounding| rounding.round(value))
+    }
+}
+
+/// Orders which established the current best price of each side
+#[derive(Default, Debug)]
+pub(crate) struct TopOrders {
+    pub buy: Option<OrderId>,
+    pub sell: Option<OrderId>,
+}
+
+/// Match `order` against `book` allocating within each price level by `policy`
+/// `top` is the order which established the book's best price, `tiebreak` breaks allocation
+/// ties randomly if given
+/// Returning fills and remaining unfilled order if any
+pub(crate) fn submit_order<'a, T: Order + From<LimitOrder>, B: BookBackend<T>>(
+    book: &mut B,
+    order: &'a mut T::Opposite,
+    policy: &MatchingPolicy,
+    top: Option<OrderId>,
+    tiebreak: Option<&mut StdRng>,
+) -> (Vec<Fill>, Option<&'a T::Opposite>) {
+    let fills = match policy {
+        MatchingPolicy::PriceTime => return book.submit_order(order),
+        MatchingPolicy::SizeTime => size_time(book, order, tiebreak),
+        MatchingPolicy::ProRata {
+            top_allocation,
+            residual,
+        } => pro_rata(book, order, *top_allocation, residual, top, tiebreak),
+    };
+    if order.is_zero() {
+        (fills, None)
+    } else {
+        (fills, Some(order))
+    }
+}
+
+fn size_time<T: Order, B: BookBackend<T>>(
+    book: &mut B,
+    order: &mut T::Opposite,
+    mut tiebreak: Option<&mut StdRng>,
+) -> Vec<Fill> {
+    let mut fills = Vec::<Fill>::default();
+    while !order.is_zero() {
+        let Some(price) = book.front().map(|best| best.inner().price) else {
+            break;
+        };
+        let level: Vec<&LimitOrder> = book
+            .iter()
+            .map(Order::inner)
+            .take_while(|resting| resting.price == price)
+            .collect();
+        let largest = level
+            .iter()
+            .map(|resting| resting.amount)
+            .max()
+            .expect("level is not empty");
+        // largest orders in time priority
+        let tied: Vec<OrderId> = level
+            .iter()
+            .filter(|resting| resting.amount == largest)
+            .map(|resting| resting.nonce)
+            .collect();
+        let next = match tiebreak.as_deref_mut() {
+            Some(rng) => *tied.choose(rng).expect("level is not empty"),
+            None => tied[0],
+        };
+        let mut resting_order = book.remove(next).expect("order is resting");
+        let filled = resting_order.try_fill(order);
+        if !resting_order.is_zero() {
+            book.insert_order(&resting_order)
+                .expect("orderbook has capacity");
+        }
+        let Some((fill_0, fill_1)) = filled else {
+            break;
+        };
+        fills.push(fill_0);
+        fills.push(fill_1);
+    }
+    fills
+}
+
+fn pro_rata<T: Order + From<LimitOrder>, B: BookBackend<T>>(
+    book: &mut B,
+    order: &mut T::Opposite,
+    top_allocation: f32,
+    residual_allocation: &ResidualAllocation,
+    top: Option<OrderId>,
+    mut tiebreak: Option<&mut StdRng>,
+) -> Vec<Fill> {
+    let mut fills = Vec::<Fill>::default();
+    let mut first_level = true;
+    while !order.is_zero() {
+        let Some(best) = book.front() else {
+            break;
+        };
+        if best.clone().try_fill(&mut order.clone()).is_none() {
+            break;
+        }
+        let price = best.inner().price;
+        // (order id, resting amount, allocated amount) in time priority
+        let mut level: Vec<(OrderId, u32, u32)> = book
+            .iter()
+            .map(Order::inner)
+            .take_while(|resting| resting.price == price)
+            .map(|resting| (resting.nonce, resting.amount, 0))
+            .collect();
+        let mut remaining = order.inner().amount;
+
+        // top of book priority only applies on the best level
+        if let Some(top) = level
+            .iter_mut()
+            .find(|(order_id, _, _)| first_level && Some(*order_id) == top)
+        {
+            // f32 rounding can take the allocation of large amounts above `remaining`
+            let allocation = (remaining as f32 * top_allocation).ceil() as u32;
+            top.2 = allocation.min(top.1).min(remaining);
+            remaining -= top.2;
+        }
+        first_level = false;
+
+        let open: u64 = level
+            .iter()
+            .map(|(_, size, alloc)| (size - alloc) as u64)
+            .sum();
+        if remaining as u64 >= open {
+            level.iter_mut().for_each(|entry| entry.2 = entry.1);
+        } else {
+            let mut allocated = 0;
+            // fraction of each share lost rounding down, in units of 1/open
+            let mut remainders = Vec::with_capacity(level.len());
+            for (_, size, alloc) in level.iter_mut() {
+                let exact = remaining as u64 * (*size - *alloc) as u64;
+                let share = (exact / open) as u32;
+                remainders.push(exact % open);
+                *alloc += share;
+                allocated += share;
+            }
+            // fewer residual lots than orders sharing the level, one pass suffices
+            let residual = remaining - allocated;
+            let mut open: Vec<usize> = (0..level.len())
+                .filter(|i| level[*i].2 < level[*i].1)
+                .collect();
+            match residual_allocation {
+                ResidualAllocation::Fifo => (),
+                ResidualAllocation::LargestRemainder => {
+                    open.sort_by(|a, b| remainders[*b].cmp(&remainders[*a]))
+                }
+                ResidualAllocation::Random => {
+                    open.shuffle(tiebreak.as_deref_mut().expect("market has a tiebreak rng"))
+                }
+            }
+            for i in open.into_iter().take(residual as usize) {
+                level[i].2 += 1;
+            }
+        }
+
+        for (order_id, _, amount) in level.into_iter().filter(|entry| entry.2 > 0) {
+            let resting = book
+                .remove(order_id)
+                .expect("order is resting")
+                .inner()
+                .clone();
+            let mut part: T = LimitOrder {
+                amount,
+                ..resting.clone()
+            }
+            .into();
+            let (fill_0, fill_1) = part.try_fill(order).expect("level crosses");
+            fills.push(fill_0);
+            fills.push(fill_1);
+            if resting.amount > amount {
+                let rest = LimitOrder {
+                    amount: resting.amount - amount,
+                    ..resting
+                };
+                book.insert_order(&rest.into())
+                    .expect("orderbook has capacity");
+            }
+        }
+    }
+    fills
+}
+
+impl Market {
+    /// Remove resting `side` orders in the STP group of `trader_id`
+    pub(crate) fn withdraw_group(&mut self, side: &OrderSide, trader_id: u32) -> Vec<LimitOrder> {
+        let group = self.config.stp_group(trader_id);
+        let mut members: BTreeSet<u32> = self
+            .config
+            .stp_groups
+            .iter()
+            .filter_map(|(member, member_group)| (*member_group == group).then_some(*member))
+            .collect();
+        if self.config.stp_group(group) == group {
+            members.insert(group);
+        }
+        let order_ids: Vec<OrderId> = members
+            .into_iter()
+            .flat_map(|member| match side {
+                OrderSide::Buy => self.buys.order_ids_of(member).collect::<Vec<_>>(),
+                OrderSide::Sell => self.sells.order_ids_of(member).collect(),
+            })
+            .map(|(order_id, _)| order_id)
+            .collect();
+        order_ids
+            .into_iter()
+            .filter_map(|order_id| match side {
+                OrderSide::Buy => self.buys.remove(order_id).map(|o| o.inner().clone()),
+                OrderSide::Sell => self.sells.remove(order_id).map(|o| o.inner().clone()),
+            })
+            .collect()
+    }
+    /// Return withdrawn orders to the book at their original priority
+    pub(crate) fn restore_group(&mut self, side: &OrderSide, orders: Vec<LimitOrder>) {
+        for order in orders {
+            match side {
+                OrderSide::Buy => self.buys.insert_order(&order.into()),
+                OrderSide::Sell => self.sells.insert_order(&order.into()),
+            }
+            .expect("orderbook has capacity");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BudgetRemainder, ConfigError, MarketConfig, MatchBudget, MatchingPolicy,
+        ResidualAllocation, Rounding, RoundingMode, StpAction, TradePrice,
+    };
+    use crate::{Error, Market, OrderRequest, OrderSide, LOB};
+
+    #[test]
+    fn size_time_priority() {
+        let mut lob = Market::new(MarketConfig {
+            matching: MatchingPolicy::SizeTime,
+            ..Default::default()
+        });
+        for amount in [10, 30, 30] {
+            lob.submit_order(1, amount, 2.0, OrderSide::Sell).unwrap();
+        }
+        lob.submit_order(1, 50, 1.0, OrderSide::Sell).unwrap();
+
+        let fills = lob.submit_order(2, 35, 2.0, OrderSide::Buy).unwrap();
+        let resting: Vec<_> = fills
+            .iter()
+            .filter(|fill| fill.side == OrderSide::Sell)
+            .map(|fill| (fill.order_id, fill.amount, fill.price))
+            .collect();
+        // the better priced order fills first regardless of size
+        assert_eq!(resting, vec![(3, 35, 1.0)]);
+
+        let fills = lob.submit_order(2, 40, 2.0, OrderSide::Buy).unwrap();
+        let resting: Vec<_> = fills
+            .iter()
+            .filter(|fill| fill.side == OrderSide::Sell)
+            .map(|fill| (fill.order_id, fill.amount))
+            .collect();
+        assert_eq!(resting, vec![(3, 15), (1, 25)]);
+        assert_eq!(lob.get_order(2).map(|order| order.amount), Some(30));
+    }
+
+    fn allocations(lob: &mut Market, amount: u32) -> Vec<(u64, u32)> {
+        lob.submit_order(9, amount, 2.0, OrderSide::Buy)
+            .unwrap()
+            .iter()
+            .filter(|fill| fill.side == OrderSide::Sell)
+            .map(|fill| (fill.order_id, fill.amount))
+            .collect()
+    }
+
+    #[test]
+    fn pro_rata_with_top_priority() {
+        let config = |top_allocation| MarketConfig {
+            matching: MatchingPolicy::ProRata {
+                top_allocation,
+                residual: ResidualAllocation::Fifo,
+            },
+            ..Default::default()
+        };
+        let mut lob = Market::new(config(0.0));
+        lob.submit_order(1, 100, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 300, 2.0, OrderSide::Sell).unwrap();
+        assert_eq!(allocations(&mut lob, 200), vec![(0, 50), (1, 150)]);
+
+        let mut lob = Market::new(config(0.4));
+        lob.submit_order(1, 100, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 300, 2.0, OrderSide::Sell).unwrap();
+        // 80 to the top order then 120 shared 7.5:112.5, the residual lot going to the earliest
+        assert_eq!(allocations(&mut lob, 200), vec![(0, 88), (1, 112)]);
+        assert_eq!(lob.get_order(0).map(|order| order.amount), Some(12));
+        // sweeping the level fills every order completely
+        assert_eq!(allocations(&mut lob, 500), vec![(0, 12), (1, 188)]);
+    }
+
+    fn pro_rata(residual: ResidualAllocation, tiebreak_seed: Option<u64>) -> Market {
+        Market::new(MarketConfig {
+            matching: MatchingPolicy::ProRata {
+                top_allocation: 0.0,
+                residual,
+            },
+            tiebreak_seed,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn residual_allocation_rules() {
+        let run = |residual, sizes: &[u32]| {
+            let mut lob = pro_rata(residual, None);
+            for (trader_id, amount) in sizes.iter().enumerate() {
+                lob.submit_order(trader_id as u32, *amount, 2.0, OrderSide::Sell)
+                    .unwrap();
+            }
+            allocations(&mut lob, 7)
+        };
+        // exact shares 2.1, 0.7 and 4.2 leave one residual lot
+        let sizes = [30, 10, 60];
+        assert_eq!(run(ResidualAllocation::Fifo, &sizes), vec![(0, 3), (2, 4)]);
+        assert_eq!(
+            run(ResidualAllocation::LargestRemainder, &sizes),
+            vec![(0, 2), (1, 1), (2, 4)]
+        );
+    }
+
+    #[test]
+    fn seeded_tiebreak_is_reproducible() {
+        let run = |residual, tiebreak_seed| {
+            let mut lob = pro_rata(residual, tiebreak_seed);
+            for trader_id in 0..10 {
+                lob.submit_order(trader_id, 10, 2.0, OrderSide::Sell)
+                    .unwrap();
+            }
+            // every share rounds down to zero leaving 5 residual lots to tiebreak
+            allocations(&mut lob, 5)
+        };
+        let earliest: Vec<_> = (0..5).map(|order_id| (order_id, 1)).collect();
+        assert_eq!(run(ResidualAllocation::Fifo, Some(42)), earliest);
+
+        let seeded = run(ResidualAllocation::Random, Some(42));
+        assert_eq!(seeded.len(), 5);
+        assert_eq!(run(ResidualAllocation::Random, Some(42)), seeded);
+        assert!((0..10).any(|seed| run(ResidualAllocation::Random, Some(seed)) != earliest));
+        // unseeded draws are seeded with zero
+        assert_eq!(
+            run(ResidualAllocation::Random, None),
+            run(ResidualAllocation::Random, Some(0))
+        );
+    }
+
+    #[test]
+    fn anti_internalize_skips_own_group() {
+        let mut lob = Market::new(MarketConfig {
+            stp_groups: [(1, 7), (2, 7)].into(),
+            ..Default::default()
+        });
+        lob.submit_order(1, 10, 1.0, OrderSide::Sell).unwrap();
+        lob.submit_order(3, 10, 1.5, OrderSide::Sell).unwrap();
+
+        let request = OrderRequest::new(2, 15, 1.5, OrderSide::Buy).with_anti_internalize();
+        let result = lob.place_order(request).unwrap();
+        assert_eq!(result.fills.len(), 2);
+        assert_eq!(result.fills[0].order_id, 1);
+        // the internal order keeps its place, the remainder would cross it so is cancelled
+        assert_eq!(lob.get_order(0).map(|order| order.amount), Some(10));
+        assert_eq!(lob.get_order(2), None);
+        assert_uncrossed(&lob);
+
+        // a remainder not crossing the group rests
+        let request = OrderRequest::new(2, 5, 0.5, OrderSide::Buy).with_anti_internalize();
+        let order_id = lob.place_order(request).unwrap().order_id;
+        assert_eq!(lob.get_order(order_id).map(|order| order.amount), Some(5));
+        assert_uncrossed(&lob);
+
+        let fills = lob.submit_order(2, 5, 1.0, OrderSide::Buy).unwrap();
+        assert_eq!(fills[0].trader, 1);
+    }
+
+    #[test]
+    fn anti_internalize_cancels_resting() {
+        let mut lob = Market::new(MarketConfig {
+            stp_groups: [(1, 7), (2, 7)].into(),
+            stp_action: StpAction::CancelResting,
+            ..Default::default()
+        });
+        lob.submit_order(1, 10, 1.0, OrderSide::Sell).unwrap();
+        lob.submit_order(1, 10, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(3, 10, 1.5, OrderSide::Sell).unwrap();
+
+        let request = OrderRequest::new(2, 15, 1.5, OrderSide::Buy).with_anti_internalize();
+        let order_id = lob.place_order(request).unwrap().order_id;
+        assert_eq!(lob.get_order(order_id).map(|order| order.amount), Some(5));
+        // the crossed group order is cancelled, the one above the remainder is kept
+        assert_eq!(lob.get_order(0), None);
+        assert_eq!(lob.get_order(1).map(|order| order.amount), Some(10));
+        assert_uncrossed(&lob);
+    }
+
+    fn assert_uncrossed(lob: &Market) {
+        let depth = lob.depth(1);
+        if let (Some(bid), Some(ask)) = (depth.bids.first(), depth.asks.first()) {
+            assert!(
+                bid.price < ask.price,
+                "book crossed at {} {}",
+                bid.price,
+                ask.price
+            );
+        }
+    }
+
+    #[test]
+    fn order_caps() {
+        let mut lob = Market::new(MarketConfig {
+            max_orders_per_level: Some(1),
+            max_orders_per_trader: Some(3),
+            ..Default::default()
+        });
+        lob.submit_order(1, 10, 1.0, OrderSide::Buy).unwrap();
+        assert_eq!(
+            lob.submit_order(1, 10, 1.0, OrderSide::Buy),
+            Err(Error::OrderLimit)
+        );
+        lob.submit_order(2, 10, 1.0, OrderSide::Buy).unwrap();
+        lob.submit_order(1, 10, 2.0, OrderSide::Sell).unwrap();
+        lob.submit_order(1, 10, 3.0, OrderSide::Sell).unwrap();
+        assert_eq!(
+            lob.submit_order(1, 10, 4.0, OrderSide::Sell),
+            Err(Error::OrderLimit)
+        );
+        // a rejected amend keeps the original order
+        assert_eq!(lob.amend_order(3, 10, 2.0), Err(Error::OrderLimit));
+        assert!(lob.get_order(3).is_some());
+        lob.cancel_order(0);
+        lob.submit_order(1, 10, 4.0, OrderSide::Sell).unwrap();
+    }
+
+    #[test]
+    fn negative_prices() {
+        let mut lob = Market::default();
+        assert_eq!(
+            lob.submit_order(1, 10, -1.0, OrderSide::Sell),
+            Err(Error::InvalidPrice(-1.0))
+        );
+        assert!(lob.submit_order(1, 10, f32::NAN, OrderSide::Sell).is_err());
+
+        let mut lob = Market::new(MarketConfig {
+            allow_negative_prices: true,
+            ..Default::default()
+        });
+        lob.submit_order(1, 10, 0.5, OrderSide::Sell).unwrap();
+        lob.submit_order(1, 10, -0.0, OrderSide::Sell).unwrap();
+        lob.submit_order(1, 10, 0.0, OrderSide::Sell).unwrap();
+        lob.submit_order(1, 10, -1.5, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 10, -2.0, OrderSide::Buy).unwrap();
+        assert_eq!(lob.depth(usize::MAX).asks.len(), 3);
+
+        // a buy at zero sweeps the negative level then zero, trading at resting prices
+        let fills = lob.submit_order(3, 25, 0.0, OrderSide::Buy).unwrap();
+        let prices: Vec<_> = fills
+            .iter()
+            .filter(|fill| fill.side == OrderSide::Buy)
+            .map(|fill| (fill.amount, fill.price))
+            .collect();
+        assert_eq!(prices, vec![(10, -1.5), (10, 0.0), (5, 0.0)]);
+
+        let fills = lob.submit_order(4, 10, -2.5, OrderSide::Sell).unwrap();
+        assert_eq!(fills[0].price, -2.0);
+        assert!(lob.depth(1).bids.is_empty());
+
+        // a huge price rests next to a negative one on every backend
+        lob.submit_order(5, 10, -1.0, OrderSide::Sell).unwrap();
+        lob.submit_order(5, 10, 3.0e38, OrderSide::Sell).unwrap();
+        let asks = lob.depth(usize::MAX).asks;
+        assert_eq!(asks.first().map(|level| level.price), Some(-1.0));
+        assert_eq!(asks.last().map(|level| level.price), Some(3.0e38));
+    }
+
+    #[test]
+    fn match_budget() {
+        // a buy at 2.0 for `amount` against asks 1x10 2x10 at 1.0, 3x10 at 1.5 and 4x10 at 2.0
+        let sweep = |budget, amount| {
+            let mut lob = Market::new(MarketConfig {
+                budget,
+                ..Default::default()
+            });
+            for (trader, price) in [(1, 1.0), (2, 1.0), (3, 1.5), (4, 2.0)] {
+                lob.submit_order(trader, 10, price, OrderSide::Sell)
+                    .unwrap();
+            }
+            let fills = lob.submit_order(9, amount, 2.0, OrderSide::Buy).unwrap();
+            let bids = lob.depth(1).bids;
+            (
+                fills.len() / 2,
+                bids.first().map(|level| (level.price, level.amount)),
+            )
+        };
+        let levels = |max, remainder| MatchBudget {
+            max_levels: Some(max),
+            remainder,
+            ..Default::default()
+        };
+        let fills = |max| MatchBudget {
+            max_fills: Some(max),
+            ..Default::default()
+        };
+        assert_eq!(sweep(MatchBudget::default(), 50), (4, Some((2.0, 10))));
+        assert_eq!(
+            sweep(levels(2, BudgetRemainder::Rest), 50),
+            (3, Some((1.5, 20)))
+        );
+        assert_eq!(sweep(levels(1, BudgetRemainder::Cancel), 25), (2, None));
+        // orders remain at the last level so the remainder would cross
+        assert_eq!(sweep(fills(1), 15), (1, None));
+        assert_eq!(sweep(fills(2), 25), (2, Some((1.0, 5))));
+        assert_eq!(sweep(fills(2), 20), (2, None));
+    }
+
+    #[test]
+    fn top_allocation_is_bounded() {
+        let pro_rata = |top_allocation| MarketConfig {
+            matching: MatchingPolicy::ProRata {
+                top_allocation,
+                residual: ResidualAllocation::Fifo,
+            },
+            ..Default::default()
+        };
+        for invalid in [1.5, -0.1, f32::NAN] {
+            assert!(matches!(
+                Market::with_config(pro_rata(invalid)),
+                Err(ConfigError::TopAllocation(_))
+            ));
+        }
+
+        // the f32 allocation of amounts near u32::MAX rounds above the incoming amount
+        let mut lob = Market::with_config(pro_rata(1.0)).unwrap();
+        lob.submit_order(1, u32::MAX, 1.0, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 10, 1.0, OrderSide::Sell).unwrap();
+        let fills = lob
+            .submit_order(3, u32::MAX - 5, 1.0, OrderSide::Buy)
+            .unwrap();
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[0].amount, u32::MAX - 5);
+        assert_eq!(lob.get_order(0).map(|order| order.amount), Some(5));
+    }
+
+    #[test]
+    fn trade_price_rules() {
+        // a buy at 2.0 sweeps asks at 1.0 and 1.5
+        let trade_prices = |trade_price| {
+            let mut lob = Market::new(MarketConfig {
+                trade_price,
+                ..Default::default()
+            });
+            lob.submit_order(1, 10, 1.0, OrderSide::Sell).unwrap();
+            lob.submit_order(1, 10, 1.5, OrderSide::Sell).unwrap();
+            let fills = lob.submit_order(2, 20, 2.0, OrderSide::Buy).unwrap();
+            assert!(fills.chunks(2).all(|pair| pair[0].price == pair[1].price));
+            fills
+                .iter()
+                .step_by(2)
+                .map(|fill| fill.price)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(trade_prices(TradePrice::Resting), vec![1.0, 1.5]);
+        assert_eq!(trade_prices(TradePrice::Aggressor), vec![2.0, 2.0]);
+        assert_eq!(trade_prices(TradePrice::Midpoint), vec![1.5, 1.75]);
+    }
+
+    #[test]
+    fn rounding_modes() {
+        let rounding = |mode| Rounding { decimals: 2, mode };
+        assert_eq!(rounding(RoundingMode::HalfEven).round(0.125), 0.12);
+        assert_eq!(rounding(RoundingMode::HalfUp).round(0.125), 0.13);
+        assert_eq!(rounding(RoundingMode::Floor).round(0.129), 0.12);
+
+        let mut lob = Market::new(MarketConfig {
+            quote_rounding: Some(rounding(RoundingMode::Floor)),
+            ..Default::default()
+        });
+        lob.submit_order(1, 3, 0.333, OrderSide::Sell).unwrap();
+        lob.submit_order(2, 3, 0.333, OrderSide::Buy).unwrap();
+        assert_eq!(lob.position(1).balance, 0.99);
+        assert_eq!(lob.trade_stats().notional, 0.99);
+    }
+}
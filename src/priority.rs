@@ -0,0 +1,113 @@
+//! Time priority sources
+//!
+//! Each new order is stamped with a priority which orders it against others at the same price
+//! and doubles as its order id. By default this is the market's internal nonce. Embedders that
+//! already sequence orders, e.g. with exchange-assigned sequence numbers or hardware
+//! timestamps, can supply their own source so the book preserves the external ordering.
+//!
+//! Priorities must strictly increase, an order stamped at or below an earlier priority is
+//! rejected with `Error::StalePriority`.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// A source of order priorities
+pub trait PrioritySource: Send {
+    /// Priority of the next order, `nonce` is the lowest priority still available
+    fn next(&mut self, nonce: u64) -> u64;
+}
+
+/// The market's internal nonce
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Nonce;
+
+impl PrioritySource for Nonce {
+    fn next(&mut self, nonce: u64) -> u64 {
+        nonce
+    }
+}
+
+/// Priorities stamped by the embedder before each submit, clones share the same sequence
+#[derive(Clone, Debug, Default)]
+pub struct ExternalSequence(Arc<AtomicU64>);
+
+impl ExternalSequence {
+    pub fn new(sequence: u64) -> Self {
+        ExternalSequence(Arc::new(AtomicU64::new(sequence)))
+    }
+    /// Stamp the next order with `sequence`
+    pub fn set(&self, sequence: u64) {
+        self.0.store(sequence, Ordering::Relaxed);
+    }
+}
+
+impl PrioritySource for ExternalSequence {
+    fn next(&mut self, _nonce: u64) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl<F: FnMut(u64) -> u64 + Send> PrioritySource for F {
+    fn next(&mut self, nonce: u64) -> u64 {
+        self(nonce)
+    }
+}
+
+/// The market's priority source, the internal nonce unless replaced
+pub(crate) struct MarketPriority(pub Box<dyn PrioritySource>);
+
+impl Default for MarketPriority {
+    fn default() -> Self {
+        MarketPriority(Box::new(Nonce))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExternalSequence;
+    use crate::{Error, Market, OrderRequest, OrderSide};
+
+    #[test]
+    fn external_sequence_sets_priority() {
+        let mut market = Market::default();
+        let sequence = ExternalSequence::new(0);
+        market.set_priority_source(sequence.clone());
+
+        for (stamp, trader) in [(100, 1), (250, 2)] {
+            sequence.set(stamp);
+            let result = market
+                .place_order(OrderRequest::new(trader, 5, 1.0, OrderSide::Sell))
+                .unwrap();
+            assert_eq!(result.order_id, stamp);
+        }
+        sequence.set(250);
+        assert_eq!(
+            market.place_order(OrderRequest::new(3, 5, 1.0, OrderSide::Sell)),
+            Err(Error::StalePriority(250))
+        );
+
+        // the earlier stamp keeps time priority
+        sequence.set(300);
+        let fills = market
+            .place_order(OrderRequest::new(4, 5, 1.0, OrderSide::Buy))
+            .unwrap()
+            .fills;
+        assert_eq!(fills[0].order_id, 100);
+    }
+
+    #[test]
+    fn closures_are_sources() {
+        let mut market = Market::default();
+        market.set_priority_source(|nonce: u64| nonce * 10);
+        let ids: Vec<_> = (0..3)
+            .map(|_| {
+                market
+                    .place_order(OrderRequest::new(1, 1, 1.0, OrderSide::Buy))
+                    .unwrap()
+                    .order_id
+            })
+            .collect();
+        assert_eq!(ids, vec![0, 10, 110]);
+    }
+}
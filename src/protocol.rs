@@ -0,0 +1,316 @@
+//! Versioned JSON command protocol shared by network frontends
+//!
+//! Requests are JSON objects carrying the protocol version `v`, an optional client `id` which is
+//! echoed in the response, and a `cmd`:
+//! ```json
+//! {"v": 1, "id": 7, "cmd": "submit", "trader_id": 1, "side": "buy", "amount": 100, "price": 1.5}
+//! {"v": 1, "cmd": "cancel", "order_id": 3}
+//! {"v": 1, "cmd": "amend", "order_id": 3, "amount": 50, "price": 1.25}
+//! {"v": 1, "cmd": "query", "order_id": 3}
+//! {"v": 1, "cmd": "query", "levels": 5}
+//! {"v": 1, "cmd": "subscribe", "topics": ["fills", "depth"]}
+//! ```
+//! Responses have `"status": "ok"` with the command's result or `"status": "error"` with an
+//! `error` message. `subscribe` only validates and acknowledges topics, delivering updates is
+//! left to the frontend.
+//!
+//! An `Exchange` answers discovery requests listing its markets or describing one:
+//! ```json
+//! {"v": 1, "cmd": "markets"}
+//! {"v": 1, "cmd": "market", "symbol": "BTC-USD"}
+//! ```
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    depth::Level,
+    wire::{WireFill, WireOrder},
+    Error, Exchange, Market, OrderId, OrderRequest,
+};
+
+/// Current protocol version
+pub const VERSION: u64 = 1;
+
+/// Topics accepted by `subscribe`
+pub const TOPICS: &[&str] = &["fills", "depth"];
+
+const DEFAULT_LEVELS: usize = 10;
+
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+enum Request {
+    Submit(WireOrder),
+    Cancel {
+        order_id: OrderId,
+    },
+    Amend {
+        order_id: OrderId,
+        amount: u32,
+        price: f32,
+    },
+    Query {
+        order_id: Option<OrderId>,
+        levels: Option<usize>,
+    },
+    Subscribe {
+        topics: Vec<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+enum ExchangeRequest {
+    Markets,
+    Market { symbol: String },
+}
+
+fn levels_json(levels: &[Level]) -> Value {
+    levels
+        .iter()
+        .map(|level| json!([level.price, level.amount, level.orders]))
+        .collect()
+}
+
+fn handle(market: &mut Market, request: Request) -> Result<Value, String> {
+    let response = match request {
+        Request::Submit(order) => {
+            let request = OrderRequest::try_from(order).map_err(|err| err.to_string())?;
+            let result = market.place_order(request).map_err(|err| err.to_string())?;
+            let fills: Vec<WireFill> = result.fills.iter().map(WireFill::from).collect();
+            json!({ "order_id": result.order_id, "fills": fills })
+        }
+        Request::Cancel { order_id } => {
+            let order = market
+                .cancel_order(order_id)
+                .ok_or_else(|| crate::Error::OrderNotFound(order_id).to_string())?;
+            json!({ "order_id": order_id, "amount": order.amount })
+        }
+        Request::Amend {
+            order_id,
+            amount,
+            price,
+        } => {
+            let result = market
+                .amend_order(order_id, amount, price)
+                .map_err(|err| err.to_string())?;
+            let fills: Vec<WireFill> = result.fills.iter().map(WireFill::from).collect();
+            json!({ "order_id": result.order_id, "fills": fills })
+        }
+        Request::Query {
+            order_id: Some(order_id),
+            ..
+        } => {
+            let order = market
+                .get_order(order_id)
+                .ok_or_else(|| crate::Error::OrderNotFound(order_id).to_string())?;
+            json!({
+                "order": {
+                    "order_id": order.nonce,
+                    "trader_id": order.trader_id,
+                    "amount": order.amount,
+                    "price": order.price,
+                }
+            })
+        }
+        Request::Query {
+            order_id: None,
+            levels,
+        } => {
+            let depth = market.depth(levels.unwrap_or(DEFAULT_LEVELS));
+            json!({
+                "depth": { "bids": levels_json(&depth.bids), "asks": levels_json(&depth.asks) }
+            })
+        }
+        Request::Subscribe { topics } => {
+            if let Some(topic) = topics.iter().find(|t| !TOPICS.contains(&t.as_str())) {
+                return Err(format!("unknown topic: {topic}"));
+            }
+            json!({ "topics": topics })
+        }
+    };
+    Ok(response)
+}
+
+fn handle_exchange(exchange: &Exchange, request: ExchangeRequest) -> Result<Value, String> {
+    let response = match request {
+        ExchangeRequest::Markets => json!({ "markets": exchange.list_markets() }),
+        ExchangeRequest::Market { symbol } => {
+            let info = exchange
+                .market_info(&symbol)
+                .ok_or_else(|| Error::UnknownSymbol(symbol).to_string())?;
+            json!({ "market": info })
+        }
+    };
+    Ok(response)
+}
+
+/// Execute a JSON request against `market` returning the JSON response
+pub fn dispatch(market: &mut Market, request: &str) -> String {
+    respond(request, |request| handle(market, request))
+}
+
+/// Execute a JSON discovery request against `exchange` returning the JSON response
+pub fn dispatch_exchange(exchange: &Exchange, request: &str) -> String {
+    respond(request, |request| handle_exchange(exchange, request))
+}
+
+/// Check the envelope of `request` and answer it with `handle`
+fn respond<R: serde::de::DeserializeOwned>(
+    request: &str,
+    handle: impl FnOnce(R) -> Result<Value, String>,
+) -> String {
+    let mut id = Value::Null;
+    let result = serde_json::from_str::<Value>(request)
+        .map_err(|err| format!("invalid json: {err}"))
+        .and_then(|mut value| {
+            let object = value
+                .as_object_mut()
+                .ok_or_else(|| "request must be an object".to_string())?;
+            id = object.remove("id").unwrap_or_default();
+            match object.remove("v").and_then(|v| v.as_u64()) {
+                Some(VERSION) => (),
+                Some(v) => return Err(format!("unsupported version: {v}")),
+                None => return Err("missing version".to_string()),
+            }
+            serde_json::from_value::<R>(value).map_err(|err| err.to_string())
+        })
+        .and_then(handle);
+
+    let mut response = Map::new();
+    response.insert("v".to_string(), VERSION.into());
+    if !id.is_null() {
+        response.insert("id".to_string(), id);
+    }
+    match result {
+        Ok(Value::Object(body)) => {
+            response.insert("status".to_string(), "ok".into());
+            response.extend(body);
+        }
+        Ok(_) => unreachable!("responses are objects"),
+        Err(err) => {
+            response.insert("status".to_string(), "error".into());
+            response.insert("error".to_string(), err.into());
+        }
+    }
+    Value::Object(response).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{dispatch, dispatch_exchange};
+    use crate::{Exchange, Instrument, Market};
+
+    fn call(market: &mut Market, request: Value) -> Value {
+        serde_json::from_str(&dispatch(market, &request.to_string())).unwrap()
+    }
+
+    #[test]
+    fn order_lifecycle() {
+        let mut market = Market::default();
+        let response = call(
+            &mut market,
+            json!({"v": 1, "id": "a", "cmd": "submit", "trader_id": 1, "side": "sell", "amount": 100, "price": 1.5}),
+        );
+        assert_eq!(
+            response,
+            json!({"v": 1, "id": "a", "status": "ok", "order_id": 0, "fills": []})
+        );
+
+        let response = call(
+            &mut market,
+            json!({"v": 1, "cmd": "submit", "trader_id": 2, "side": "buy", "amount": 40, "price": 1.5}),
+        );
+        assert_eq!(response["fills"].as_array().map(Vec::len), Some(2));
+        assert_eq!(response["fills"][0]["amount"], 40);
+
+        let response = call(
+            &mut market,
+            json!({"v": 1, "cmd": "amend", "order_id": 0, "amount": 30, "price": 1.5}),
+        );
+        assert_eq!(response["order_id"], 0);
+
+        let response = call(&mut market, json!({"v": 1, "cmd": "query", "levels": 1}));
+        assert_eq!(
+            response["depth"],
+            json!({"bids": [], "asks": [[1.5, 30, 1]]})
+        );
+        let response = call(&mut market, json!({"v": 1, "cmd": "query", "order_id": 0}));
+        assert_eq!(response["order"]["amount"], 30);
+
+        let response = call(&mut market, json!({"v": 1, "cmd": "cancel", "order_id": 0}));
+        assert_eq!(
+            response,
+            json!({"v": 1, "status": "ok", "order_id": 0, "amount": 30})
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut market = Market::default();
+        for (request, error) in [
+            (json!({"cmd": "cancel", "order_id": 1}), "missing version"),
+            (
+                json!({"v": 2, "cmd": "cancel", "order_id": 1}),
+                "unsupported version: 2",
+            ),
+            (
+                json!({"v": 1, "cmd": "cancel", "order_id": 1}),
+                "order 1 not found",
+            ),
+            (
+                json!({"v": 1, "cmd": "subscribe", "topics": ["fills", "news"]}),
+                "unknown topic: news",
+            ),
+        ] {
+            let response = call(&mut market, request);
+            assert_eq!(response["status"], "error");
+            assert_eq!(response["error"], error);
+        }
+        let response: Value = serde_json::from_str(&dispatch(&mut market, "{")).unwrap();
+        assert_eq!(response["status"], "error");
+    }
+
+    #[test]
+    fn market_discovery() {
+        let mut exchange = Exchange::default();
+        exchange.add_market_with(
+            "BTC-USD",
+            Instrument {
+                base: "BTC".to_string(),
+                quote: "USD".to_string(),
+                tick_size: 0.5,
+                lot_size: 0.25,
+            },
+        );
+        exchange.add_market("ETH-USD");
+        let call = |request: Value| -> Value {
+            serde_json::from_str(&dispatch_exchange(&exchange, &request.to_string())).unwrap()
+        };
+
+        let response = call(json!({"v": 1, "cmd": "markets"}));
+        let symbols: Vec<&Value> = response["markets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|market| &market["symbol"])
+            .collect();
+        assert_eq!(symbols, vec!["BTC-USD", "ETH-USD"]);
+        assert_eq!(
+            call(json!({"v": 1, "cmd": "market", "symbol": "BTC-USD"}))["market"],
+            json!({
+                "symbol": "BTC-USD",
+                "base": "BTC",
+                "quote": "USD",
+                "tick_size": 0.5,
+                "lot_size": 0.25,
+                "status": "open"
+            })
+        );
+        assert_eq!(
+            call(json!({"v": 1, "cmd": "market", "symbol": "XRP"}))["error"],
+            "unknown symbol: XRP"
+        );
+    }
+}
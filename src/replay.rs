@@ -0,0 +1,377 @@
+//! Deterministic replay verification
+//!
+//! A golden file pairs a command stream with the events and final book state it is expected to
+//! produce. Commands are prefixed with `>`, events with `<` and the final state hash with `=`:
+//! ```text
+//! > submit 1 sell 100 1.5
+//! < accept 0
+//! > cancel 0
+//! < cancel 0 100
+//! = 529a60dc8ff58c08
+//! ```
+//! Replaying a golden file against the current engine detects any change in matching behavior.
+//!
+//! Timestamped command streams, such as an `AuditTrail`, can be played back against a market
+//! in real time, accelerated or as fast as possible with `play`.
+use std::{
+    fmt,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{book::BookBackend, clock::SimClock, Command, CommandParseError, Event, Market, Order};
+
+/// Golden file covering resting, partial fills, level sweeps, cancels and rejects
+pub const STANDARD: &str = include_str!("../golden/standard.golden");
+
+/// A command and the events it is expected to produce in their text form
+#[derive(PartialEq, Clone, Debug)]
+pub struct Step {
+    pub command: Command,
+    pub events: Vec<String>,
+}
+
+/// A command stream with its expected events and final state hash
+#[derive(PartialEq, Clone, Debug)]
+pub struct Golden {
+    pub steps: Vec<Step>,
+    /// Expected `Market::state_hash` after all commands
+    pub state_hash: u64,
+}
+
+/// Ways a replay can fail
+#[derive(PartialEq, Clone, Debug)]
+pub enum ReplayError {
+    /// Golden file line could not be parsed
+    Parse { line: usize, text: String },
+    /// The command at `step` produced different events
+    EventMismatch {
+        step: usize,
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+    /// Final book state differs
+    StateMismatch { expected: u64, actual: u64 },
+    /// A speed factor which is not finite and positive
+    InvalidSpeed(f64),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse { line, text } => write!(f, "line {line}: cannot parse {text:?}"),
+            Self::EventMismatch {
+                step,
+                expected,
+                actual,
+            } => write!(f, "step {step}: expected {expected:?} got {actual:?}"),
+            Self::StateMismatch { expected, actual } => {
+                write!(f, "state hash: expected {expected:016x} got {actual:016x}")
+            }
+            Self::InvalidSpeed(factor) => write!(f, "invalid speed factor: {factor}"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl Golden {
+    /// Record the events and final state the current engine produces for `commands`
+    pub fn record(commands: impl IntoIterator<Item = Command>) -> Self {
+        let mut market = Market::default();
+        let steps = commands
+            .into_iter()
+            .map(|command| Step {
+                events: market
+                    .execute(&command)
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                command,
+            })
+            .collect();
+        Golden {
+            steps,
+            state_hash: market.state_hash(),
+        }
+    }
+    /// Replay the commands against a new market checking the output matches
+    pub fn verify(&self) -> Result<(), ReplayError> {
+        let actual = Golden::record(self.steps.iter().map(|step| step.command.clone()));
+        for (idx, (expected, actual)) in self.steps.iter().zip(actual.steps).enumerate() {
+            if expected.events != actual.events {
+                return Err(ReplayError::EventMismatch {
+                    step: idx,
+                    expected: expected.events.clone(),
+                    actual: actual.events,
+                });
+            }
+        }
+        if self.state_hash != actual.state_hash {
+            return Err(ReplayError::StateMismatch {
+                expected: self.state_hash,
+                actual: actual.state_hash,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Verify the bundled `STANDARD` golden file against the current engine
+pub fn verify_standard() -> Result<(), ReplayError> {
+    STANDARD.parse::<Golden>()?.verify()
+}
+
+impl FromStr for Golden {
+    type Err = ReplayError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut golden = Golden {
+            steps: vec![],
+            state_hash: 0,
+        };
+        for (idx, line) in s.lines().enumerate() {
+            let parse_err = || ReplayError::Parse {
+                line: idx + 1,
+                text: line.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (prefix, rest) = line.split_at_checked(1).ok_or_else(parse_err)?;
+            match prefix {
+                ">" => golden.steps.push(Step {
+                    command: rest
+                        .trim()
+                        .parse()
+                        .map_err(|_: CommandParseError| parse_err())?,
+                    events: vec![],
+                }),
+                "<" => golden
+                    .steps
+                    .last_mut()
+                    .ok_or_else(parse_err)?
+                    .events
+                    .push(rest.trim().to_string()),
+                "=" => {
+                    golden.state_hash =
+                        u64::from_str_radix(rest.trim(), 16).map_err(|_| parse_err())?
+                }
+                _ => return Err(parse_err()),
+            }
+        }
+        Ok(golden)
+    }
+}
+
+impl fmt::Display for Golden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in self.steps.iter() {
+            writeln!(f, "> {}", step.command)?;
+            for event in step.events.iter() {
+                writeln!(f, "< {event}")?;
+            }
+        }
+        writeln!(f, "= {:016x}", self.state_hash)
+    }
+}
+
+/// FNV-1a, stable across platforms and compiler versions unlike `DefaultHasher`
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100_0000_01b3);
+        }
+    }
+}
+
+fn hash_book<T: Order>(hasher: &mut Fnv, book: &impl BookBackend<T>) {
+    for order in book.iter().map(Order::inner) {
+        hasher.write(&order.nonce.to_le_bytes());
+        hasher.write(&order.trader_id.to_le_bytes());
+        hasher.write(&order.amount.to_le_bytes());
+        hasher.write(&order.price.to_bits().to_le_bytes());
+        // flagless orders hash as before flags were added
+        if !order.flags.is_empty() {
+            hasher.write(&order.flags.bits().to_le_bytes());
+        }
+    }
+}
+
+impl Market {
+    /// Hash of the resting orders and order nonce
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+        hasher.write(&self.nonce.to_le_bytes());
+        hash_book(&mut hasher, &self.buys);
+        hasher.write(b"|");
+        hash_book(&mut hasher, &self.sells);
+        hasher.0
+    }
+}
+
+/// Pace of a timed replay
+#[derive(PartialEq, Clone, Debug)]
+pub enum Speed {
+    /// Commands are spaced as they were recorded
+    Realtime,
+    /// Recorded gaps are divided by the factor, see `Speed::accelerated`
+    /// Factors which are not finite and positive play as fast as possible
+    Accelerated(f64),
+    /// No waiting between commands
+    AsFastAsPossible,
+}
+
+impl Speed {
+    /// Recorded gaps divided by `factor`, rejecting factors which are not finite and positive
+    pub fn accelerated(factor: f64) -> Result<Self, ReplayError> {
+        if factor.is_finite() && factor > 0.0 {
+            Ok(Speed::Accelerated(factor))
+        } else {
+            Err(ReplayError::InvalidSpeed(factor))
+        }
+    }
+}
+
+/// Execute timestamped commands on `market` paced by `speed`, returning each command's events
+///
+/// The market's clock is replaced by `clock`, which is set to each command's timestamp before it
+/// executes, so time driven behavior matches the recording whatever the speed
+pub fn play(
+    market: &mut Market,
+    clock: &SimClock,
+    commands: impl IntoIterator<Item = (u64, Command)>,
+    speed: Speed,
+) -> Vec<Vec<Event>> {
+    market.set_clock(clock.clone());
+    let factor = match speed {
+        Speed::Realtime => Some(1.0),
+        Speed::Accelerated(factor) => Some(factor).filter(|f| f.is_finite() && *f > 0.0),
+        Speed::AsFastAsPossible => None,
+    };
+    let start = Instant::now();
+    let mut first = None;
+    let mut events = Vec::default();
+    for (timestamp, command) in commands {
+        let first = *first.get_or_insert(timestamp);
+        if let Some(factor) = factor {
+            let gap = Duration::from_nanos(timestamp.saturating_sub(first));
+            let due =
+                Duration::try_from_secs_f64(gap.as_secs_f64() / factor).unwrap_or(Duration::MAX);
+            if let Some(wait) = due.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        clock.set(timestamp);
+        events.push(market.execute(&command));
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{play, verify_standard, Golden, ReplayError, Speed};
+    use crate::{clock::SimClock, Command, Market, OrderRequest, OrderSide};
+
+    #[test]
+    fn standard_golden_file() {
+        assert_eq!(verify_standard(), Ok(()));
+    }
+
+    #[test]
+    fn detects_changes() {
+        let golden = Golden::record(vec![
+            Command::Submit(OrderRequest::new(1, 100, 1.0, OrderSide::Sell)),
+            Command::Submit(OrderRequest::new(2, 50, 1.0, OrderSide::Buy)),
+        ]);
+        assert_eq!(golden.to_string().parse(), Ok(golden.clone()));
+
+        let mut changed = golden.clone();
+        changed.steps[1].events[1] = "fill 0 1 2 sell 49 1".to_string();
+        assert!(matches!(
+            changed.verify(),
+            Err(ReplayError::EventMismatch { step: 1, .. })
+        ));
+
+        let mut changed = golden;
+        changed.state_hash ^= 1;
+        assert!(matches!(
+            changed.verify(),
+            Err(ReplayError::StateMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn timed_playback() {
+        let second = 1_000_000_000;
+        let commands = vec![
+            (
+                5 * second,
+                Command::Submit(OrderRequest::new(1, 100, 1.0, OrderSide::Sell)),
+            ),
+            (
+                6 * second,
+                Command::Submit(OrderRequest::new(2, 50, 1.0, OrderSide::Buy)),
+            ),
+            (6 * second + 1, Command::Cancel(0)),
+        ];
+        let clock = SimClock::default();
+        let mut market = Market::default();
+        let start = Instant::now();
+        let events = play(
+            &mut market,
+            &clock,
+            commands.clone(),
+            Speed::Accelerated(20.0),
+        );
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(events.len(), 3);
+        assert_eq!(market.trades()[0].timestamp, 6 * second);
+        assert_eq!(market.now(), 6 * second + 1);
+
+        let mut fast = Market::default();
+        let fast_events = play(
+            &mut fast,
+            &SimClock::default(),
+            commands,
+            Speed::AsFastAsPossible,
+        );
+        assert_eq!(fast_events, events);
+        assert_eq!(fast.trades(), market.trades());
+    }
+
+    #[test]
+    fn invalid_speed() {
+        for factor in [0.0, -2.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                Speed::accelerated(factor),
+                Err(ReplayError::InvalidSpeed(_))
+            ));
+        }
+        assert_eq!(Speed::accelerated(2.0), Ok(Speed::Accelerated(2.0)));
+
+        // built directly, an invalid factor does not wait
+        let commands = vec![
+            (
+                0,
+                Command::Submit(OrderRequest::new(1, 100, 1.0, OrderSide::Sell)),
+            ),
+            (60_000_000_000, Command::Cancel(0)),
+        ];
+        let start = Instant::now();
+        let events = play(
+            &mut Market::default(),
+            &SimClock::default(),
+            commands,
+            Speed::Accelerated(0.0),
+        );
+        assert_eq!(events.len(), 2);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,165 @@
+//! Smart order routing across venues
+//!
+//! `Router` splits an order across the books of several venues, taking the cheapest liquidity
+//! first after each venue's taker fee. The plan holds one child order per venue, limited at the
+//! worst price it takes there, ready to submit to that venue.
+use std::collections::BTreeMap;
+
+use crate::{depth::DepthQuery, OrderRequest, OrderSide};
+
+/// Part of a routed order to send to one venue
+#[derive(PartialEq, Clone, Debug)]
+pub struct ChildOrder {
+    pub venue: String,
+    pub amount: u32,
+    /// Worst price taken at the venue
+    pub price: f32,
+}
+
+impl ChildOrder {
+    /// The order request to submit on the venue
+    pub fn request(&self, trader_id: u32, side: OrderSide) -> OrderRequest {
+        OrderRequest::new(trader_id, self.amount, self.price, side)
+    }
+}
+
+/// Child orders filling as much of the target amount as the venues allow
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct RoutePlan {
+    /// Child orders in venue name order
+    pub children: Vec<ChildOrder>,
+    /// Total amount routed, less than the target if liquidity ran out
+    pub amount: u32,
+    /// Notional paid for buys or received for sells, after fees
+    pub cost: f64,
+}
+
+/// Routes orders over named books
+#[derive(Default)]
+pub struct Router<'a> {
+    venues: Vec<(String, &'a dyn DepthQuery)>,
+    /// Taker fee rate by venue
+    fees: BTreeMap<String, f64>,
+}
+
+impl<'a> Router<'a> {
+    pub fn venue(mut self, venue: &str, book: &'a dyn DepthQuery) -> Self {
+        self.venues.push((venue.to_string(), book));
+        self
+    }
+    /// Charge `rate` of notional for taking liquidity on `venue`
+    pub fn fee(mut self, venue: &str, rate: f64) -> Self {
+        self.fees.insert(venue.to_string(), rate);
+        self
+    }
+    /// Plan a `side` order for `amount`, taking no liquidity beyond `limit` if given
+    pub fn route(&self, side: OrderSide, amount: u32, limit: Option<f32>) -> RoutePlan {
+        // (effective price, venue, price, amount) of every level on the opposite side
+        let mut levels = Vec::<(f64, &str, f32, u64)>::default();
+        for (venue, book) in self.venues.iter() {
+            let fee = self.fees.get(venue).copied().unwrap_or_default();
+            let depth = book.depth(usize::MAX);
+            let (opposite, fee) = match side {
+                OrderSide::Buy => (depth.asks, fee),
+                OrderSide::Sell => (depth.bids, -fee),
+            };
+            for level in opposite {
+                let effective = level.price as f64 * (1.0 + fee);
+                levels.push((effective, venue, level.price, level.amount));
+            }
+        }
+        levels.sort_by(|a, b| match side {
+            OrderSide::Buy => a.0.total_cmp(&b.0),
+            OrderSide::Sell => b.0.total_cmp(&a.0),
+        });
+
+        let within_limit = |price: f32| match (&side, limit) {
+            (_, None) => true,
+            (OrderSide::Buy, Some(limit)) => price <= limit,
+            (OrderSide::Sell, Some(limit)) => price >= limit,
+        };
+        let mut children = BTreeMap::<&str, ChildOrder>::default();
+        let (mut remaining, mut cost) = (amount, 0.0);
+        for (effective, venue, price, available) in levels {
+            if remaining == 0 {
+                break;
+            }
+            if !within_limit(price) {
+                continue;
+            }
+            let take = available.min(remaining as u64) as u32;
+            remaining -= take;
+            cost += effective * take as f64;
+            let child = children.entry(venue).or_insert_with(|| ChildOrder {
+                venue: venue.to_string(),
+                amount: 0,
+                price,
+            });
+            child.amount += take;
+            // levels of a venue are visited best first
+            child.price = price;
+        }
+        RoutePlan {
+            children: children.into_values().collect(),
+            amount: amount - remaining,
+            cost,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChildOrder, Router};
+    use crate::{
+        depth::{DepthSnapshot, Level},
+        shadow::ShadowBook,
+        Market, OrderSide, LOB,
+    };
+
+    #[test]
+    fn splits_by_cost_after_fees() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 50, 1.5, OrderSide::Sell).unwrap();
+        lob.submit_order(1, 50, 1.75, OrderSide::Sell).unwrap();
+        let level = |price, amount| Level {
+            price,
+            amount,
+            orders: 1,
+        };
+        let external = ShadowBook::from_depth(DepthSnapshot {
+            bids: vec![],
+            asks: vec![level(1.49, 30), level(1.6, 100)],
+        });
+        // 1.49 costs more than 1.5 after a 1% fee
+        let router = Router::default()
+            .venue("local", &lob)
+            .venue("ext", &external)
+            .fee("ext", 0.01);
+
+        let plan = router.route(OrderSide::Buy, 100, None);
+        assert_eq!(
+            plan.children,
+            vec![
+                ChildOrder {
+                    venue: "ext".to_string(),
+                    amount: 50,
+                    price: 1.6
+                },
+                ChildOrder {
+                    venue: "local".to_string(),
+                    amount: 50,
+                    price: 1.5
+                }
+            ]
+        );
+        let ext_cost = (30.0 * 1.49_f32 as f64 + 20.0 * 1.6_f32 as f64) * 1.01;
+        assert!((plan.cost - (75.0 + ext_cost)).abs() < 1e-6);
+
+        let limited = router.route(OrderSide::Buy, 500, Some(1.6));
+        assert_eq!(limited.amount, 180);
+        let fills = lob
+            .submit_order(2, 50, limited.children[1].price, OrderSide::Buy)
+            .unwrap();
+        assert_eq!(fills.len(), 2);
+    }
+}
@@ -0,0 +1,304 @@
+//! Human editable matching scenarios
+//!
+//! A scenario is a TOML document describing an initial book in the `fixtures` text form, a
+//! script of commands in their text form with the events each is expected to produce, and
+//! optionally the book expected after the last step. Config lines are those of session files:
+//! ```toml
+//! name = "partial fill rests the remainder"
+//! config = ["max-orders-per-trader 10"]
+//! book = """
+//! sell 1.5: 1x100 2x50
+//! buy 1.0: 3x20
+//! """
+//! expect_book = "buy 1.0: 3x20"
+//!
+//! [[step]]
+//! command = "submit 4 buy 120 1.5"
+//! expect = [
+//!     "accept 3",
+//!     "fill 0 1 4 sell 100 1.5",
+//!     "fill 3 4 1 buy 100 1.5",
+//!     "fill 1 2 4 sell 20 1.5",
+//!     "fill 3 4 2 buy 20 1.5",
+//! ]
+//!
+//! [[step]]
+//! command = "cancel 1"
+//! ```
+//! Steps without `expect` are run unchecked. Running a scenario reports every difference
+//! rather than stopping at the first, `lob-scenario` runs scenario files from the command line.
+use std::{fmt, str::FromStr};
+
+use serde::Deserialize;
+
+use crate::{
+    fixtures::{BookFixture, FixtureParseError},
+    session::parse_config,
+    Command, MarketConfig,
+};
+
+/// An initial book and a script of commands with their expected events
+#[derive(PartialEq, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    /// Session config lines
+    #[serde(default)]
+    pub config: Vec<String>,
+    /// Initial book in the fixture text form
+    #[serde(default)]
+    pub book: String,
+    #[serde(default, rename = "step")]
+    pub steps: Vec<Step>,
+    /// Book after the last step in the fixture text form
+    pub expect_book: Option<String>,
+}
+
+/// A command and the events it is expected to produce, in their text forms
+#[derive(PartialEq, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub command: String,
+    pub expect: Option<Vec<String>>,
+}
+
+/// A scenario could not be loaded or run
+#[derive(PartialEq, Clone, Debug)]
+pub enum ScenarioError {
+    /// The document is not a valid scenario
+    Parse(String),
+    /// A config line could not be parsed
+    Config(String),
+    /// The initial or expected book could not be parsed
+    Book(FixtureParseError),
+    /// The initial book's best bid is at or above its best ask
+    CrossedBook,
+    /// The command of `step` could not be parsed
+    Command { step: usize, text: String },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "invalid scenario: {err}"),
+            Self::Config(line) => write!(f, "invalid config line: {line:?}"),
+            Self::Book(err) => write!(f, "{err}"),
+            Self::CrossedBook => write!(f, "initial book is crossed"),
+            Self::Command { step, text } => write!(f, "step {step}: invalid command {text:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<FixtureParseError> for ScenarioError {
+    fn from(err: FixtureParseError) -> Self {
+        ScenarioError::Book(err)
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = ScenarioError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).map_err(|err| ScenarioError::Parse(err.message().to_string()))
+    }
+}
+
+/// Where a run differed from the scenario, as expected and actual text lines
+#[derive(PartialEq, Clone, Debug)]
+pub enum Difference {
+    /// The events of the command at `step`
+    Events {
+        step: usize,
+        command: String,
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+    /// The book after the last step
+    Book {
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+}
+
+/// Outcome of running a scenario
+#[derive(PartialEq, Clone, Debug)]
+pub struct ScenarioReport {
+    pub name: String,
+    pub differences: Vec<Difference>,
+}
+
+impl ScenarioReport {
+    pub fn passed(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// Write `expected` and `actual` line by line, prefixing differing lines with `-` and `+`
+fn write_diff(f: &mut fmt::Formatter<'_>, expected: &[String], actual: &[String]) -> fmt::Result {
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(expected), Some(actual)) if expected == actual => writeln!(f, "  {expected}")?,
+            (expected, actual) => {
+                if let Some(expected) = expected {
+                    writeln!(f, "- {expected}")?;
+                }
+                if let Some(actual) = actual {
+                    writeln!(f, "+ {actual}")?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.passed() {
+            return writeln!(f, "{}: ok", self.name);
+        }
+        writeln!(f, "{}: {} differences", self.name, self.differences.len())?;
+        for difference in &self.differences {
+            match difference {
+                Difference::Events {
+                    step,
+                    command,
+                    expected,
+                    actual,
+                } => {
+                    writeln!(f, "step {step} `{command}`")?;
+                    write_diff(f, expected, actual)?;
+                }
+                Difference::Book { expected, actual } => {
+                    writeln!(f, "book")?;
+                    write_diff(f, expected, actual)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn lines(fixture: &BookFixture) -> Vec<String> {
+    fixture.to_string().lines().map(str::to_string).collect()
+}
+
+impl Scenario {
+    /// Run the scenario against a new `Market`, reporting where it differed
+    ///
+    /// Panics if the initial book is crossed
+    pub fn run(&self) -> Result<ScenarioReport, ScenarioError> {
+        let mut config = MarketConfig::default();
+        for line in &self.config {
+            parse_config(&mut config, line).ok_or_else(|| ScenarioError::Config(line.clone()))?;
+        }
+        let book = self.book.parse::<BookFixture>()?;
+        if book.is_crossed() {
+            return Err(ScenarioError::CrossedBook);
+        }
+        let mut market = book.build_with(config);
+        let mut differences = vec![];
+        for (step, Step { command, expect }) in self.steps.iter().enumerate() {
+            let parsed: Command = command.parse().map_err(|_| ScenarioError::Command {
+                step,
+                text: command.clone(),
+            })?;
+            let actual: Vec<String> = market
+                .execute(&parsed)
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            match expect {
+                Some(expected) if *expected != actual => differences.push(Difference::Events {
+                    step,
+                    command: command.clone(),
+                    expected: expected.clone(),
+                    actual,
+                }),
+                _ => (),
+            }
+        }
+        if let Some(expect_book) = &self.expect_book {
+            let expected = lines(&expect_book.parse::<BookFixture>()?.sorted());
+            let actual = lines(&BookFixture::of(&market));
+            if expected != actual {
+                differences.push(Difference::Book { expected, actual });
+            }
+        }
+        Ok(ScenarioReport {
+            name: self.name.clone(),
+            differences,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Difference, Scenario, ScenarioError};
+
+    const PARTIAL_FILL: &str = include_str!("../scenarios/partial-fill.toml");
+
+    #[test]
+    fn bundled_scenario_passes() {
+        let report = PARTIAL_FILL.parse::<Scenario>().unwrap().run().unwrap();
+        assert!(report.passed(), "{report}");
+    }
+
+    #[test]
+    fn reports_differences() {
+        let scenario: Scenario = r#"
+            name = "wrong"
+            book = "sell 1.5: 1x10"
+            expect_book = "sell 1.5: 1x10"
+
+            [[step]]
+            command = "submit 2 buy 4 1.5"
+            expect = ["accept 1", "fill 0 1 2 sell 5 1.5"]
+        "#
+        .parse()
+        .unwrap();
+        let report = scenario.run().unwrap();
+        assert_eq!(
+            report.differences,
+            vec![
+                Difference::Events {
+                    step: 0,
+                    command: "submit 2 buy 4 1.5".to_string(),
+                    expected: vec!["accept 1".to_string(), "fill 0 1 2 sell 5 1.5".to_string()],
+                    actual: vec![
+                        "accept 1".to_string(),
+                        "fill 0 1 2 sell 4 1.5".to_string(),
+                        "fill 1 2 1 buy 4 1.5".to_string()
+                    ],
+                },
+                Difference::Book {
+                    expected: vec!["sell 1.5: 1x10".to_string()],
+                    actual: vec!["sell 1.5: 1x6".to_string()],
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_string().lines().nth(3),
+            Some("- fill 0 1 2 sell 5 1.5")
+        );
+        assert!(matches!(
+            "name = 1".parse::<Scenario>(),
+            Err(ScenarioError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_crossed_book() {
+        let scenario: Scenario = r#"
+            name = "crossed"
+            book = """
+                sell 1.5: 1x10
+                buy 1.5: 2x10
+            """
+            expect_book = ""
+        "#
+        .parse()
+        .unwrap();
+        assert_eq!(scenario.run(), Err(ScenarioError::CrossedBook));
+    }
+}
@@ -0,0 +1,196 @@
+//! Seed a market from external exchange order book snapshots
+//!
+//! Reads the `bids`/`asks` arrays returned by most crypto exchange REST depth endpoints, where
+//! each level is `[price, size, ...]` with numbers or numeric strings and any extra fields are
+//! ignored:
+//! ```json
+//! {"lastUpdateId": 1027024, "bids": [["4.00000000", "431.00000000"]], "asks": [["4.00000200", "12.00000000"]]}
+//! ```
+//! Each level becomes a single resting order of `SeedOptions::trader_id`, sizes are converted to
+//! order amounts in units of `lot_size`.
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::{fixtures::BookFixture, Market, MarketConfig};
+
+/// How external levels map onto the market
+#[derive(PartialEq, Clone, Debug)]
+pub struct SeedOptions {
+    /// Trader owning the seeded orders
+    pub trader_id: u32,
+    /// Size of one unit of order amount
+    pub lot_size: f64,
+    pub config: MarketConfig,
+}
+
+impl Default for SeedOptions {
+    fn default() -> Self {
+        SeedOptions {
+            trader_id: 0,
+            lot_size: 1.0,
+            config: MarketConfig::default(),
+        }
+    }
+}
+
+/// Errors reading an external snapshot
+#[derive(PartialEq, Clone, Debug)]
+pub enum SeedError {
+    /// The snapshot is not valid JSON or lacks the `bids`/`asks` arrays
+    Json(String),
+    /// A level is not a `[price, size]` pair of numbers
+    InvalidLevel(String),
+    /// The best bid is not below the best ask
+    Crossed,
+    /// `SeedOptions::lot_size` is not finite and positive
+    InvalidLotSize(f64),
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "invalid snapshot: {err}"),
+            Self::InvalidLevel(level) => write!(f, "invalid level: {level}"),
+            Self::Crossed => write!(f, "snapshot book is crossed"),
+            Self::InvalidLotSize(lot_size) => write!(f, "invalid lot size: {lot_size}"),
+        }
+    }
+}
+
+impl std::error::Error for SeedError {}
+
+/// A price or size given as a number or numeric string
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(number) => number.parse().ok(),
+        _ => None,
+    }
+}
+
+/// `(price, amount)` levels of a side, dropping levels smaller than one lot
+fn levels(
+    snapshot: &Value,
+    side: &str,
+    options: &SeedOptions,
+) -> Result<Vec<(f32, u32)>, SeedError> {
+    let levels = snapshot[side]
+        .as_array()
+        .ok_or_else(|| SeedError::Json(format!("missing {side} array")))?;
+    let mut parsed = Vec::with_capacity(levels.len());
+    for level in levels {
+        let invalid = || SeedError::InvalidLevel(level.to_string());
+        let (price, size) = match level.as_array().map(Vec::as_slice) {
+            Some([price, size, ..]) => (
+                number(price).ok_or_else(invalid)?,
+                number(size).ok_or_else(invalid)?,
+            ),
+            _ => return Err(invalid()),
+        };
+        // prices beyond the f32 range are finite here but not once cast
+        let price = price as f32;
+        let negative = price < 0.0 && !options.config.allow_negative_prices;
+        if !price.is_finite() || negative || size.is_nan() || size < 0.0 {
+            return Err(invalid());
+        }
+        let amount = (size / options.lot_size).round().min(u32::MAX as f64) as u32;
+        if amount > 0 {
+            parsed.push((price, amount));
+        }
+    }
+    Ok(parsed)
+}
+
+/// Build a market resting the levels of an exchange depth snapshot
+pub fn market_from_json(json: &str, options: &SeedOptions) -> Result<Market, SeedError> {
+    if !options.lot_size.is_finite() || options.lot_size <= 0.0 {
+        return Err(SeedError::InvalidLotSize(options.lot_size));
+    }
+    let snapshot: Value =
+        serde_json::from_str(json).map_err(|err| SeedError::Json(err.to_string()))?;
+    let bids = levels(&snapshot, "bids", options)?;
+    let asks = levels(&snapshot, "asks", options)?;
+    let best_bid = bids.iter().map(|(price, _)| *price).reduce(f32::max);
+    let best_ask = asks.iter().map(|(price, _)| *price).reduce(f32::min);
+    if let (Some(bid), Some(ask)) = (best_bid, best_ask) {
+        if bid >= ask {
+            return Err(SeedError::Crossed);
+        }
+    }
+    let trader_id = options.trader_id;
+    let fixture = bids
+        .into_iter()
+        .fold(BookFixture::default(), |fixture, (price, amount)| {
+            fixture.buy(price, &[(trader_id, amount)])
+        });
+    let fixture = asks.into_iter().fold(fixture, |fixture, (price, amount)| {
+        fixture.sell(price, &[(trader_id, amount)])
+    });
+    Ok(fixture.build_with(options.config.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{market_from_json, SeedError, SeedOptions};
+    use crate::{depth::Level, OrderSide, LOB};
+
+    #[test]
+    fn seeds_from_rest_depth() {
+        let json = r#"{
+            "lastUpdateId": 1027024,
+            "bids": [["4.00", "431.5"], [3.5, 10, "ignored"], ["3.0", "0.001"]],
+            "asks": [["4.25", "12.00"]]
+        }"#;
+        let options = SeedOptions {
+            trader_id: 99,
+            lot_size: 0.5,
+            ..Default::default()
+        };
+        let mut market = market_from_json(json, &options).unwrap();
+        assert_eq!(
+            market.depth(5).bids,
+            vec![
+                Level {
+                    price: 4.0,
+                    amount: 863,
+                    orders: 1
+                },
+                Level {
+                    price: 3.5,
+                    amount: 20,
+                    orders: 1
+                }
+            ]
+        );
+        let fills = market.submit_order(1, 24, 4.25, OrderSide::Buy).unwrap();
+        assert_eq!(fills[0].trader, 99);
+
+        assert_eq!(
+            market_from_json(r#"{"bids": [["5", "1"]], "asks": [["4", "1"]]}"#, &options).err(),
+            Some(SeedError::Crossed)
+        );
+        assert!(matches!(
+            market_from_json(r#"{"bids": [["x", "1"]], "asks": []}"#, &options),
+            Err(SeedError::InvalidLevel(_))
+        ));
+        assert!(matches!(
+            market_from_json(r#"{"bids": []}"#, &options),
+            Err(SeedError::Json(_))
+        ));
+        assert!(matches!(
+            market_from_json(r#"{"bids": [[1e39, "1"]], "asks": []}"#, &options),
+            Err(SeedError::InvalidLevel(_))
+        ));
+        for lot_size in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let options = SeedOptions {
+                lot_size,
+                ..Default::default()
+            };
+            assert!(matches!(
+                market_from_json(r#"{"bids": [], "asks": []}"#, &options),
+                Err(SeedError::InvalidLotSize(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,470 @@
+//! Recorded sessions
+//!
+//! A session file captures everything needed to reproduce a market run: the config, the
+//! commands in their text form and the clock ticks between them, closed by the expected final
+//! state hash. Config lines are only written for settings differing from the default:
+//! ```text
+//! simple-lob-session 1
+//! config matching pro-rata 0.4 random
+//! config stp-group 2 7
+//! config funding 3600000000000 0.01
+//! config tiebreak-seed 42
+//! @ 1000
+//! submit 1 sell 33 1.5
+//! submit 2 sell 33 1.5
+//! submit 3 sell 33 1.5
+//! @ 3600000001000
+//! submit 4 buy 50 1.5
+//! @ 7200000001000
+//! cancel 0
+//! = 6d0c45f9d2a590a5
+//! ```
+//! Share a session with a bug report, `replay` rebuilds the market and checks its final state.
+use std::{fmt, str::FromStr, time::Duration};
+
+use crate::{
+    analytics::VpinConfig,
+    clock::{Clock, SimClock, SystemClock},
+    compaction::Compaction,
+    funding::Funding,
+    halt::HaltPolicy,
+    BudgetRemainder, Command, CommandParseError, Event, Market, MarketConfig, MatchingPolicy,
+    ResidualAllocation, Rounding, RoundingMode, StpAction, TradePrice,
+};
+
+/// Version written by `Session`'s `Display`, the only version parsed
+pub const VERSION: u32 = 1;
+
+/// An entry of a session in execution order
+#[derive(PartialEq, Clone, Debug)]
+pub enum Entry {
+    /// Set the clock to the given time in nanoseconds
+    Tick(u64),
+    Command(Command),
+}
+
+/// Entries in their session text form, `@ <now>` for a tick
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tick(now) => write!(f, "@ {now}"),
+            Self::Command(command) => write!(f, "{command}"),
+        }
+    }
+}
+
+impl FromStr for Entry {
+    type Err = CommandParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("@ ") {
+            Some(now) => now
+                .trim()
+                .parse()
+                .map(Entry::Tick)
+                .map_err(|_| CommandParseError(s.to_string())),
+            None => s.parse().map(Entry::Command),
+        }
+    }
+}
+
+/// A recorded market run
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Session {
+    pub config: MarketConfig,
+    pub entries: Vec<Entry>,
+    /// Expected `Market::state_hash` after all entries, unchecked if `None`
+    pub state_hash: Option<u64>,
+}
+
+/// Ways reading or replaying a session can fail
+#[derive(PartialEq, Clone, Debug)]
+pub enum SessionError {
+    /// The file is not a session of a supported version
+    Version(String),
+    /// Session file line could not be parsed
+    Parse { line: usize, text: String },
+    /// Final book state differs
+    StateMismatch { expected: u64, actual: u64 },
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Version(header) => write!(f, "unsupported session header: {header:?}"),
+            Self::Parse { line, text } => write!(f, "line {line}: cannot parse {text:?}"),
+            Self::StateMismatch { expected, actual } => {
+                write!(f, "state hash: expected {expected:016x} got {actual:016x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// Executes commands on a new market recording them as a `Session`
+pub struct Recorder {
+    market: Market,
+    session: Session,
+    /// Time of the last recorded tick
+    now: Option<u64>,
+}
+
+/// Start recording a session on a new market with `config` timed by the system clock
+pub fn record(config: MarketConfig) -> Recorder {
+    Recorder {
+        market: Market::new(config.clone()),
+        session: Session {
+            config,
+            ..Default::default()
+        },
+        now: None,
+    }
+    .with_clock(SystemClock)
+}
+
+impl Recorder {
+    /// Time the market by `clock`, each command records a tick if the time changed
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.market.set_clock(clock);
+        self
+    }
+    pub fn market(&self) -> &Market {
+        &self.market
+    }
+    /// Execute `command` on the market recording it
+    pub fn execute(&mut self, command: &Command) -> Vec<Event> {
+        let now = self.market.now();
+        if self.now != Some(now) {
+            self.now = Some(now);
+            self.session.entries.push(Entry::Tick(now));
+        }
+        self.session.entries.push(Entry::Command(command.clone()));
+        self.market.execute(command)
+    }
+    /// The recorded session and the final market state
+    pub fn finish(self) -> (Session, Market) {
+        let session = Session {
+            state_hash: Some(self.market.state_hash()),
+            ..self.session
+        };
+        (session, self.market)
+    }
+}
+
+/// Rebuild the market of `session` on a simulated clock, checking the final state hash
+pub fn replay(session: &Session) -> Result<Market, SessionError> {
+    let clock = SimClock::default();
+    let mut market = Market::new(session.config.clone());
+    market.set_clock(clock.clone());
+    for entry in session.entries.iter() {
+        match entry {
+            Entry::Tick(now) => clock.set(*now),
+            Entry::Command(command) => {
+                market.execute(command);
+            }
+        }
+    }
+    match session.state_hash {
+        Some(expected) if expected != market.state_hash() => Err(SessionError::StateMismatch {
+            expected,
+            actual: market.state_hash(),
+        }),
+        _ => Ok(market),
+    }
+}
+
+fn rounding_mode_str(mode: &RoundingMode) -> &'static str {
+    match mode {
+        RoundingMode::HalfEven => "half-even",
+        RoundingMode::HalfUp => "half-up",
+        RoundingMode::Floor => "floor",
+    }
+}
+
+fn residual_str(residual: &ResidualAllocation) -> &'static str {
+    match residual {
+        ResidualAllocation::Fifo => "fifo",
+        ResidualAllocation::LargestRemainder => "largest-remainder",
+        ResidualAllocation::Random => "random",
+    }
+}
+
+/// Config lines of the settings differing from the default
+pub(crate) fn config_lines(config: &MarketConfig) -> Vec<String> {
+    let default = MarketConfig::default();
+    let mut lines = vec![];
+    match &config.matching {
+        MatchingPolicy::PriceTime => (),
+        MatchingPolicy::SizeTime => lines.push("matching size-time".to_string()),
+        MatchingPolicy::ProRata {
+            top_allocation,
+            residual,
+        } => lines.push(format!(
+            "matching pro-rata {top_allocation} {}",
+            residual_str(residual)
+        )),
+    }
+    for (trader_id, group) in config.stp_groups.iter() {
+        lines.push(format!("stp-group {trader_id} {group}"));
+    }
+    if let Some(max) = config.max_orders_per_level {
+        lines.push(format!("max-orders-per-level {max}"));
+    }
+    if let Some(max) = config.max_orders_per_trader {
+        lines.push(format!("max-orders-per-trader {max}"));
+    }
+    if config.allow_negative_prices {
+        lines.push("allow-negative-prices".to_string());
+    }
+    if let Some(rounding) = &config.quote_rounding {
+        lines.push(format!(
+            "quote-rounding {} {}",
+            rounding.decimals,
+            rounding_mode_str(&rounding.mode)
+        ));
+    }
+    if config.vpin != default.vpin {
+        let VpinConfig {
+            bucket_volume,
+            buckets,
+        } = &config.vpin;
+        lines.push(format!("vpin {bucket_volume} {buckets}"));
+    }
+    if let Some(Funding { interval, rate }) = &config.funding {
+        lines.push(format!("funding {} {rate}", interval.as_nanos()));
+    }
+    if let Some(seed) = config.tiebreak_seed {
+        lines.push(format!("tiebreak-seed {seed}"));
+    }
+    if let Some(Compaction {
+        ratio,
+        min_capacity,
+    }) = &config.compaction
+    {
+        lines.push(format!("compaction {ratio} {min_capacity}"));
+    }
+    if config.halt == HaltPolicy::Park {
+        lines.push("halt park".to_string());
+    }
+    match config.trade_price {
+        TradePrice::Resting => (),
+        TradePrice::Aggressor => lines.push("trade-price aggressor".to_string()),
+        TradePrice::Midpoint => lines.push("trade-price midpoint".to_string()),
+    }
+    if config.stp_action == StpAction::CancelResting {
+        lines.push("stp-action cancel-resting".to_string());
+    }
+    if let Some(max) = config.budget.max_fills {
+        lines.push(format!("max-fills {max}"));
+    }
+    if let Some(max) = config.budget.max_levels {
+        lines.push(format!("max-levels {max}"));
+    }
+    if config.budget.remainder == BudgetRemainder::Cancel {
+        lines.push("budget-remainder cancel".to_string());
+    }
+    lines
+}
+
+/// Apply a config line's setting to `config`, `None` if it is invalid
+pub(crate) fn parse_config(config: &mut MarketConfig, line: &str) -> Option<()> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    match parts.as_slice() {
+        ["matching", "price-time"] => config.matching = MatchingPolicy::PriceTime,
+        ["matching", "size-time"] => config.matching = MatchingPolicy::SizeTime,
+        ["matching", "pro-rata", top_allocation, residual] => {
+            config.matching = MatchingPolicy::ProRata {
+                top_allocation: top_allocation
+                    .parse()
+                    .ok()
+                    .filter(|fraction| (0.0..=1.0).contains(fraction))?,
+                residual: match *residual {
+                    "fifo" => ResidualAllocation::Fifo,
+                    "largest-remainder" => ResidualAllocation::LargestRemainder,
+                    "random" => ResidualAllocation::Random,
+                    _ => return None,
+                },
+            }
+        }
+        ["stp-group", trader_id, group] => {
+            config
+                .stp_groups
+                .insert(trader_id.parse().ok()?, group.parse().ok()?);
+        }
+        ["max-orders-per-level", max] => config.max_orders_per_level = Some(max.parse().ok()?),
+        ["max-orders-per-trader", max] => config.max_orders_per_trader = Some(max.parse().ok()?),
+        ["allow-negative-prices"] => config.allow_negative_prices = true,
+        ["quote-rounding", decimals, mode] => {
+            config.quote_rounding = Some(Rounding {
+                decimals: decimals.parse().ok()?,
+                mode: match *mode {
+                    "half-even" => RoundingMode::HalfEven,
+                    "half-up" => RoundingMode::HalfUp,
+                    "floor" => RoundingMode::Floor,
+                    _ => return None,
+                },
+            })
+        }
+        ["vpin", bucket_volume, buckets] => {
+            config.vpin = VpinConfig {
+                bucket_volume: bucket_volume.parse().ok()?,
+                buckets: buckets.parse().ok()?,
+            }
+        }
+        ["funding", interval, rate] => {
+            config.funding = Some(Funding {
+                interval: Duration::from_nanos(interval.parse().ok()?),
+                rate: rate.parse().ok()?,
+            })
+        }
+        ["tiebreak-seed", seed] => config.tiebreak_seed = Some(seed.parse().ok()?),
+        ["compaction", ratio, min_capacity] => {
+            config.compaction = Some(Compaction {
+                ratio: ratio.parse().ok()?,
+                min_capacity: min_capacity.parse().ok()?,
+            })
+        }
+        ["halt", "reject"] => config.halt = HaltPolicy::Reject,
+        ["halt", "park"] => config.halt = HaltPolicy::Park,
+        ["trade-price", "resting"] => config.trade_price = TradePrice::Resting,
+        ["trade-price", "aggressor"] => config.trade_price = TradePrice::Aggressor,
+        ["trade-price", "midpoint"] => config.trade_price = TradePrice::Midpoint,
+        ["stp-action", "cancel-incoming"] => config.stp_action = StpAction::CancelIncoming,
+        ["stp-action", "cancel-resting"] => config.stp_action = StpAction::CancelResting,
+        ["max-fills", max] => config.budget.max_fills = Some(max.parse().ok()?),
+        ["max-levels", max] => config.budget.max_levels = Some(max.parse().ok()?),
+        ["budget-remainder", "rest"] => config.budget.remainder = BudgetRemainder::Rest,
+        ["budget-remainder", "cancel"] => config.budget.remainder = BudgetRemainder::Cancel,
+        _ => return None,
+    }
+    Some(())
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "simple-lob-session {VERSION}")?;
+        for line in config_lines(&self.config) {
+            writeln!(f, "config {line}")?;
+        }
+        for entry in self.entries.iter() {
+            writeln!(f, "{entry}")?;
+        }
+        if let Some(state_hash) = self.state_hash {
+            writeln!(f, "= {state_hash:016x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Session {
+    type Err = SessionError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let header = lines.next().map(|(_, line)| line).unwrap_or_default();
+        if header != format!("simple-lob-session {VERSION}") {
+            return Err(SessionError::Version(header.to_string()));
+        }
+        let mut session = Session::default();
+        for (line, text) in lines {
+            let parse_err = || SessionError::Parse {
+                line,
+                text: text.to_string(),
+            };
+            if let Some(setting) = text.strip_prefix("config ") {
+                parse_config(&mut session.config, setting).ok_or_else(parse_err)?;
+            } else if let Some(hash) = text.strip_prefix("= ") {
+                let hash = u64::from_str_radix(hash.trim(), 16).map_err(|_| parse_err())?;
+                session.state_hash = Some(hash);
+            } else {
+                let entry = text.parse().map_err(|_| parse_err())?;
+                session.entries.push(entry);
+            }
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{record, replay, Session, SessionError};
+    use crate::{
+        clock::SimClock, funding::Funding, halt::HaltPolicy, BudgetRemainder, Command,
+        MarketConfig, MatchBudget, MatchingPolicy, OrderRequest, OrderSide, ResidualAllocation,
+    };
+
+    #[test]
+    fn record_and_replay() {
+        let hour = Duration::from_secs(3600);
+        let config = MarketConfig {
+            matching: MatchingPolicy::ProRata {
+                top_allocation: 0.4,
+                residual: ResidualAllocation::Random,
+            },
+            stp_groups: [(2, 7)].into(),
+            funding: Some(Funding {
+                interval: hour,
+                rate: 0.01,
+            }),
+            tiebreak_seed: Some(42),
+            halt: HaltPolicy::Park,
+            budget: MatchBudget {
+                max_levels: Some(4),
+                remainder: BudgetRemainder::Cancel,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let clock = SimClock::new(1_000);
+        let mut recorder = record(config).with_clock(clock.clone());
+        for trader_id in 1..=3 {
+            recorder.execute(&Command::Submit(OrderRequest::new(
+                trader_id,
+                33,
+                1.5,
+                OrderSide::Sell,
+            )));
+        }
+        clock.advance(hour);
+        recorder.execute(&Command::Submit(OrderRequest::new(
+            4,
+            50,
+            1.5,
+            OrderSide::Buy,
+        )));
+        clock.advance(hour);
+        recorder.execute(&Command::Cancel(0));
+        let (session, market) = recorder.finish();
+
+        let text = session.to_string();
+        assert_eq!(text.lines().filter(|line| line.starts_with("@")).count(), 3);
+        let parsed: Session = text.parse().unwrap();
+        assert_eq!(parsed, session);
+        let replayed = replay(&parsed).unwrap();
+        assert_eq!(replayed.trades(), market.trades());
+        assert_eq!(replayed.funding_payments(), market.funding_payments());
+
+        let mut changed = parsed;
+        changed.entries.pop();
+        assert!(matches!(
+            replay(&changed),
+            Err(SessionError::StateMismatch { .. })
+        ));
+        assert!(matches!(
+            "simple-lob-session 2\n".parse::<Session>(),
+            Err(SessionError::Version(_))
+        ));
+        assert_eq!(
+            "simple-lob-session 1\nconfig matching fast\n".parse::<Session>(),
+            Err(SessionError::Parse {
+                line: 2,
+                text: "config matching fast".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Books maintained from external level 2 feeds
+//!
+//! A `ShadowBook` holds aggregated price levels only, updated from incremental level changes
+//! such as those produced by `DepthSnapshot::diff`. No matching takes place, so analytics written
+//! against `DepthQuery` run unchanged on a `Market` or a shadow of an external venue.
+use crate::{
+    depth::{DepthQuery, DepthSnapshot, Level, LevelChange},
+    OrderSide,
+};
+
+/// Price levels of an external book, best price first
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct ShadowBook {
+    bids: Vec<Level>,
+    asks: Vec<Level>,
+}
+
+impl ShadowBook {
+    /// Start from a full depth snapshot
+    pub fn from_depth(depth: DepthSnapshot) -> Self {
+        let mut book = ShadowBook::default();
+        for level in depth.bids {
+            book.set_level(OrderSide::Buy, level);
+        }
+        for level in depth.asks {
+            book.set_level(OrderSide::Sell, level);
+        }
+        book
+    }
+    /// Replace the level at `level.price`, removing it if its amount is zero
+    pub fn set_level(&mut self, side: OrderSide, level: Level) {
+        let levels = match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        };
+        let position = levels.binary_search_by(|existing| match side {
+            OrderSide::Buy => level.price.total_cmp(&existing.price),
+            OrderSide::Sell => existing.price.total_cmp(&level.price),
+        });
+        match (position, level.amount) {
+            (Ok(i), 0) => {
+                levels.remove(i);
+            }
+            (Ok(i), _) => levels[i] = level,
+            (Err(_), 0) => (),
+            (Err(i), _) => levels.insert(i, level),
+        }
+    }
+    /// Apply an incremental update
+    pub fn apply(&mut self, change: &LevelChange) {
+        match change {
+            LevelChange::Added { side, level }
+            | LevelChange::Changed {
+                side, to: level, ..
+            } => self.set_level(side.clone(), level.clone()),
+            LevelChange::Removed { side, level } => self.set_level(
+                side.clone(),
+                Level {
+                    amount: 0,
+                    ..level.clone()
+                },
+            ),
+        }
+    }
+}
+
+impl DepthQuery for ShadowBook {
+    fn depth(&self, max_levels: usize) -> DepthSnapshot {
+        DepthSnapshot {
+            bids: self.bids.iter().take(max_levels).cloned().collect(),
+            asks: self.asks.iter().take(max_levels).cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShadowBook;
+    use crate::{depth::DepthQuery, Market, OrderSide, LOB};
+
+    #[test]
+    fn mirrors_market_from_diffs() {
+        let mut lob = Market::default();
+        let mut shadow = ShadowBook::default();
+        let mut last = lob.depth(usize::MAX);
+        for (trader, amount, price, side) in [
+            (1, 100, 1.0, OrderSide::Buy),
+            (2, 50, 1.5, OrderSide::Sell),
+            (3, 20, 1.25, OrderSide::Buy),
+            (4, 10, 2.0, OrderSide::Sell),
+            (5, 70, 1.0, OrderSide::Sell),
+            (6, 5, 1.5, OrderSide::Buy),
+        ] {
+            lob.submit_order(trader, amount, price, side).unwrap();
+            let depth = lob.depth(usize::MAX);
+            for change in last.diff(&depth) {
+                shadow.apply(&change);
+            }
+            last = depth;
+        }
+        assert_eq!(shadow.depth(10), lob.depth(10));
+        assert_eq!(shadow.mid_price(), lob.mid_price());
+        assert_eq!(shadow.best_ask().map(|level| level.amount), Some(45));
+        assert_eq!(ShadowBook::from_depth(lob.depth(10)), shadow);
+    }
+}
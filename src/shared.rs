@@ -0,0 +1,71 @@
+//! Thread safe market handle
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    ingress::{IngressConfig, IngressQueue},
+    Command, Event, Market,
+};
+
+/// A market shared between threads
+///
+/// Commands are executed one at a time and stamped with a gap free sequence number, either
+/// directly or buffered through the ingress queue to be prioritized under load
+#[derive(Clone, Default)]
+pub struct SharedMarket {
+    inner: Arc<Mutex<Sequenced>>,
+    ingress: Arc<Mutex<IngressQueue>>,
+}
+
+#[derive(Default)]
+struct Sequenced {
+    market: Market,
+    /// Sequence number of the next command
+    seq: u64,
+}
+
+impl SharedMarket {
+    pub fn new(market: Market) -> Self {
+        SharedMarket {
+            inner: Arc::new(Mutex::new(Sequenced { market, seq: 0 })),
+            ingress: Default::default(),
+        }
+    }
+    /// Set the depths and shedding policy of the ingress queue
+    pub fn with_ingress(mut self, config: IngressConfig) -> Self {
+        self.ingress = Arc::new(Mutex::new(IngressQueue::new(config)));
+        self
+    }
+    /// Execute `command` returning its sequence number and events
+    pub fn execute(&self, command: &Command) -> (u64, Vec<Event>) {
+        let mut inner = self.inner.lock().expect("lock poisoned");
+        let seq = inner.seq;
+        inner.seq += 1;
+        (seq, inner.market.execute(command))
+    }
+    /// Queue `command` for `process`, returning the command shed if the queue was full
+    pub fn enqueue(&self, command: Command) -> Option<Command> {
+        self.ingress.lock().expect("lock poisoned").push(command)
+    }
+    /// Execute up to `max` queued commands in priority order, returning each with its sequence
+    /// number and events
+    pub fn process(&self, max: usize) -> Vec<(u64, Command, Vec<Event>)> {
+        let mut processed = vec![];
+        while processed.len() < max {
+            // release the queue before matching so producers are not blocked
+            let Some(command) = self.ingress.lock().expect("lock poisoned").pop() else {
+                break;
+            };
+            let (seq, events) = self.execute(&command);
+            processed.push((seq, command, events));
+        }
+        processed
+    }
+    /// Run `f` with exclusive access to the market
+    pub fn with<R>(&self, f: impl FnOnce(&Market) -> R) -> R {
+        f(&self.inner.lock().expect("lock poisoned").market)
+    }
+    /// Run `f` with exclusive access to the ingress queue
+    pub fn with_ingress_queue<R>(&self, f: impl FnOnce(&IngressQueue) -> R) -> R {
+        f(&self.ingress.lock().expect("lock poisoned"))
+    }
+}
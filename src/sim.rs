@@ -0,0 +1,310 @@
+//! Backtest and agent-based simulation
+//!
+//! `LatencyModel` delays a strategy's timestamped decisions by per-trader latency distributions,
+//! producing the order commands arrive at the book for `replay::play`. Faster traders' orders can
+//! overtake slower ones and win queue priority, as they would live.
+//!
+//! A `Simulation` drives `Agent`s against a market in fixed time steps, delivering each agent its
+//! fills from the market's event bus and the book after every step in which it changed.
+use std::{collections::BTreeMap, sync::mpsc, time::Duration};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{clock::SimClock, depth::DepthSnapshot, Command, Fill, Market};
+
+/// Distribution of the delay between decision and book arrival
+#[derive(PartialEq, Clone, Debug)]
+pub enum LatencyDistribution {
+    Fixed(Duration),
+    /// Uniformly distributed within `min..=max`
+    Uniform {
+        min: Duration,
+        max: Duration,
+    },
+    /// Normally distributed, negative samples are clamped to zero
+    Normal {
+        mean: Duration,
+        std_dev: Duration,
+    },
+}
+
+impl Default for LatencyDistribution {
+    fn default() -> Self {
+        LatencyDistribution::Fixed(Duration::ZERO)
+    }
+}
+
+impl LatencyDistribution {
+    /// Sample a latency in nanoseconds
+    fn sample(&self, rng: &mut StdRng) -> u64 {
+        match self {
+            Self::Fixed(latency) => latency.as_nanos() as u64,
+            Self::Uniform { min, max } => {
+                rng.gen_range(min.as_nanos() as u64..=max.as_nanos().max(min.as_nanos()) as u64)
+            }
+            Self::Normal { mean, std_dev } => {
+                // Box-Muller transform
+                let (u1, u2): (f64, f64) = (1.0 - rng.gen::<f64>(), rng.gen());
+                let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+                (mean.as_nanos() as f64 + z * std_dev.as_nanos() as f64).max(0.0) as u64
+            }
+        }
+    }
+}
+
+/// Per-trader order latency
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct LatencyModel {
+    /// Latency of traders without their own distribution
+    pub default: LatencyDistribution,
+    pub traders: BTreeMap<u32, LatencyDistribution>,
+    pub seed: u64,
+}
+
+impl LatencyModel {
+    pub fn new(default: LatencyDistribution) -> Self {
+        LatencyModel {
+            default,
+            ..Default::default()
+        }
+    }
+    /// Use `latency` for commands of `trader_id`
+    pub fn with_trader(mut self, trader_id: u32, latency: LatencyDistribution) -> Self {
+        self.traders.insert(trader_id, latency);
+        self
+    }
+    pub fn with_seed(self, seed: u64) -> Self {
+        LatencyModel { seed, ..self }
+    }
+    /// Delay `(decision time, trader, command)` decisions, returning `(arrival time, command)`
+    /// in arrival order
+    ///
+    /// A trader's commands arrive in the order they were decided, as over a single connection.
+    /// Commands arriving at the same time keep their decision order.
+    pub fn delay(
+        &self,
+        decisions: impl IntoIterator<Item = (u64, u32, Command)>,
+    ) -> Vec<(u64, Command)> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut last_arrival = BTreeMap::<u32, u64>::default();
+        let mut arrivals: Vec<(u64, Command)> = decisions
+            .into_iter()
+            .map(|(timestamp, trader_id, command)| {
+                let latency = self.traders.get(&trader_id).unwrap_or(&self.default);
+                let last = last_arrival.entry(trader_id).or_default();
+                *last = timestamp
+                    .saturating_add(latency.sample(&mut rng))
+                    .max(*last);
+                (*last, command)
+            })
+            .collect();
+        arrivals.sort_by_key(|(arrival, _)| *arrival);
+        arrivals
+    }
+}
+
+/// A simulated market participant
+pub trait Agent {
+    /// Commands to execute at market time `now`
+    fn on_tick(&mut self, market: &Market, now: u64) -> Vec<Command>;
+    /// One of the agent's orders filled
+    fn on_fill(&mut self, _fill: &Fill) {}
+    /// The book changed during the last step
+    fn on_book_update(&mut self, _depth: &DepthSnapshot) {}
+}
+
+/// Runs agents against a market on a simulated clock
+pub struct Simulation {
+    market: Market,
+    clock: SimClock,
+    /// Time between steps
+    step: Duration,
+    /// Levels per side of the depth sent to agents
+    depth_levels: usize,
+    /// Agents and the trader id whose fills they receive, in turn order
+    agents: Vec<(u32, Box<dyn Agent>)>,
+    fills: mpsc::Receiver<Fill>,
+}
+
+impl Simulation {
+    /// Simulate `market` starting at time zero, advancing by `step`
+    pub fn new(mut market: Market, step: Duration, depth_levels: usize) -> Self {
+        let clock = SimClock::new(0);
+        market.set_clock(clock.clone());
+        let (tx, fills) = mpsc::channel();
+        market.subscribe(move |fill: &Fill| {
+            let _ = tx.send(fill.clone());
+        });
+        Simulation {
+            market,
+            clock,
+            step,
+            depth_levels,
+            agents: Vec::default(),
+            fills,
+        }
+    }
+    /// Add an agent trading as `trader_id`, agents act in the order they were added
+    pub fn add_agent(&mut self, trader_id: u32, agent: impl Agent + 'static) {
+        self.agents.push((trader_id, Box::new(agent)));
+    }
+    pub fn market(&self) -> &Market {
+        &self.market
+    }
+    /// Run `steps` steps, in each every agent acts in turn receiving fills as they happen
+    pub fn run(&mut self, steps: usize) {
+        let mut depth = self.market.depth(self.depth_levels);
+        for _ in 0..steps {
+            self.clock.advance(self.step);
+            let now = self.market.now();
+            for i in 0..self.agents.len() {
+                for command in self.agents[i].1.on_tick(&self.market, now) {
+                    self.market.execute(&command);
+                    self.deliver_fills();
+                }
+            }
+            let updated = self.market.depth(self.depth_levels);
+            if updated != depth {
+                for (_, agent) in self.agents.iter_mut() {
+                    agent.on_book_update(&updated);
+                }
+                depth = updated;
+            }
+        }
+    }
+    fn deliver_fills(&mut self) {
+        for fill in self.fills.try_iter() {
+            for (_, agent) in self
+                .agents
+                .iter_mut()
+                .filter(|(trader_id, _)| *trader_id == fill.trader)
+            {
+                agent.on_fill(&fill);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Agent, LatencyDistribution, LatencyModel, Simulation};
+    use crate::{
+        clock::SimClock,
+        depth::DepthSnapshot,
+        replay::{play, Speed},
+        Command, Fill, Market, OrderRequest, OrderSide,
+    };
+
+    #[test]
+    fn faster_trader_wins_priority() {
+        let micros = |n| Duration::from_micros(n);
+        let model = LatencyModel::new(LatencyDistribution::Fixed(micros(100)))
+            .with_trader(
+                2,
+                LatencyDistribution::Uniform {
+                    min: micros(5),
+                    max: micros(10),
+                },
+            )
+            .with_seed(7);
+        let submit =
+            |trader_id| Command::Submit(OrderRequest::new(trader_id, 10, 1.0, OrderSide::Buy));
+        let arrivals = model.delay(vec![
+            (0, 1, submit(1)),
+            (20_000, 2, submit(2)),
+            (20_000, 1, submit(1)),
+        ]);
+        assert_eq!(arrivals[0].1, submit(2));
+        assert!((25_000..=30_000).contains(&arrivals[0].0));
+        assert_eq!(arrivals[1], (100_000, submit(1)));
+        assert_eq!(arrivals[2], (120_000, submit(1)));
+
+        let mut lob = Market::default();
+        play(
+            &mut lob,
+            &SimClock::new(0),
+            arrivals,
+            Speed::AsFastAsPossible,
+        );
+        assert_eq!(lob.get_order(0).map(|order| order.trader_id), Some(2));
+        assert_eq!(lob.queue_position(1), Some(10));
+    }
+
+    #[test]
+    fn normal_latency_is_seeded() {
+        let model = LatencyModel::new(LatencyDistribution::Normal {
+            mean: Duration::from_micros(50),
+            std_dev: Duration::from_micros(20),
+        });
+        let decisions = || (0..100).map(|i| (i * 1_000, i as u32 % 3, Command::Cancel(i)));
+        let arrivals = model.delay(decisions());
+        assert_eq!(arrivals, model.delay(decisions()));
+        assert!(arrivals.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    }
+
+    /// Quotes one ask each step while its inventory lasts
+    struct Maker {
+        inventory: u32,
+    }
+
+    impl Agent for Maker {
+        fn on_tick(&mut self, market: &Market, _now: u64) -> Vec<Command> {
+            if market.depth(1).asks.is_empty() && self.inventory > 0 {
+                let amount = self.inventory.min(10);
+                vec![Command::Submit(OrderRequest::new(
+                    1,
+                    amount,
+                    2.0,
+                    OrderSide::Sell,
+                ))]
+            } else {
+                vec![]
+            }
+        }
+        fn on_fill(&mut self, fill: &Fill) {
+            self.inventory -= fill.amount;
+        }
+    }
+
+    /// Buys 5 of the best ask each step and records what it saw
+    #[derive(Default)]
+    struct Taker {
+        bought: std::sync::Arc<std::sync::Mutex<(u32, usize)>>,
+    }
+
+    impl Agent for Taker {
+        fn on_tick(&mut self, market: &Market, _now: u64) -> Vec<Command> {
+            assert!(market.now() > 0);
+            market
+                .depth(1)
+                .asks
+                .iter()
+                .map(|level| Command::Submit(OrderRequest::new(2, 5, level.price, OrderSide::Buy)))
+                .collect()
+        }
+        fn on_fill(&mut self, fill: &Fill) {
+            self.bought.lock().unwrap().0 += fill.amount;
+        }
+        fn on_book_update(&mut self, _depth: &DepthSnapshot) {
+            self.bought.lock().unwrap().1 += 1;
+        }
+    }
+
+    #[test]
+    fn agents_trade() {
+        let mut sim = Simulation::new(Market::default(), Duration::from_secs(1), 5);
+        let taker = Taker::default();
+        let bought = taker.bought.clone();
+        sim.add_agent(1, Maker { inventory: 25 });
+        sim.add_agent(2, taker);
+        sim.run(5);
+
+        // the maker requotes 10 once taken out, the last quote is its remaining 5
+        assert_eq!(sim.market().trade_stats().volume, 25);
+        assert_eq!(sim.market().now(), 5_000_000_000);
+        // the book ends the final step empty as it started the step
+        assert_eq!(*bought.lock().unwrap(), (25, 4));
+    }
+}
@@ -0,0 +1,527 @@
+//! Point in time market state
+//!
+//! Snapshots have a line based text form, the order nonce, the market's config lines as in
+//! session files, resting orders (`<side> <order id> <trader> <amount> <price> [flags]`) in
+//! priority order, then the ledger: trades, trader positions, the statistics of completed
+//! sessions and the current session's first trade:
+//! ```text
+//! nonce 3
+//! config max-orders-per-trader 10
+//! buy 1 7 100 1.5
+//! sell 2 8 50 1.75 post-only
+//! trade 0 1000 1.5 1.5 20 sell 7 1 9 0
+//! position 7 20 -30
+//! position 9 -20 30
+//! history 1.5 1.5 1.5 1.5 1 20
+//! history - - - - 0 0
+//! session 1
+//! ```
+//! Trades are `trade <trade id> <timestamp> <price> <maker price> <amount> <aggressor> <buyer>
+//! <buy order id> <seller> <sell order id> [busted]`, positions `position <trader> <position>
+//! <balance>` and completed sessions `history <open> <high> <low> <close> <trades> <volume>`,
+//! with `-` for the prices of a session without trades.
+//!
+//! A market's resting orders can also be drained into a snapshot and loaded into another market,
+//! e.g. one with a different config or book backend, keeping their ids and priority. Loaded
+//! orders pass the target market's price and order cap checks.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+};
+
+use crate::{
+    book::BookBackend,
+    day::SessionStats,
+    hooks::{OrderCaps, PreTradeHook, PriceCheck},
+    ledger::{Ledger, Position, Trade, TradeId},
+    session::{config_lines, parse_config},
+    ConfigError, Error, LimitOrder, Market, MarketConfig, Order, OrderFlags, OrderId, OrderRequest,
+    OrderSide,
+};
+
+/// The state required to rebuild a `Market`
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Snapshot {
+    /// Next order nonce
+    pub nonce: u64,
+    pub config: MarketConfig,
+    /// Resting orders, buys then sells in priority order
+    pub orders: Vec<(OrderSide, LimitOrder)>,
+    /// All trades in trade id order, including busted trades
+    pub trades: Vec<Trade>,
+    pub positions: BTreeMap<u32, Position>,
+    /// Statistics of the completed sessions in order
+    pub session_history: Vec<SessionStats>,
+    /// First trade of the current session
+    pub session_start: TradeId,
+}
+
+/// Ways restoring a snapshot can fail
+#[derive(PartialEq, Clone, Debug)]
+pub enum SnapshotError {
+    /// The snapshot's config is invalid
+    Config(ConfigError),
+    /// The snapshot's orders can't rest on a market with its config
+    Orders(Error),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(err) => write!(f, "{err}"),
+            Self::Orders(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<ConfigError> for SnapshotError {
+    fn from(err: ConfigError) -> Self {
+        Self::Config(err)
+    }
+}
+
+impl From<Error> for SnapshotError {
+    fn from(err: Error) -> Self {
+        Self::Orders(err)
+    }
+}
+
+impl Market {
+    /// Resting orders, buys then sells in priority order
+    fn resting_orders(&self) -> Vec<(OrderSide, LimitOrder)> {
+        let buys = self
+            .buys
+            .iter()
+            .map(|order| (OrderSide::Buy, order.inner().clone()));
+        let sells = self
+            .sells
+            .iter()
+            .map(|order| (OrderSide::Sell, order.inner().clone()));
+        buys.chain(sells).collect()
+    }
+    /// Capture the market state
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            nonce: self.nonce,
+            config: self.config.clone(),
+            orders: self.resting_orders(),
+            trades: self.trades().to_vec(),
+            positions: self.ledger.positions().clone(),
+            session_history: self.day.history.clone(),
+            session_start: self.ledger.stats_from(),
+        }
+    }
+    /// Rebuild a market from `snapshot`, failing if its config is invalid or its orders would
+    /// be rejected by `load_orders`
+    pub fn from_snapshot(snapshot: Snapshot) -> Result<Self, SnapshotError> {
+        let mut market = Market::with_config(snapshot.config)?;
+        market.nonce = snapshot.nonce;
+        let session_start = snapshot.session_start.min(snapshot.trades.len() as TradeId);
+        market.ledger = Ledger::restore(
+            market.config.quote_rounding.clone(),
+            snapshot.trades,
+            snapshot.positions,
+            session_start,
+        );
+        market.day.history = snapshot.session_history;
+        market.rebucket();
+        market.load_orders(Snapshot {
+            nonce: snapshot.nonce,
+            orders: snapshot.orders,
+            ..Default::default()
+        })?;
+        Ok(market)
+    }
+    /// Remove the resting orders matching `filter`, returning them with the order nonce
+    pub fn drain_orders(
+        &mut self,
+        mut filter: impl FnMut(&OrderSide, &LimitOrder) -> bool,
+    ) -> Snapshot {
+        let mut drained = Snapshot {
+            nonce: self.nonce,
+            orders: self.resting_orders(),
+            ..Default::default()
+        };
+        drained.orders.retain(|(side, order)| filter(side, order));
+        for (side, order) in drained.orders.iter() {
+            match side {
+                OrderSide::Buy => self.buys.remove(order.nonce).map(|_| ()),
+                OrderSide::Sell => self.sells.remove(order.nonce).map(|_| ()),
+            }
+            .expect("order is resting");
+        }
+        drained
+    }
+    /// Add the resting orders of `snapshot` to the book at their original priority
+    ///
+    /// The order nonce advances past the snapshot's so new ids stay unique. Nothing is loaded
+    /// if an order id is already resting, the orders would cross the book, or an order fails
+    /// the market's `PriceCheck` or `OrderCaps`. A loaded order improving a side's best price
+    /// becomes its pro-rata top order.
+    pub fn load_orders(&mut self, snapshot: Snapshot) -> Result<(), Error> {
+        let mut best_bid = self.buys.front().map(|order| order.inner().price);
+        let mut best_ask = self.sells.front().map(|order| order.inner().price);
+        let mut loaded = BTreeSet::<OrderId>::default();
+        for (side, order) in snapshot.orders.iter() {
+            if self.get_order(order.nonce).is_some() || !loaded.insert(order.nonce) {
+                return Err(Error::DuplicateOrder(order.nonce));
+            }
+            let best = match side {
+                OrderSide::Buy => &mut best_bid,
+                OrderSide::Sell => &mut best_ask,
+            };
+            *best = Some(match (side, *best) {
+                (OrderSide::Buy, Some(best)) => best.max(order.price),
+                (OrderSide::Sell, Some(best)) => best.min(order.price),
+                (_, None) => order.price,
+            });
+        }
+        if let (Some(bid), Some(ask)) = (best_bid, best_ask) {
+            if bid >= ask {
+                return Err(Error::CrossedBook);
+            }
+        }
+
+        let bid = self.buys.front().map(|order| order.inner().price);
+        let ask = self.sells.front().map(|order| order.inner().price);
+        let mut inserted = Vec::<(OrderSide, OrderId)>::default();
+        for (side, order) in snapshot.orders {
+            let mut request = OrderRequest::new(order.trader_id, order.amount, order.price, side);
+            let checked = PriceCheck
+                .check(self, &mut request)
+                .and_then(|_| OrderCaps.check(self, &mut request));
+            if let Err(err) = checked {
+                // leave the book as it was
+                for (side, order_id) in inserted {
+                    match side {
+                        OrderSide::Buy => self.buys.remove(order_id).map(|_| ()),
+                        OrderSide::Sell => self.sells.remove(order_id).map(|_| ()),
+                    }
+                    .expect("order was loaded");
+                }
+                return Err(err);
+            }
+            let order = LimitOrder {
+                price: request.price,
+                ..order
+            };
+            match request.side {
+                OrderSide::Buy => self.buys.insert_order(&order.clone().into()),
+                OrderSide::Sell => self.sells.insert_order(&order.clone().into()),
+            }
+            .expect("order ids are unique");
+            inserted.push((request.side, order.nonce));
+        }
+
+        let is_loaded = |order_id: OrderId| loaded.contains(&order_id);
+        if let Some(front) = self.buys.front().map(Order::inner) {
+            if is_loaded(front.nonce) && bid.is_none_or(|bid| front.price > bid) {
+                self.top.buy = Some(front.nonce);
+            }
+        }
+        if let Some(front) = self.sells.front().map(Order::inner) {
+            if is_loaded(front.nonce) && ask.is_none_or(|ask| front.price < ask) {
+                self.top.sell = Some(front.nonce);
+            }
+        }
+        self.nonce = self.nonce.max(snapshot.nonce);
+        Ok(())
+    }
+}
+
+/// A snapshot line could not be parsed
+#[derive(PartialEq, Clone, Debug)]
+pub struct SnapshotParseError(pub String);
+
+impl fmt::Display for SnapshotParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid snapshot line: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for SnapshotParseError {}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "nonce {}", self.nonce)?;
+        for line in config_lines(&self.config) {
+            writeln!(f, "config {line}")?;
+        }
+        for (side, order) in self.orders.iter() {
+            write!(
+                f,
+                "{} {} {} {} {}",
+                side.as_str(),
+                order.nonce,
+                order.trader_id,
+                order.amount,
+                order.price
+            )?;
+            if !order.flags.is_empty() {
+                write!(f, " {}", order.flags)?;
+            }
+            writeln!(f)?;
+        }
+        for trade in self.trades.iter() {
+            write!(
+                f,
+                "trade {} {} {} {} {} {} {} {} {} {}",
+                trade.trade_id,
+                trade.timestamp,
+                trade.price,
+                trade.maker_price,
+                trade.amount,
+                trade.aggressor.as_str(),
+                trade.buyer,
+                trade.buy_order_id,
+                trade.seller,
+                trade.sell_order_id
+            )?;
+            if trade.busted {
+                write!(f, " busted")?;
+            }
+            writeln!(f)?;
+        }
+        for (trader_id, position) in self.positions.iter() {
+            writeln!(
+                f,
+                "position {trader_id} {} {}",
+                position.position, position.balance
+            )?;
+        }
+        let price = |price: Option<f32>| price.map_or("-".to_string(), |price| price.to_string());
+        for stats in self.session_history.iter() {
+            writeln!(
+                f,
+                "history {} {} {} {} {} {}",
+                price(stats.open),
+                price(stats.high),
+                price(stats.low),
+                price(stats.close),
+                stats.trades,
+                stats.volume
+            )?;
+        }
+        writeln!(f, "session {}", self.session_start)
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = SnapshotParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut snapshot = Snapshot::default();
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let invalid = || SnapshotParseError(line.to_string());
+            match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                ["nonce", nonce] => snapshot.nonce = nonce.parse().map_err(|_| invalid())?,
+                ["config", ..] => {
+                    let config = line.strip_prefix("config").unwrap_or_default().trim();
+                    parse_config(&mut snapshot.config, config).ok_or_else(invalid)?;
+                }
+                ["trade", trade_id, timestamp, price, maker_price, amount, aggressor, buyer, buy_order_id, seller, sell_order_id, busted @ ..] =>
+                {
+                    let trade = Trade {
+                        trade_id: trade_id.parse().map_err(|_| invalid())?,
+                        timestamp: timestamp.parse().map_err(|_| invalid())?,
+                        price: price.parse().map_err(|_| invalid())?,
+                        maker_price: maker_price.parse().map_err(|_| invalid())?,
+                        amount: amount.parse().map_err(|_| invalid())?,
+                        aggressor: OrderSide::try_from(*aggressor).map_err(|_| invalid())?,
+                        buyer: buyer.parse().map_err(|_| invalid())?,
+                        buy_order_id: buy_order_id.parse().map_err(|_| invalid())?,
+                        seller: seller.parse().map_err(|_| invalid())?,
+                        sell_order_id: sell_order_id.parse().map_err(|_| invalid())?,
+                        busted: match busted {
+                            [] => false,
+                            ["busted"] => true,
+                            _ => return Err(invalid()),
+                        },
+                    };
+                    // trades are looked up by id
+                    if trade.trade_id != snapshot.trades.len() as TradeId {
+                        return Err(invalid());
+                    }
+                    snapshot.trades.push(trade);
+                }
+                ["position", trader_id, position, balance] => {
+                    snapshot.positions.insert(
+                        trader_id.parse().map_err(|_| invalid())?,
+                        Position {
+                            position: position.parse().map_err(|_| invalid())?,
+                            balance: balance.parse().map_err(|_| invalid())?,
+                        },
+                    );
+                }
+                ["history", open, high, low, close, trades, volume] => {
+                    let price = |price: &str| match price {
+                        "-" => Ok(None),
+                        price => price.parse().map(Some).map_err(|_| invalid()),
+                    };
+                    snapshot.session_history.push(SessionStats {
+                        open: price(open)?,
+                        high: price(high)?,
+                        low: price(low)?,
+                        close: price(close)?,
+                        trades: trades.parse().map_err(|_| invalid())?,
+                        volume: volume.parse().map_err(|_| invalid())?,
+                    });
+                }
+                ["session", trade_id] => {
+                    snapshot.session_start = trade_id.parse().map_err(|_| invalid())?
+                }
+                [side, nonce, trader_id, amount, price, flags @ ..] => snapshot.orders.push((
+                    OrderSide::try_from(*side).map_err(|_| invalid())?,
+                    LimitOrder {
+                        nonce: nonce.parse().map_err(|_| invalid())?,
+                        trader_id: trader_id.parse().map_err(|_| invalid())?,
+                        amount: amount.parse().map_err(|_| invalid())?,
+                        price: price.parse().map_err(|_| invalid())?,
+                        flags: OrderFlags::from_names(flags.iter().copied()).ok_or_else(invalid)?,
+                    },
+                )),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::SimClock, Error, Market, MarketConfig, MatchingPolicy, OrderFlags, OrderRequest,
+        OrderSide, ResidualAllocation, Snapshot, SnapshotError, LOB,
+    };
+
+    #[test]
+    fn snapshot_round_trip() {
+        let mut lob = Market::new(MarketConfig {
+            matching: MatchingPolicy::SizeTime,
+            max_orders_per_trader: Some(10),
+            ..Default::default()
+        });
+        lob.set_clock(SimClock::new(7));
+        lob.submit_order(1, 100, 1.5, OrderSide::Buy).unwrap();
+        lob.submit_order(2, 100, 1.25, OrderSide::Buy).unwrap();
+        lob.submit_order(3, 50, 1.75, OrderSide::Sell).unwrap();
+        lob.submit_order(4, 20, 1.5, OrderSide::Sell).unwrap();
+        let post_only =
+            OrderRequest::new(5, 10, 2.0, OrderSide::Sell).with_flags(OrderFlags::POST_ONLY);
+        lob.place_order(post_only).unwrap();
+
+        lob.submit_order(6, 5, 1.25, OrderSide::Sell).unwrap();
+        lob.bust_trade(1, false).unwrap();
+        lob.end_of_day();
+        lob.end_of_day();
+        lob.submit_order(7, 5, 1.5, OrderSide::Sell).unwrap();
+
+        let snapshot = lob.snapshot();
+        let text = snapshot.to_string();
+        assert!(text.contains("config max-orders-per-trader 10\n"));
+        assert!(text.contains("sell 4 5 10 2 post-only\n"));
+        assert!(text.contains("trade 1 7 1.5 1.5 5 sell 1 0 6 5 busted\n"));
+        assert!(text.contains("history 1.5 1.5 1.5 1.5 1 20\nhistory - - - - 0 0\nsession 2\n"));
+        assert_eq!(text.parse(), Ok(snapshot.clone()));
+
+        let restored = Market::from_snapshot(snapshot).unwrap();
+        assert_eq!(restored.state_hash(), lob.state_hash());
+        assert_eq!(restored.config(), lob.config());
+        assert_eq!(restored.trades(), lob.trades());
+        assert_eq!(restored.position(4), lob.position(4));
+        assert_eq!(restored.trade_stats(), lob.trade_stats());
+        assert_eq!(restored.session_history(), lob.session_history());
+        assert_eq!(restored.session_stats(), lob.session_stats());
+        assert_eq!(restored.session_stats().trades, 1);
+
+        let invalid = Snapshot {
+            config: MarketConfig {
+                matching: MatchingPolicy::ProRata {
+                    top_allocation: 2.0,
+                    residual: ResidualAllocation::Fifo,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(Market::from_snapshot(invalid).is_err());
+        let duplicate = "nonce 2\nbuy 1 7 100 1.5\nbuy 1 8 100 1.25"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            Market::from_snapshot(duplicate).map(|_| ()),
+            Err(SnapshotError::Orders(Error::DuplicateOrder(1)))
+        );
+        assert!("trade 3 0 1 1 5 sell 2 1 6 5".parse::<Snapshot>().is_err());
+    }
+
+    #[test]
+    fn migrate_orders() {
+        let mut lob = Market::default();
+        lob.submit_order(1, 100, 1.5, OrderSide::Buy).unwrap();
+        lob.submit_order(2, 100, 1.5, OrderSide::Buy).unwrap();
+        lob.submit_order(3, 50, 1.75, OrderSide::Sell).unwrap();
+        lob.submit_order(1, 20, 2.0, OrderSide::Sell).unwrap();
+
+        // move trader 1 and 2's orders, leaving trader 3's
+        let bundle = lob.drain_orders(|_, order| order.trader_id != 3);
+        assert_eq!(bundle.orders.len(), 3);
+        assert_eq!(lob.snapshot().orders.len(), 1);
+
+        let mut migrated = Market::new(MarketConfig {
+            matching: MatchingPolicy::SizeTime,
+            ..Default::default()
+        });
+        migrated.load_orders(bundle.clone()).unwrap();
+        assert_eq!(migrated.load_orders(bundle), Err(Error::DuplicateOrder(0)));
+        assert_eq!(migrated.queue_position(1), Some(100));
+        assert_eq!(migrated.submit_order(5, 1, 1.5, OrderSide::Buy), Ok(vec![]));
+        assert_eq!(migrated.get_order(4).map(|order| order.trader_id), Some(5));
+        assert_eq!(migrated.load_orders(lob.drain_orders(|_, _| true)), Ok(()));
+        let crossing = "nonce 9\nsell 8 1 10 1.0".parse().unwrap();
+        assert_eq!(migrated.load_orders(crossing), Err(Error::CrossedBook));
+    }
+
+    #[test]
+    fn loaded_orders_pass_the_target_checks() {
+        let bundle: Snapshot = "nonce 3\nbuy 0 1 10 -2.0\nbuy 1 1 10 -2.0\nsell 2 2 10 -1.0"
+            .parse()
+            .unwrap();
+        let mut lob = Market::new(MarketConfig {
+            allow_negative_prices: true,
+            max_orders_per_level: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(lob.load_orders(bundle.clone()), Err(Error::OrderLimit));
+        assert_eq!(lob.snapshot().orders, vec![]);
+        let mut lob = Market::new(MarketConfig {
+            max_orders_per_trader: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(
+            lob.load_orders(bundle.clone()),
+            Err(Error::InvalidPrice(-2.0))
+        );
+        assert_eq!(lob.snapshot().orders, vec![]);
+
+        // the first order at a better best price gets the pro-rata top allocation
+        let mut lob = Market::new(MarketConfig {
+            matching: MatchingPolicy::ProRata {
+                top_allocation: 0.5,
+                residual: ResidualAllocation::Fifo,
+            },
+            ..Default::default()
+        });
+        let bundle = "nonce 3\nbuy 0 1 10 1.5\nbuy 1 2 30 1.5".parse().unwrap();
+        lob.load_orders(bundle).unwrap();
+        let fills = lob.submit_order(3, 20, 1.5, OrderSide::Sell).unwrap();
+        let filled: Vec<_> = fills
+            .iter()
+            .filter(|fill| fill.side == OrderSide::Buy)
+            .map(|fill| (fill.order_id, fill.amount))
+            .collect();
+        assert_eq!(filled, vec![(0, 10), (1, 10)]);
+    }
+}
@@ -0,0 +1,563 @@
+//! Persistent storage of the command journal and snapshots
+//!
+//! A market is recovered by loading the latest snapshot and replaying the entries journaled
+//! after it on a `SimClock`. Entries are commands and the clock ticks between them, in their
+//! session text form, so a `Journal` reproduces trade timestamps and funding. Writing a
+//! snapshot truncates the journal. Snapshots hold the market's config and ledger, before the
+//! first snapshot recovery starts from the config passed to `recover`.
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    clock::SimClock, session::Entry, Command, ConfigError, Event, Market, MarketConfig, Snapshot,
+    SnapshotError,
+};
+
+/// A durable store for market commands and snapshots
+pub trait Storage {
+    type Error;
+    /// Append a command or clock tick to the journal
+    fn append(&mut self, entry: &Entry) -> Result<(), Self::Error>;
+    /// Store `snapshot` replacing the journal
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), Self::Error>;
+    /// Load the latest snapshot and the entries journaled after it
+    fn load(&mut self) -> Result<(Option<Snapshot>, Vec<Entry>), Self::Error>;
+}
+
+/// Rebuild a market from the contents of `storage`, starting from `config` if nothing was
+/// snapshotted yet
+///
+/// The journal is replayed on a `SimClock` left in place, set the market's clock before
+/// executing further commands.
+pub fn recover<S: Storage>(storage: &mut S, config: MarketConfig) -> Result<Market, S::Error>
+where
+    S::Error: From<ConfigError> + From<SnapshotError>,
+{
+    let (snapshot, entries) = storage.load()?;
+    let mut market = match snapshot {
+        Some(snapshot) => Market::from_snapshot(snapshot)?,
+        None => Market::with_config(config)?,
+    };
+    let clock = SimClock::default();
+    market.set_clock(clock.clone());
+    for entry in entries.iter() {
+        match entry {
+            Entry::Tick(now) => clock.set(*now),
+            Entry::Command(command) => {
+                market.execute(command);
+            }
+        }
+    }
+    Ok(market)
+}
+
+impl<S: Storage> Storage for &mut S {
+    type Error = S::Error;
+    fn append(&mut self, entry: &Entry) -> Result<(), Self::Error> {
+        (**self).append(entry)
+    }
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), Self::Error> {
+        (**self).write_snapshot(snapshot)
+    }
+    fn load(&mut self) -> Result<(Option<Snapshot>, Vec<Entry>), Self::Error> {
+        (**self).load()
+    }
+}
+
+/// Journals commands executed on a market with a clock tick whenever the market's time
+/// changed, as `session::Recorder` does
+pub struct Journal<S: Storage> {
+    storage: S,
+    /// Time of the last journaled tick
+    now: Option<u64>,
+}
+
+impl<S: Storage> Journal<S> {
+    pub fn new(storage: S) -> Self {
+        Journal { storage, now: None }
+    }
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+    /// Journal `command` then execute it on `market`
+    pub fn execute(
+        &mut self,
+        market: &mut Market,
+        command: &Command,
+    ) -> Result<Vec<Event>, S::Error> {
+        let now = market.now();
+        if self.now != Some(now) {
+            self.storage.append(&Entry::Tick(now))?;
+            self.now = Some(now);
+        }
+        self.storage.append(&Entry::Command(command.clone()))?;
+        Ok(market.execute(command))
+    }
+    /// Snapshot `market` replacing the journal
+    pub fn snapshot(&mut self, market: &Market) -> Result<(), S::Error> {
+        self.storage.write_snapshot(&market.snapshot())?;
+        self.now = None;
+        Ok(())
+    }
+}
+
+/// Storage errors
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    /// Stored data could not be decoded
+    Corrupt(String),
+    /// The stored market config is invalid
+    Config(ConfigError),
+    /// The stored snapshot can't be restored
+    Snapshot(SnapshotError),
+    #[cfg(feature = "sled")]
+    Sled(sled::Error),
+    #[cfg(feature = "rocksdb")]
+    Rocks(rocksdb::Error),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io: {err}"),
+            Self::Corrupt(data) => write!(f, "corrupt data: {data:?}"),
+            Self::Config(err) => write!(f, "{err}"),
+            Self::Snapshot(err) => write!(f, "{err}"),
+            #[cfg(feature = "sled")]
+            Self::Sled(err) => write!(f, "sled: {err}"),
+            #[cfg(feature = "rocksdb")]
+            Self::Rocks(err) => write!(f, "rocksdb: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ConfigError> for StorageError {
+    fn from(err: ConfigError) -> Self {
+        Self::Config(err)
+    }
+}
+
+impl From<SnapshotError> for StorageError {
+    fn from(err: SnapshotError) -> Self {
+        Self::Snapshot(err)
+    }
+}
+
+fn decode_entry(line: &str) -> Result<Entry, StorageError> {
+    line.parse()
+        .map_err(|_| StorageError::Corrupt(line.to_string()))
+}
+
+fn decode_snapshot(data: &str) -> Result<Snapshot, StorageError> {
+    data.parse()
+        .map_err(|_| StorageError::Corrupt(data.to_string()))
+}
+
+#[cfg(any(feature = "sled", feature = "rocksdb"))]
+fn decode_utf8(data: &[u8]) -> Result<String, StorageError> {
+    std::str::from_utf8(data)
+        .map(str::to_string)
+        .map_err(|_| StorageError::Corrupt(format!("{data:?}")))
+}
+
+/// Non-durable storage, useful for tests
+#[derive(Default, Debug)]
+pub struct MemoryStorage {
+    snapshot: Option<Snapshot>,
+    journal: Vec<Entry>,
+}
+
+impl Storage for MemoryStorage {
+    type Error = StorageError;
+    fn append(&mut self, entry: &Entry) -> Result<(), Self::Error> {
+        self.journal.push(entry.clone());
+        Ok(())
+    }
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), Self::Error> {
+        self.snapshot = Some(snapshot.clone());
+        self.journal.clear();
+        Ok(())
+    }
+    fn load(&mut self) -> Result<(Option<Snapshot>, Vec<Entry>), Self::Error> {
+        Ok((self.snapshot.clone(), self.journal.clone()))
+    }
+}
+
+/// Stores a text journal and snapshot in a directory
+pub struct FileStorage {
+    dir: PathBuf,
+    journal: File,
+}
+
+impl FileStorage {
+    const JOURNAL: &'static str = "journal";
+    const SNAPSHOT: &'static str = "snapshot";
+    /// Open storage in `dir` creating it if necessary
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(Self::JOURNAL))?;
+        Ok(FileStorage { dir, journal })
+    }
+}
+
+impl Storage for FileStorage {
+    type Error = StorageError;
+    fn append(&mut self, entry: &Entry) -> Result<(), Self::Error> {
+        writeln!(self.journal, "{entry}")?;
+        self.journal.sync_data()?;
+        Ok(())
+    }
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), Self::Error> {
+        // write then rename so a crash never leaves a partial snapshot
+        let tmp = self.dir.join("snapshot.tmp");
+        let mut file = File::create(&tmp)?;
+        write!(file, "{snapshot}")?;
+        file.sync_all()?;
+        fs::rename(tmp, self.dir.join(Self::SNAPSHOT))?;
+        self.journal.set_len(0)?;
+        self.journal.sync_all()?;
+        Ok(())
+    }
+    fn load(&mut self) -> Result<(Option<Snapshot>, Vec<Entry>), Self::Error> {
+        let snapshot = match fs::read_to_string(self.dir.join(Self::SNAPSHOT)) {
+            Ok(data) => Some(decode_snapshot(&data)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        let journal = fs::read(self.dir.join(Self::JOURNAL))?;
+        let mut entries = Vec::<Entry>::default();
+        let mut valid = 0;
+        let mut lines = journal.split_inclusive(|byte| *byte == b'\n').peekable();
+        while let Some(line) = lines.next() {
+            let entry = std::str::from_utf8(line)
+                .map_err(|_| StorageError::Corrupt(String::from_utf8_lossy(line).into_owned()))
+                .and_then(|line| decode_entry(line.trim_end()));
+            // a crash mid append tears the last line, drop it so appends start on a new line
+            if lines.peek().is_none() && (entry.is_err() || !line.ends_with(b"\n")) {
+                self.journal.set_len(valid as u64)?;
+                break;
+            }
+            entries.push(entry?);
+            valid += line.len();
+        }
+        Ok((snapshot, entries))
+    }
+}
+
+/// Stores the journal and snapshot in a sled database
+#[cfg(feature = "sled")]
+pub struct SledStorage {
+    db: sled::Db,
+    journal: sled::Tree,
+}
+
+#[cfg(feature = "sled")]
+impl SledStorage {
+    const SNAPSHOT: &'static str = "snapshot";
+    /// Open storage at `path` creating it if necessary
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, StorageError> {
+        let db = sled::open(path)?;
+        let journal = db.open_tree("journal")?;
+        Ok(SledStorage { db, journal })
+    }
+}
+
+#[cfg(feature = "sled")]
+impl From<sled::Error> for StorageError {
+    fn from(err: sled::Error) -> Self {
+        Self::Sled(err)
+    }
+}
+
+#[cfg(feature = "sled")]
+impl Storage for SledStorage {
+    type Error = StorageError;
+    fn append(&mut self, entry: &Entry) -> Result<(), Self::Error> {
+        // big endian ids keep the journal in insertion order
+        let key = self.db.generate_id()?.to_be_bytes();
+        self.journal.insert(key, entry.to_string().as_bytes())?;
+        self.journal.flush()?;
+        Ok(())
+    }
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), Self::Error> {
+        self.db
+            .insert(Self::SNAPSHOT, snapshot.to_string().as_bytes())?;
+        self.journal.clear()?;
+        self.db.flush()?;
+        Ok(())
+    }
+    fn load(&mut self) -> Result<(Option<Snapshot>, Vec<Entry>), Self::Error> {
+        let snapshot = match self.db.get(Self::SNAPSHOT)? {
+            Some(data) => Some(decode_snapshot(&decode_utf8(&data)?)?),
+            None => None,
+        };
+        let mut entries = Vec::<Entry>::default();
+        for entry in self.journal.iter() {
+            let (_, data) = entry?;
+            entries.push(decode_entry(&decode_utf8(&data)?)?);
+        }
+        Ok((snapshot, entries))
+    }
+}
+
+/// Stores the snapshot and, in its own column family, the journal in a RocksDB database
+#[cfg(feature = "rocksdb")]
+pub struct RocksStorage {
+    db: rocksdb::DB,
+    /// Key of the next journaled command
+    next: u64,
+}
+
+#[cfg(feature = "rocksdb")]
+impl RocksStorage {
+    const SNAPSHOT: &'static str = "snapshot";
+    const JOURNAL: &'static str = "journal";
+    /// Open storage at `path` creating it if necessary
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, StorageError> {
+        let mut options = rocksdb::Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let db = rocksdb::DB::open_cf(&options, path, [Self::JOURNAL])?;
+        let next = match db
+            .iterator_cf(Self::journal(&db), rocksdb::IteratorMode::End)
+            .next()
+        {
+            Some(entry) => Self::decode_key(&entry?.0)? + 1,
+            None => 0,
+        };
+        Ok(RocksStorage { db, next })
+    }
+    fn journal(db: &rocksdb::DB) -> &rocksdb::ColumnFamily {
+        db.cf_handle(Self::JOURNAL)
+            .expect("journal column family is open")
+    }
+    fn decode_key(key: &[u8]) -> Result<u64, StorageError> {
+        <[u8; 8]>::try_from(key)
+            .map(u64::from_be_bytes)
+            .map_err(|_| StorageError::Corrupt(format!("{key:?}")))
+    }
+    fn synced() -> rocksdb::WriteOptions {
+        let mut options = rocksdb::WriteOptions::default();
+        options.set_sync(true);
+        options
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+impl From<rocksdb::Error> for StorageError {
+    fn from(err: rocksdb::Error) -> Self {
+        Self::Rocks(err)
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+impl Storage for RocksStorage {
+    type Error = StorageError;
+    fn append(&mut self, entry: &Entry) -> Result<(), Self::Error> {
+        // big endian keys keep the journal in insertion order
+        self.db.put_cf_opt(
+            Self::journal(&self.db),
+            self.next.to_be_bytes(),
+            entry.to_string(),
+            &Self::synced(),
+        )?;
+        self.next += 1;
+        Ok(())
+    }
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), Self::Error> {
+        // one batch so the snapshot and journal truncation apply atomically
+        let mut batch = rocksdb::WriteBatch::default();
+        batch.put(Self::SNAPSHOT, snapshot.to_string());
+        batch.delete_range_cf(
+            Self::journal(&self.db),
+            0_u64.to_be_bytes(),
+            self.next.to_be_bytes(),
+        );
+        self.db.write_opt(batch, &Self::synced())?;
+        Ok(())
+    }
+    fn load(&mut self) -> Result<(Option<Snapshot>, Vec<Entry>), Self::Error> {
+        let snapshot = match self.db.get(Self::SNAPSHOT)? {
+            Some(data) => Some(decode_snapshot(&decode_utf8(&data)?)?),
+            None => None,
+        };
+        let mut entries = Vec::<Entry>::default();
+        for entry in self
+            .db
+            .iterator_cf(Self::journal(&self.db), rocksdb::IteratorMode::Start)
+        {
+            let (_, data) = entry?;
+            entries.push(decode_entry(&decode_utf8(&data)?)?);
+        }
+        Ok((snapshot, entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{recover, FileStorage, Journal, MemoryStorage, Storage};
+    use crate::{
+        clock::SimClock, session::Entry, Command, Market, MarketConfig, OrderRequest, OrderSide,
+    };
+
+    fn config() -> MarketConfig {
+        MarketConfig {
+            max_orders_per_trader: Some(5),
+            ..Default::default()
+        }
+    }
+
+    fn assert_recovered(recovered: &Market, market: &Market) {
+        assert_eq!(recovered.state_hash(), market.state_hash());
+        assert_eq!(recovered.config(), market.config());
+        assert_eq!(recovered.trades(), market.trades());
+        assert_eq!(recovered.trade_stats(), market.trade_stats());
+    }
+
+    /// Journal commands to `storage` on a simulated clock, snapshotting midway
+    fn run<S: Storage>(storage: &mut S) -> Market
+    where
+        S::Error: std::fmt::Debug,
+    {
+        let clock = SimClock::new(1_000);
+        let mut market = Market::new(config());
+        market.set_clock(clock.clone());
+        let mut journal = Journal::new(storage);
+        let commands = [
+            Command::Submit(OrderRequest::new(1, 100, 1.5, OrderSide::Sell)),
+            Command::Submit(OrderRequest::new(2, 50, 1.25, OrderSide::Buy)),
+            Command::Submit(OrderRequest::new(3, 30, 1.5, OrderSide::Buy)),
+        ];
+        for command in commands.iter() {
+            journal.execute(&mut market, command).unwrap();
+            clock.advance(Duration::from_secs(1));
+        }
+        journal.snapshot(&market).unwrap();
+
+        clock.advance(Duration::from_secs(60));
+        for command in [
+            Command::Submit(OrderRequest::new(4, 20, 1.5, OrderSide::Buy)),
+            Command::Cancel(1),
+            Command::Cancel(5),
+        ] {
+            journal.execute(&mut market, &command).unwrap();
+        }
+        assert_eq!(
+            market.trades().last().map(|trade| trade.timestamp),
+            Some(63_000_001_000)
+        );
+        market
+    }
+
+    #[test]
+    fn memory_storage_recovers() {
+        let mut storage = MemoryStorage::default();
+        let market = run(&mut storage);
+        // a tick then the journaled commands
+        assert_eq!(storage.load().unwrap().1.len(), 4);
+        let mut recovered = recover(&mut storage, MarketConfig::default()).unwrap();
+        assert_recovered(&recovered, &market);
+        assert!(recovered.bust_trade(0, false).is_ok());
+    }
+
+    #[test]
+    fn recovers_config_without_snapshot() {
+        let mut storage = MemoryStorage::default();
+        let mut market = Market::new(config());
+        let mut journal = Journal::new(&mut storage);
+        for nonce in 0..6 {
+            let command = Command::Submit(OrderRequest::new(
+                1,
+                10,
+                1.0 + nonce as f32,
+                OrderSide::Sell,
+            ));
+            journal.execute(&mut market, &command).unwrap();
+        }
+        assert_eq!(market.orders_for(1).len(), 5);
+        assert_recovered(&recover(&mut storage, config()).unwrap(), &market);
+    }
+
+    #[test]
+    fn file_storage_recovers() {
+        let dir = std::env::temp_dir().join(format!("simple-lob-storage-{}", std::process::id()));
+        let market = run(&mut FileStorage::open(&dir).unwrap());
+
+        // reopen as if after a restart
+        let mut storage = FileStorage::open(&dir).unwrap();
+        assert_recovered(&recover(&mut storage, config()).unwrap(), &market);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn file_storage_drops_torn_last_line() {
+        let dir = std::env::temp_dir().join(format!("simple-lob-torn-{}", std::process::id()));
+        let market = run(&mut FileStorage::open(&dir).unwrap());
+        // a crash mid append
+        let path = dir.join(FileStorage::JOURNAL);
+        let mut journal = std::fs::read(&path).unwrap();
+        journal.extend_from_slice(b"submit 5 bu");
+        std::fs::write(&path, journal).unwrap();
+
+        let mut storage = FileStorage::open(&dir).unwrap();
+        let mut recovered = recover(&mut storage, config()).unwrap();
+        assert_recovered(&recovered, &market);
+        // journaling resumes on a fresh line
+        let command = Command::Cancel(0);
+        storage.append(&Entry::Command(command.clone())).unwrap();
+        recovered.execute(&command);
+        assert_recovered(&recover(&mut storage, config()).unwrap(), &recovered);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_storage_recovers() {
+        let dir = std::env::temp_dir().join(format!("simple-lob-sled-{}", std::process::id()));
+        // reuse the handle, sled's background flusher may hold the db lock after a drop
+        let mut storage = super::SledStorage::open(&dir).unwrap();
+        let market = run(&mut storage);
+        assert_recovered(&recover(&mut storage, config()).unwrap(), &market);
+        drop(storage);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn rocksdb_storage_recovers() {
+        let dir = std::env::temp_dir().join(format!("simple-lob-rocksdb-{}", std::process::id()));
+        let market = run(&mut super::RocksStorage::open(&dir).unwrap());
+
+        // reopen as if after a restart, journaling continues after the recovered entries
+        let mut storage = super::RocksStorage::open(&dir).unwrap();
+        let mut recovered = recover(&mut storage, config()).unwrap();
+        assert_recovered(&recovered, &market);
+        let command = Command::Cancel(0);
+        Journal::new(&mut storage)
+            .execute(&mut recovered, &command)
+            .unwrap();
+        assert_eq!(storage.load().unwrap().1.len(), 6);
+        assert_recovered(&recover(&mut storage, config()).unwrap(), &recovered);
+        drop(storage);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
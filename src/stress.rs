@@ -0,0 +1,183 @@
+//! Multi-threaded stress testing of `SharedMarket`
+//!
+//! Producer threads submit and cancel orders concurrently on a new market. Afterwards the run
+//! is checked for gap free sequence numbers and replayed single threaded in sequence order on a
+//! market with the same config, which must produce identical events and the same final book
+//! state.
+use std::{
+    fmt, thread,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{Command, Event, Market, MarketConfig, OrderRequest, OrderSide, SharedMarket};
+
+/// Stress run parameters
+#[derive(Clone, Debug)]
+pub struct StressConfig {
+    /// Number of producer threads
+    pub threads: usize,
+    /// Commands sent by each thread
+    pub operations: usize,
+    /// Fraction of commands which cancel one of the thread's earlier orders
+    pub cancel_ratio: f32,
+    pub seed: u64,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        StressConfig {
+            threads: 4,
+            operations: 10_000,
+            cancel_ratio: 0.3,
+            seed: 0,
+        }
+    }
+}
+
+/// Summary of a successful run
+#[derive(Clone, Debug)]
+pub struct StressReport {
+    /// Total commands executed
+    pub operations: usize,
+    pub fills: usize,
+    pub elapsed: Duration,
+}
+
+/// Consistency violations found by a run
+#[derive(PartialEq, Clone, Debug)]
+pub enum StressError {
+    /// Sequence numbers were not `0..operations`, `seq` is the first unexpected one
+    SequenceGap { expected: u64, seq: u64 },
+    /// Replaying the command at `seq` produced different events
+    Divergence { seq: u64 },
+    /// Final state differs from the single threaded replay
+    StateMismatch,
+}
+
+impl fmt::Display for StressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SequenceGap { expected, seq } => {
+                write!(f, "sequence gap: expected {expected} got {seq}")
+            }
+            Self::Divergence { seq } => write!(f, "replay diverged at sequence {seq}"),
+            Self::StateMismatch => write!(f, "final state differs from replay"),
+        }
+    }
+}
+
+impl std::error::Error for StressError {}
+
+type Record = (u64, Command, Vec<Event>);
+
+fn producer(market: SharedMarket, config: StressConfig, thread_id: usize) -> Vec<Record> {
+    let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(thread_id as u64));
+    let mut placed = Vec::default();
+    let mut records = Vec::with_capacity(config.operations);
+    for _ in 0..config.operations {
+        let command = if !placed.is_empty() && rng.gen::<f32>() < config.cancel_ratio {
+            Command::Cancel(placed.swap_remove(rng.gen_range(0..placed.len())))
+        } else {
+            let side = if rng.gen() {
+                OrderSide::Buy
+            } else {
+                OrderSide::Sell
+            };
+            Command::Submit(OrderRequest::new(
+                thread_id as u32,
+                rng.gen_range(1..=100),
+                rng.gen_range(90..=110) as f32,
+                side,
+            ))
+        };
+        let (seq, events) = market.execute(&command);
+        if let Some(Event::Accepted(order_id)) = events.first() {
+            placed.push(*order_id);
+        }
+        records.push((seq, command, events));
+    }
+    records
+}
+
+/// Drive a new market with `market` config from `config.threads` producers and verify the
+/// result
+pub fn run(market: MarketConfig, config: StressConfig) -> Result<StressReport, StressError> {
+    let mut replay = Market::new(market.clone());
+    let market = SharedMarket::new(Market::new(market));
+    let start = Instant::now();
+    let handles: Vec<_> = (0..config.threads)
+        .map(|thread_id| {
+            let (market, config) = (market.clone(), config.clone());
+            thread::spawn(move || producer(market, config, thread_id))
+        })
+        .collect();
+    let mut records: Vec<Record> = handles
+        .into_iter()
+        .flat_map(|handle| handle.join().expect("producer panicked"))
+        .collect();
+    let elapsed = start.elapsed();
+
+    records.sort_unstable_by_key(|(seq, _, _)| *seq);
+    for (expected, (seq, _, _)) in records.iter().enumerate() {
+        if *seq != expected as u64 {
+            return Err(StressError::SequenceGap {
+                expected: expected as u64,
+                seq: *seq,
+            });
+        }
+    }
+
+    let mut fills = 0;
+    for (seq, command, events) in records.iter() {
+        if replay.execute(command) != *events {
+            return Err(StressError::Divergence { seq: *seq });
+        }
+        fills += events
+            .iter()
+            .filter(|event| matches!(event, Event::Fill(_)))
+            .count();
+    }
+    if market.with(Market::state_hash) != replay.state_hash() {
+        return Err(StressError::StateMismatch);
+    }
+
+    Ok(StressReport {
+        operations: records.len(),
+        fills,
+        elapsed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, StressConfig};
+    use crate::{MarketConfig, MatchBudget, MatchingPolicy, ResidualAllocation};
+
+    #[test]
+    fn concurrent_producers() {
+        let config = StressConfig {
+            threads: 4,
+            operations: 2_000,
+            ..Default::default()
+        };
+        let report = run(MarketConfig::default(), config.clone()).unwrap();
+        assert_eq!(report.operations, 8_000);
+        assert!(report.fills > 0);
+
+        // the replay matches with the same config
+        let market = MarketConfig {
+            matching: MatchingPolicy::ProRata {
+                top_allocation: 0.2,
+                residual: ResidualAllocation::Random,
+            },
+            budget: MatchBudget {
+                max_fills: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(run(market, config).is_ok());
+    }
+}
@@ -0,0 +1,192 @@
+//! Wire representations of orders and fills
+//!
+//! These types mirror the JSON shape used by integration layers, e.g. an order:
+//! ```json
+//! { "trader_id": 1, "side": "buy", "amount": 100, "price": 1.5 }
+//! ```
+//! and a fill:
+//! ```json
+//! { "side": "sell", "amount": 100, "price": 1.5, "trader": 2, "counter_party": 1, "order_id": 7,
+//!   "added_liquidity": true, "auction": false, "hidden": false, "stp_decrement": false }
+//! ```
+//! Enable the `serde` feature to (de)serialize them directly.
+use std::fmt;
+
+use crate::{Fill, FillFlags, Liquidity, OrderFlags, OrderRequest, OrderSide};
+
+/// Errors converting from a wire representation
+#[derive(PartialEq, Clone, Debug)]
+pub enum WireError {
+    /// `side` was not one of "buy" or "sell"
+    InvalidSide(String),
+    /// `price` was not a finite number
+    InvalidPrice(f32),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSide(side) => write!(f, "invalid side: {side:?}"),
+            Self::InvalidPrice(price) => write!(f, "invalid price: {price}"),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+impl TryFrom<&str> for OrderSide {
+    type Error = WireError;
+    fn try_from(side: &str) -> Result<Self, Self::Error> {
+        match side {
+            "buy" => Ok(Self::Buy),
+            "sell" => Ok(Self::Sell),
+            _ => Err(WireError::InvalidSide(side.to_string())),
+        }
+    }
+}
+
+fn check_price(price: f32) -> Result<f32, WireError> {
+    if price.is_finite() {
+        Ok(price)
+    } else {
+        Err(WireError::InvalidPrice(price))
+    }
+}
+
+/// Wire representation of an `OrderRequest`
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WireOrder {
+    pub trader_id: u32,
+    pub side: String,
+    pub amount: u32,
+    pub price: f32,
+}
+
+impl TryFrom<WireOrder> for OrderRequest {
+    type Error = WireError;
+    fn try_from(wire: WireOrder) -> Result<Self, Self::Error> {
+        Ok(OrderRequest {
+            trader_id: wire.trader_id,
+            side: OrderSide::try_from(wire.side.as_str())?,
+            amount: wire.amount,
+            price: check_price(wire.price)?,
+            flags: OrderFlags::empty(),
+        })
+    }
+}
+
+impl From<OrderRequest> for WireOrder {
+    fn from(request: OrderRequest) -> Self {
+        WireOrder {
+            trader_id: request.trader_id,
+            side: request.side.as_str().to_string(),
+            amount: request.amount,
+            price: request.price,
+        }
+    }
+}
+
+/// Wire representation of a `Fill`
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WireFill {
+    pub side: String,
+    pub amount: u32,
+    pub price: f32,
+    pub trader: u32,
+    pub counter_party: u32,
+    pub order_id: u64,
+    pub added_liquidity: bool,
+    pub auction: bool,
+    pub hidden: bool,
+    pub stp_decrement: bool,
+}
+
+impl TryFrom<WireFill> for Fill {
+    type Error = WireError;
+    fn try_from(wire: WireFill) -> Result<Self, Self::Error> {
+        let liquidity = if wire.added_liquidity {
+            Liquidity::Added
+        } else {
+            Liquidity::Removed
+        };
+        Ok(Fill {
+            flags: FillFlags {
+                liquidity,
+                auction: wire.auction,
+                hidden: wire.hidden,
+                stp_decrement: wire.stp_decrement,
+                order: OrderFlags::empty(),
+            },
+            ..Fill::new(
+                wire.amount,
+                check_price(wire.price)?,
+                OrderSide::try_from(wire.side.as_str())?,
+                wire.trader,
+                wire.counter_party,
+                wire.order_id,
+            )
+        })
+    }
+}
+
+impl From<&Fill> for WireFill {
+    fn from(fill: &Fill) -> Self {
+        WireFill {
+            side: fill.side.as_str().to_string(),
+            amount: fill.amount,
+            price: fill.price,
+            trader: fill.trader,
+            counter_party: fill.counter_party,
+            order_id: fill.order_id,
+            added_liquidity: fill.flags.liquidity == Liquidity::Added,
+            auction: fill.flags.auction,
+            hidden: fill.flags.hidden,
+            stp_decrement: fill.flags.stp_decrement,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WireError, WireFill, WireOrder};
+    use crate::{Fill, Liquidity, OrderRequest, OrderSide};
+
+    #[test]
+    fn order_round_trip() {
+        let request = OrderRequest::new(1, 100, 1.5, OrderSide::Buy);
+        let wire = WireOrder::from(request.clone());
+        assert_eq!(wire.side, "buy");
+        assert_eq!(OrderRequest::try_from(wire), Ok(request));
+    }
+
+    #[test]
+    fn fill_round_trip() {
+        let fill = Fill::new(100, 1.5, OrderSide::Sell, 2, 1, 7).with_liquidity(Liquidity::Added);
+        let wire = WireFill::from(&fill);
+        assert_eq!(Fill::try_from(wire), Ok(fill));
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        let wire = WireOrder {
+            trader_id: 1,
+            side: "hold".to_string(),
+            amount: 1,
+            price: 1.0,
+        };
+        assert_eq!(
+            OrderRequest::try_from(wire.clone()),
+            Err(WireError::InvalidSide("hold".to_string()))
+        );
+        assert!(matches!(
+            OrderRequest::try_from(WireOrder {
+                side: "sell".to_string(),
+                price: f32::NAN,
+                ..wire
+            }),
+            Err(WireError::InvalidPrice(_))
+        ));
+    }
+}